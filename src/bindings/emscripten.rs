@@ -155,6 +155,7 @@ extern "C" {
 	pub fn emscripten_async_wget(url: *const CChar, file: *const CChar, onload: em_str_callback_func, onerror: em_str_callback_func); 
 }
 
+#[cfg(not(feature = "headless"))]
 extern "C" {
 	pub fn emscripten_async_wget_data(url: *const CChar, arg: *mut CVoid, onload: em_async_wget_onload_func, onerror: em_arg_callback_func); 
 }
@@ -542,6 +543,10 @@ pub struct EmscriptenWebGLContextAttributes {
 	pub explicitSwapControl: i32, 
 }
 
+#[cfg(feature = "headless")]
+pub use bindings::headless::emscripten::*;
+
+#[cfg(not(feature = "headless"))]
 extern "C" {
 	pub fn emscripten_webgl_init_context_attributes(attributes: *mut EmscriptenWebGLContextAttributes); 
 	pub fn emscripten_webgl_create_context(target: *const CChar, attributes: *const EmscriptenWebGLContextAttributes) -> EMSCRIPTEN_WEBGL_CONTEXT_HANDLE; 
@@ -549,11 +554,11 @@ extern "C" {
 	pub fn emscripten_webgl_get_current_context () -> EMSCRIPTEN_WEBGL_CONTEXT_HANDLE; 
 	pub fn emscripten_webgl_destroy_context(context: EMSCRIPTEN_WEBGL_CONTEXT_HANDLE) -> i32; 
 	pub fn emscripten_webgl_enable_extension(context: EMSCRIPTEN_WEBGL_CONTEXT_HANDLE, extension: *const CChar) -> i32;
+	pub fn emscripten_set_webglcontextlost_callback(target: *const CChar, userData: *mut CVoid, useCapture: i32, callback: em_webgl_context_callback) -> i32; 
+	pub fn emscripten_set_webglcontextrestored_callback(target: *const CChar, userData: *mut CVoid, useCapture: i32, callback: em_webgl_context_callback) -> i32; 
 }
 
 extern "C" {
-	pub fn emscripten_set_webglcontextlost_callback(target: *const CChar, userData: *mut CVoid, useCapture: i32, callback: em_webgl_context_callback) -> i32; 
-	pub fn emscripten_set_webglcontextrestored_callback(target: *const CChar, userData: *mut CVoid, useCapture: i32, callback: em_webgl_context_callback) -> i32; 
 	pub fn emscripten_is_webgl_context_lost(target: *const CChar) -> i32; 
 	pub fn emscripten_webgl_commit_frame () -> i32; 
 	pub fn emscripten_set_element_css_size(target: *const CChar, width: f64, height: f64) -> i32; 
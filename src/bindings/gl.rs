@@ -118,6 +118,7 @@ pub const ACTIVE_ATTRIBUTES: GLenum = 0x8B89;
 pub const ACTIVE_ATTRIBUTE_MAX_LENGTH: GLenum = 0x8B8A;
 pub const ACTIVE_TEXTURE: GLenum = 0x84E0;
 pub const ACTIVE_UNIFORMS: GLenum = 0x8B86;
+pub const ACTIVE_UNIFORM_BLOCKS: GLenum = 0x8A36;
 pub const ACTIVE_UNIFORM_MAX_LENGTH: GLenum = 0x8B87;
 pub const ALIASED_LINE_WIDTH_RANGE: GLenum = 0x846E;
 pub const ALIASED_POINT_SIZE_RANGE: GLenum = 0x846D;
@@ -147,14 +148,33 @@ pub const BUFFER_USAGE: GLenum = 0x8765;
 pub const BYTE: GLenum = 0x1400;
 pub const CCW: GLenum = 0x0901;
 pub const CLAMP_TO_EDGE: GLenum = 0x812F;
+pub const COLOR: GLenum = 0x1800;
 pub const COLOR_ATTACHMENT0: GLenum = 0x8CE0;
+pub const COLOR_ATTACHMENT1: GLenum = 0x8CE1;
+pub const COLOR_ATTACHMENT10: GLenum = 0x8CEA;
+pub const COLOR_ATTACHMENT11: GLenum = 0x8CEB;
+pub const COLOR_ATTACHMENT12: GLenum = 0x8CEC;
+pub const COLOR_ATTACHMENT13: GLenum = 0x8CED;
+pub const COLOR_ATTACHMENT14: GLenum = 0x8CEE;
+pub const COLOR_ATTACHMENT15: GLenum = 0x8CEF;
+pub const COLOR_ATTACHMENT2: GLenum = 0x8CE2;
+pub const COLOR_ATTACHMENT3: GLenum = 0x8CE3;
+pub const COLOR_ATTACHMENT4: GLenum = 0x8CE4;
+pub const COLOR_ATTACHMENT5: GLenum = 0x8CE5;
+pub const COLOR_ATTACHMENT6: GLenum = 0x8CE6;
+pub const COLOR_ATTACHMENT7: GLenum = 0x8CE7;
+pub const COLOR_ATTACHMENT8: GLenum = 0x8CE8;
+pub const COLOR_ATTACHMENT9: GLenum = 0x8CE9;
 pub const COLOR_BUFFER_BIT: GLenum = 0x00004000;
 pub const COLOR_CLEAR_VALUE: GLenum = 0x0C22;
 pub const COLOR_WRITEMASK: GLenum = 0x0C23;
+pub const COMPARE_REF_TO_TEXTURE: GLenum = 0x884E;
 pub const COMPILE_STATUS: GLenum = 0x8B81;
 pub const COMPRESSED_TEXTURE_FORMATS: GLenum = 0x86A3;
 pub const CONSTANT_ALPHA: GLenum = 0x8003;
 pub const CONSTANT_COLOR: GLenum = 0x8001;
+pub const COPY_READ_BUFFER: GLenum = 0x8F36;
+pub const COPY_WRITE_BUFFER: GLenum = 0x8F37;
 pub const CULL_FACE: GLenum = 0x0B44;
 pub const CULL_FACE_MODE: GLenum = 0x0B45;
 pub const CURRENT_PROGRAM: GLenum = 0x8B8D;
@@ -163,21 +183,47 @@ pub const CW: GLenum = 0x0900;
 pub const DECR: GLenum = 0x1E03;
 pub const DECR_WRAP: GLenum = 0x8508;
 pub const DELETE_STATUS: GLenum = 0x8B80;
+pub const DEPTH: GLenum = 0x1801;
+pub const DEPTH24_STENCIL8: GLenum = 0x88F0;
 pub const DEPTH_ATTACHMENT: GLenum = 0x8D00;
 pub const DEPTH_BITS: GLenum = 0x0D56;
 pub const DEPTH_BUFFER_BIT: GLenum = 0x00000100;
 pub const DEPTH_CLEAR_VALUE: GLenum = 0x0B73;
 pub const DEPTH_COMPONENT: GLenum = 0x1902;
 pub const DEPTH_COMPONENT16: GLenum = 0x81A5;
+pub const DEPTH_COMPONENT24: GLenum = 0x81A6;
+pub const DEPTH_COMPONENT32F: GLenum = 0x8CAC;
 pub const DEPTH_FUNC: GLenum = 0x0B74;
 pub const DEPTH_RANGE: GLenum = 0x0B70;
+pub const DEPTH_STENCIL: GLenum = 0x84F9;
+pub const DEPTH_STENCIL_ATTACHMENT: GLenum = 0x821A;
 pub const DEPTH_TEST: GLenum = 0x0B71;
 pub const DEPTH_WRITEMASK: GLenum = 0x0B72;
 pub const DITHER: GLenum = 0x0BD0;
 pub const DONT_CARE: GLenum = 0x1100;
+pub const DRAW_BUFFER0: GLenum = 0x8825;
+pub const DRAW_BUFFER1: GLenum = 0x8826;
+pub const DRAW_BUFFER10: GLenum = 0x882F;
+pub const DRAW_BUFFER11: GLenum = 0x8830;
+pub const DRAW_BUFFER12: GLenum = 0x8831;
+pub const DRAW_BUFFER13: GLenum = 0x8832;
+pub const DRAW_BUFFER14: GLenum = 0x8833;
+pub const DRAW_BUFFER15: GLenum = 0x8834;
+pub const DRAW_BUFFER2: GLenum = 0x8827;
+pub const DRAW_BUFFER3: GLenum = 0x8828;
+pub const DRAW_BUFFER4: GLenum = 0x8829;
+pub const DRAW_BUFFER5: GLenum = 0x882A;
+pub const DRAW_BUFFER6: GLenum = 0x882B;
+pub const DRAW_BUFFER7: GLenum = 0x882C;
+pub const DRAW_BUFFER8: GLenum = 0x882D;
+pub const DRAW_BUFFER9: GLenum = 0x882E;
+pub const DRAW_FRAMEBUFFER: GLenum = 0x8CA9;
+pub const DRAW_FRAMEBUFFER_BINDING: GLenum = 0x8CA6;
 pub const DST_ALPHA: GLenum = 0x0304;
 pub const DST_COLOR: GLenum = 0x0306;
+pub const DYNAMIC_COPY: GLenum = 0x88EA;
 pub const DYNAMIC_DRAW: GLenum = 0x88E8;
+pub const DYNAMIC_READ: GLenum = 0x88E9;
 pub const ELEMENT_ARRAY_BUFFER: GLenum = 0x8893;
 pub const ELEMENT_ARRAY_BUFFER_BINDING: GLenum = 0x8895;
 pub const EQUAL: GLenum = 0x0202;
@@ -214,6 +260,7 @@ pub const GENERATE_MIPMAP_HINT: GLenum = 0x8192;
 pub const GEQUAL: GLenum = 0x0206;
 pub const GREATER: GLenum = 0x0204;
 pub const GREEN_BITS: GLenum = 0x0D53;
+pub const HALF_FLOAT: GLenum = 0x140B;
 pub const HIGH_FLOAT: GLenum = 0x8DF2;
 pub const HIGH_INT: GLenum = 0x8DF5;
 pub const IMPLEMENTATION_COLOR_READ_FORMAT: GLenum = 0x8B9B;
@@ -227,6 +274,7 @@ pub const INT_VEC3: GLenum = 0x8B54;
 pub const INT_VEC4: GLenum = 0x8B55;
 pub const INVALID_ENUM: GLenum = 0x0500;
 pub const INVALID_FRAMEBUFFER_OPERATION: GLenum = 0x0506;
+pub const INVALID_INDEX: GLenum = 0xFFFFFFFF;
 pub const INVALID_OPERATION: GLenum = 0x0502;
 pub const INVALID_VALUE: GLenum = 0x0501;
 pub const INVERT: GLenum = 0x150A;
@@ -245,15 +293,26 @@ pub const LOW_FLOAT: GLenum = 0x8DF0;
 pub const LOW_INT: GLenum = 0x8DF3;
 pub const LUMINANCE: GLenum = 0x1909;
 pub const LUMINANCE_ALPHA: GLenum = 0x190A;
+pub const MAX_3D_TEXTURE_SIZE: GLenum = 0x8073;
+pub const MAX_ARRAY_TEXTURE_LAYERS: GLenum = 0x88FF;
+pub const MAX_COLOR_ATTACHMENTS: GLenum = 0x8CDF;
 pub const MAX_COMBINED_TEXTURE_IMAGE_UNITS: GLenum = 0x8B4D;
 pub const MAX_CUBE_MAP_TEXTURE_SIZE: GLenum = 0x851C;
+pub const MAX_DRAW_BUFFERS: GLenum = 0x8824;
+pub const MAX_ELEMENTS_INDICES: GLenum = 0x80E9;
+pub const MAX_ELEMENTS_VERTICES: GLenum = 0x80E8;
+pub const MAX_FRAGMENT_UNIFORM_BLOCKS: GLenum = 0x8A2D;
 pub const MAX_FRAGMENT_UNIFORM_VECTORS: GLenum = 0x8DFD;
 pub const MAX_RENDERBUFFER_SIZE: GLenum = 0x84E8;
+pub const MAX_SAMPLES: GLenum = 0x8D57;
 pub const MAX_TEXTURE_IMAGE_UNITS: GLenum = 0x8872;
 pub const MAX_TEXTURE_SIZE: GLenum = 0x0D33;
+pub const MAX_UNIFORM_BLOCK_SIZE: GLenum = 0x8A30;
+pub const MAX_UNIFORM_BUFFER_BINDINGS: GLenum = 0x8A2F;
 pub const MAX_VARYING_VECTORS: GLenum = 0x8DFC;
 pub const MAX_VERTEX_ATTRIBS: GLenum = 0x8869;
 pub const MAX_VERTEX_TEXTURE_IMAGE_UNITS: GLenum = 0x8B4C;
+pub const MAX_VERTEX_UNIFORM_BLOCKS: GLenum = 0x8A2B;
 pub const MAX_VERTEX_UNIFORM_VECTORS: GLenum = 0x8DFB;
 pub const MAX_VIEWPORT_DIMS: GLenum = 0x0D3A;
 pub const MEDIUM_FLOAT: GLenum = 0x8DF1;
@@ -268,6 +327,7 @@ pub const NONE: GLenum = 0;
 pub const NOTEQUAL: GLenum = 0x0205;
 pub const NO_ERROR: GLenum = 0;
 pub const NUM_COMPRESSED_TEXTURE_FORMATS: GLenum = 0x86A2;
+pub const NUM_EXTENSIONS: GLenum = 0x821D;
 pub const NUM_SHADER_BINARY_FORMATS: GLenum = 0x8DF9;
 pub const ONE: GLenum = 1;
 pub const ONE_MINUS_CONSTANT_ALPHA: GLenum = 0x8004;
@@ -282,6 +342,13 @@ pub const POINTS: GLenum = 0x0000;
 pub const POLYGON_OFFSET_FACTOR: GLenum = 0x8038;
 pub const POLYGON_OFFSET_FILL: GLenum = 0x8037;
 pub const POLYGON_OFFSET_UNITS: GLenum = 0x2A00;
+pub const R16F: GLenum = 0x822D;
+pub const R32F: GLenum = 0x822E;
+pub const R8: GLenum = 0x8229;
+pub const READ_BUFFER: GLenum = 0x0C02;
+pub const READ_FRAMEBUFFER: GLenum = 0x8CA8;
+pub const READ_FRAMEBUFFER_BINDING: GLenum = 0x8CAA;
+pub const RED: GLenum = 0x1903;
 pub const RED_BITS: GLenum = 0x0D52;
 pub const RENDERBUFFER: GLenum = 0x8D41;
 pub const RENDERBUFFER_ALPHA_SIZE: GLenum = 0x8D53;
@@ -297,12 +364,26 @@ pub const RENDERBUFFER_WIDTH: GLenum = 0x8D42;
 pub const RENDERER: GLenum = 0x1F01;
 pub const REPEAT: GLenum = 0x2901;
 pub const REPLACE: GLenum = 0x1E01;
+pub const RG: GLenum = 0x8227;
+pub const RG16F: GLenum = 0x822F;
+pub const RG32F: GLenum = 0x8230;
+pub const RG8: GLenum = 0x822B;
 pub const RGB: GLenum = 0x1907;
+pub const RGB16F: GLenum = 0x881B;
+pub const RGB32F: GLenum = 0x8815;
 pub const RGB565: GLenum = 0x8D62;
 pub const RGB5_A1: GLenum = 0x8057;
+pub const RGB8: GLenum = 0x8051;
 pub const RGBA: GLenum = 0x1908;
+pub const RGBA16F: GLenum = 0x881A;
+pub const RGBA32F: GLenum = 0x8814;
 pub const RGBA4: GLenum = 0x8056;
+pub const RGBA8: GLenum = 0x8058;
 pub const SAMPLER_2D: GLenum = 0x8B5E;
+pub const SAMPLER_2D_ARRAY: GLenum = 0x8DC1;
+pub const SAMPLER_2D_SHADOW: GLenum = 0x8B62;
+pub const SAMPLER_3D: GLenum = 0x8B5F;
+pub const SAMPLER_BINDING: GLenum = 0x8919;
 pub const SAMPLER_CUBE: GLenum = 0x8B60;
 pub const SAMPLES: GLenum = 0x80A9;
 pub const SAMPLE_ALPHA_TO_COVERAGE: GLenum = 0x809E;
@@ -321,7 +402,11 @@ pub const SHORT: GLenum = 0x1402;
 pub const SRC_ALPHA: GLenum = 0x0302;
 pub const SRC_ALPHA_SATURATE: GLenum = 0x0308;
 pub const SRC_COLOR: GLenum = 0x0300;
+pub const SRGB8_ALPHA8: GLenum = 0x8C43;
+pub const STATIC_COPY: GLenum = 0x88E6;
 pub const STATIC_DRAW: GLenum = 0x88E4;
+pub const STATIC_READ: GLenum = 0x88E5;
+pub const STENCIL: GLenum = 0x1802;
 pub const STENCIL_ATTACHMENT: GLenum = 0x8D20;
 pub const STENCIL_BACK_FAIL: GLenum = 0x8801;
 pub const STENCIL_BACK_FUNC: GLenum = 0x8800;
@@ -342,7 +427,9 @@ pub const STENCIL_REF: GLenum = 0x0B97;
 pub const STENCIL_TEST: GLenum = 0x0B90;
 pub const STENCIL_VALUE_MASK: GLenum = 0x0B93;
 pub const STENCIL_WRITEMASK: GLenum = 0x0B98;
+pub const STREAM_COPY: GLenum = 0x88E2;
 pub const STREAM_DRAW: GLenum = 0x88E0;
+pub const STREAM_READ: GLenum = 0x88E1;
 pub const SUBPIXEL_BITS: GLenum = 0x0D50;
 pub const TEXTURE: GLenum = 0x1702;
 pub const TEXTURE0: GLenum = 0x84C0;
@@ -378,8 +465,15 @@ pub const TEXTURE7: GLenum = 0x84C7;
 pub const TEXTURE8: GLenum = 0x84C8;
 pub const TEXTURE9: GLenum = 0x84C9;
 pub const TEXTURE_2D: GLenum = 0x0DE1;
+pub const TEXTURE_2D_ARRAY: GLenum = 0x8C1A;
+pub const TEXTURE_3D: GLenum = 0x806F;
+pub const TEXTURE_BASE_LEVEL: GLenum = 0x813C;
 pub const TEXTURE_BINDING_2D: GLenum = 0x8069;
+pub const TEXTURE_BINDING_2D_ARRAY: GLenum = 0x8C1D;
+pub const TEXTURE_BINDING_3D: GLenum = 0x806A;
 pub const TEXTURE_BINDING_CUBE_MAP: GLenum = 0x8514;
+pub const TEXTURE_COMPARE_FUNC: GLenum = 0x884D;
+pub const TEXTURE_COMPARE_MODE: GLenum = 0x884C;
 pub const TEXTURE_CUBE_MAP: GLenum = 0x8513;
 pub const TEXTURE_CUBE_MAP_NEGATIVE_X: GLenum = 0x8516;
 pub const TEXTURE_CUBE_MAP_NEGATIVE_Y: GLenum = 0x8518;
@@ -388,16 +482,34 @@ pub const TEXTURE_CUBE_MAP_POSITIVE_X: GLenum = 0x8515;
 pub const TEXTURE_CUBE_MAP_POSITIVE_Y: GLenum = 0x8517;
 pub const TEXTURE_CUBE_MAP_POSITIVE_Z: GLenum = 0x8519;
 pub const TEXTURE_MAG_FILTER: GLenum = 0x2800;
+pub const TEXTURE_MAX_LEVEL: GLenum = 0x813D;
+pub const TEXTURE_MAX_LOD: GLenum = 0x813B;
 pub const TEXTURE_MIN_FILTER: GLenum = 0x2801;
+pub const TEXTURE_MIN_LOD: GLenum = 0x813A;
+pub const TEXTURE_WRAP_R: GLenum = 0x8072;
 pub const TEXTURE_WRAP_S: GLenum = 0x2802;
 pub const TEXTURE_WRAP_T: GLenum = 0x2803;
 pub const TRIANGLES: GLenum = 0x0004;
 pub const TRIANGLE_FAN: GLenum = 0x0006;
 pub const TRIANGLE_STRIP: GLenum = 0x0005;
 pub const TRUE: GLboolean = 1;
+pub const UNIFORM_BLOCK_ACTIVE_UNIFORMS: GLenum = 0x8A42;
+pub const UNIFORM_BLOCK_BINDING: GLenum = 0x8A3F;
+pub const UNIFORM_BLOCK_DATA_SIZE: GLenum = 0x8A40;
+pub const UNIFORM_BLOCK_INDEX: GLenum = 0x8A3A;
+pub const UNIFORM_BUFFER: GLenum = 0x8A11;
+pub const UNIFORM_BUFFER_BINDING: GLenum = 0x8A28;
+pub const UNIFORM_BUFFER_OFFSET_ALIGNMENT: GLenum = 0x8A34;
+pub const UNIFORM_BUFFER_SIZE: GLenum = 0x8A2A;
+pub const UNIFORM_BUFFER_START: GLenum = 0x8A29;
+pub const UNIFORM_OFFSET: GLenum = 0x8A3B;
 pub const UNPACK_ALIGNMENT: GLenum = 0x0CF5;
 pub const UNSIGNED_BYTE: GLenum = 0x1401;
 pub const UNSIGNED_INT: GLenum = 0x1405;
+pub const UNSIGNED_INT_24_8: GLenum = 0x84FA;
+pub const UNSIGNED_INT_VEC2: GLenum = 0x8DC6;
+pub const UNSIGNED_INT_VEC3: GLenum = 0x8DC7;
+pub const UNSIGNED_INT_VEC4: GLenum = 0x8DC8;
 pub const UNSIGNED_SHORT: GLenum = 0x1403;
 pub const UNSIGNED_SHORT_4_4_4_4: GLenum = 0x8033;
 pub const UNSIGNED_SHORT_5_5_5_1: GLenum = 0x8034;
@@ -405,8 +517,11 @@ pub const UNSIGNED_SHORT_5_6_5: GLenum = 0x8363;
 pub const VALIDATE_STATUS: GLenum = 0x8B83;
 pub const VENDOR: GLenum = 0x1F00;
 pub const VERSION: GLenum = 0x1F02;
+pub const VERTEX_ARRAY_BINDING: GLenum = 0x85B5;
 pub const VERTEX_ATTRIB_ARRAY_BUFFER_BINDING: GLenum = 0x889F;
+pub const VERTEX_ATTRIB_ARRAY_DIVISOR: GLenum = 0x88FE;
 pub const VERTEX_ATTRIB_ARRAY_ENABLED: GLenum = 0x8622;
+pub const VERTEX_ATTRIB_ARRAY_INTEGER: GLenum = 0x88FD;
 pub const VERTEX_ATTRIB_ARRAY_NORMALIZED: GLenum = 0x886A;
 pub const VERTEX_ATTRIB_ARRAY_POINTER: GLenum = 0x8645;
 pub const VERTEX_ATTRIB_ARRAY_SIZE: GLenum = 0x8623;
@@ -416,23 +531,36 @@ pub const VERTEX_SHADER: GLenum = 0x8B31;
 pub const VIEWPORT: GLenum = 0x0BA2;
 pub const ZERO: GLenum = 0;
 
+#[cfg(feature = "headless")]
+pub use bindings::headless::gl::*;
+
+#[cfg(not(feature = "headless"))]
 extern "system" {
 #[link_name="glActiveTexture"]              pub fn ActiveTexture(texture: GLenum);
 #[link_name="glAttachShader"]               pub fn AttachShader(program: u32, shader: u32);
 #[link_name="glBindAttribLocation"]         pub fn BindAttribLocation(program: u32, index: u32, name: *const GLchar);
 #[link_name="glBindBuffer"]                 pub fn BindBuffer(target: GLenum, buffer: u32);
+#[link_name="glBindBufferBase"]             pub fn BindBufferBase(target: GLenum, index: u32, buffer: u32);
+#[link_name="glBindBufferRange"]            pub fn BindBufferRange(target: GLenum, index: u32, buffer: u32, offset: GLintptr, size: GLsizeiptr);
 #[link_name="glBindFramebuffer"]            pub fn BindFramebuffer(target: GLenum, framebuffer: u32);
 #[link_name="glBindRenderbuffer"]           pub fn BindRenderbuffer(target: GLenum, renderbuffer: u32);
+#[link_name="glBindSampler"]                pub fn BindSampler(unit: u32, sampler: u32);
 #[link_name="glBindTexture"]                pub fn BindTexture(target: GLenum, texture: u32);
+#[link_name="glBindVertexArray"]            pub fn BindVertexArray(array: u32);
 #[link_name="glBlendColor"]                 pub fn BlendColor(red: f32, green: f32, blue: f32, alpha: f32);
 #[link_name="glBlendEquation"]              pub fn BlendEquation(mode: GLenum);
 #[link_name="glBlendEquationSeparate"]      pub fn BlendEquationSeparate(modeRGB: GLenum, modeAlpha: GLenum);
 #[link_name="glBlendFunc"]                  pub fn BlendFunc(sfactor: GLenum, dfactor: GLenum);
 #[link_name="glBlendFuncSeparate"]          pub fn BlendFuncSeparate(sfactorRGB: GLenum, dfactorRGB: GLenum, sfactorAlpha: GLenum, dfactorAlpha: GLenum);
+#[link_name="glBlitFramebuffer"]            pub fn BlitFramebuffer(srcX0: i32, srcY0: i32, srcX1: i32, srcY1: i32, dstX0: i32, dstY0: i32, dstX1: i32, dstY1: i32, mask: GLbitfield, filter: GLenum);
 #[link_name="glBufferData"]                 pub fn BufferData(target: GLenum, size: i32, data: *const GLvoid, usage: GLenum);
 #[link_name="glBufferSubData"]              pub fn BufferSubData(target: GLenum, offset: i32, size: i32, data: *const GLvoid);
 #[link_name="glCheckFramebufferStatus"]     pub fn CheckFramebufferStatus(target: GLenum) -> GLenum;
 #[link_name="glClear"]                      pub fn Clear(mask: GLbitfield);
+#[link_name="glClearBufferfi"]              pub fn ClearBufferfi(buffer: GLenum, drawbuffer: i32, depth: f32, stencil: i32);
+#[link_name="glClearBufferfv"]              pub fn ClearBufferfv(buffer: GLenum, drawbuffer: i32, value: *const f32);
+#[link_name="glClearBufferiv"]              pub fn ClearBufferiv(buffer: GLenum, drawbuffer: i32, value: *const i32);
+#[link_name="glClearBufferuiv"]             pub fn ClearBufferuiv(buffer: GLenum, drawbuffer: i32, value: *const u32);
 #[link_name="glClearColor"]                 pub fn ClearColor(red: f32, green: f32, blue: f32, alpha: f32);
 #[link_name="glClearDepthf"]                pub fn ClearDepthf(d: f32);
 #[link_name="glClearStencil"]               pub fn ClearStencil(s: i32);
@@ -440,8 +568,10 @@ extern "system" {
 #[link_name="glCompileShader"]              pub fn CompileShader(shader: u32);
 #[link_name="glCompressedTexImage2D"]       pub fn CompressedTexImage2D(target: GLenum, level: i32, internalformat: GLenum, width: i32, height: i32, border: i32, imageSize: i32, data: *const GLvoid);
 #[link_name="glCompressedTexSubImage2D"]    pub fn CompressedTexSubImage2D(target: GLenum, level: i32, xoffset: i32, yoffset: i32, width: i32, height: i32, format: GLenum, imageSize: i32, data: *const GLvoid);
+#[link_name="glCopyBufferSubData"]          pub fn CopyBufferSubData(readTarget: GLenum, writeTarget: GLenum, readOffset: GLintptr, writeOffset: GLintptr, size: GLsizeiptr);
 #[link_name="glCopyTexImage2D"]             pub fn CopyTexImage2D(target: GLenum, level: i32, internalformat: GLenum, x: i32, y: i32, width: i32, height: i32, border: i32);
 #[link_name="glCopyTexSubImage2D"]          pub fn CopyTexSubImage2D(target: GLenum, level: i32, xoffset: i32, yoffset: i32, x: i32, y: i32, width: i32, height: i32);
+#[link_name="glCopyTexSubImage3D"]          pub fn CopyTexSubImage3D(target: GLenum, level: i32, xoffset: i32, yoffset: i32, zoffset: i32, x: i32, y: i32, width: i32, height: i32);
 #[link_name="glCreateProgram"]              pub fn CreateProgram() -> u32;
 #[link_name="glCreateShader"]               pub fn CreateShader(type_: GLenum) -> u32;
 #[link_name="glCullFace"]                   pub fn CullFace(mode: GLenum);
@@ -449,8 +579,10 @@ extern "system" {
 #[link_name="glDeleteFramebuffers"]         pub fn DeleteFramebuffers(n: i32, framebuffers: *const u32);
 #[link_name="glDeleteProgram"]              pub fn DeleteProgram(program: u32);
 #[link_name="glDeleteRenderbuffers"]        pub fn DeleteRenderbuffers(n: i32, renderbuffers: *const u32);
+#[link_name="glDeleteSamplers"]             pub fn DeleteSamplers(count: i32, samplers: *const u32);
 #[link_name="glDeleteShader"]               pub fn DeleteShader(shader: u32);
 #[link_name="glDeleteTextures"]             pub fn DeleteTextures(n: i32, textures: *const u32);
+#[link_name="glDeleteVertexArrays"]         pub fn DeleteVertexArrays(n: i32, arrays: *const u32);
 #[link_name="glDepthFunc"]                  pub fn DepthFunc(func: GLenum);
 #[link_name="glDepthMask"]                  pub fn DepthMask(flag: GLboolean);
 #[link_name="glDepthRangef"]                pub fn DepthRangef(n: f32, f: f32);
@@ -458,21 +590,31 @@ extern "system" {
 #[link_name="glDisable"]                    pub fn Disable(cap: GLenum);
 #[link_name="glDisableVertexAttribArray"]   pub fn DisableVertexAttribArray(index: u32);
 #[link_name="glDrawArrays"]                 pub fn DrawArrays(mode: GLenum, first: i32, count: i32);
+#[link_name="glDrawArraysInstanced"]        pub fn DrawArraysInstanced(mode: GLenum, first: i32, count: i32, instancecount: i32);
+#[link_name="glDrawBuffers"]                pub fn DrawBuffers(n: i32, bufs: *const GLenum);
 #[link_name="glDrawElements"]               pub fn DrawElements(mode: GLenum, count: i32, type_: GLenum, indices: *const GLvoid);
+#[link_name="glDrawElementsInstanced"]      pub fn DrawElementsInstanced(mode: GLenum, count: i32, type_: GLenum, indices: *const GLvoid, instancecount: i32);
+#[link_name="glDrawRangeElements"]          pub fn DrawRangeElements(mode: GLenum, start: u32, end: u32, count: i32, type_: GLenum, indices: *const GLvoid);
 #[link_name="glEnable"]                     pub fn Enable(cap: GLenum);
 #[link_name="glEnableVertexAttribArray"]    pub fn EnableVertexAttribArray(index: u32);
 #[link_name="glFinish"]                     pub fn Finish();
 #[link_name="glFlush"]                      pub fn Flush();
 #[link_name="glFramebufferRenderbuffer"]    pub fn FramebufferRenderbuffer(target: GLenum, attachment: GLenum, renderbuffertarget: GLenum, renderbuffer: u32);
 #[link_name="glFramebufferTexture2D"]       pub fn FramebufferTexture2D(target: GLenum, attachment: GLenum, textarget: GLenum, texture: u32, level: i32);
+#[link_name="glFramebufferTextureLayer"]    pub fn FramebufferTextureLayer(target: GLenum, attachment: GLenum, texture: u32, level: i32, layer: i32);
 #[link_name="glFrontFace"]                  pub fn FrontFace(mode: GLenum);
 #[link_name="glGenBuffers"]                 pub fn GenBuffers(n: i32, buffers: *mut u32);
 #[link_name="glGenFramebuffers"]            pub fn GenFramebuffers(n: i32, framebuffers: *mut u32);
 #[link_name="glGenRenderbuffers"]           pub fn GenRenderbuffers(n: i32, renderbuffers: *mut u32);
+#[link_name="glGenSamplers"]                pub fn GenSamplers(count: i32, samplers: *mut u32);
 #[link_name="glGenTextures"]                pub fn GenTextures(n: i32, textures: *mut u32);
+#[link_name="glGenVertexArrays"]            pub fn GenVertexArrays(n: i32, arrays: *mut u32);
 #[link_name="glGenerateMipmap"]             pub fn GenerateMipmap(target: GLenum);
 #[link_name="glGetActiveAttrib"]            pub fn GetActiveAttrib(program: u32, index: u32, bufSize: i32, length: *mut i32, size: *mut i32, type_: *mut GLenum, name: *mut GLchar);
 #[link_name="glGetActiveUniform"]           pub fn GetActiveUniform(program: u32, index: u32, bufSize: i32, length: *mut i32, size: *mut i32, type_: *mut GLenum, name: *mut GLchar);
+#[link_name="glGetActiveUniformBlockName"]  pub fn GetActiveUniformBlockName(program: u32, uniformBlockIndex: u32, bufSize: i32, length: *mut i32, uniformBlockName: *mut GLchar);
+#[link_name="glGetActiveUniformBlockiv"]    pub fn GetActiveUniformBlockiv(program: u32, uniformBlockIndex: u32, pname: GLenum, params: *mut i32);
+#[link_name="glGetActiveUniformsiv"]        pub fn GetActiveUniformsiv(program: u32, uniformCount: i32, uniformIndices: *const u32, pname: GLenum, params: *mut i32);
 #[link_name="glGetAttachedShaders"]         pub fn GetAttachedShaders(program: u32, maxCount: i32, count: *mut i32, shaders: *mut u32);
 #[link_name="glGetAttribLocation"]          pub fn GetAttribLocation(program: u32, name: *const GLchar) -> i32;
 #[link_name="glGetBooleanv"]                pub fn GetBooleanv(pname: GLenum, data: *mut GLboolean);
@@ -484,13 +626,18 @@ extern "system" {
 #[link_name="glGetProgramInfoLog"]          pub fn GetProgramInfoLog(program: u32, bufSize: i32, length: *mut i32, infoLog: *mut GLchar);
 #[link_name="glGetProgramiv"]               pub fn GetProgramiv(program: u32, pname: GLenum, params: *mut i32);
 #[link_name="glGetRenderbufferParameteriv"] pub fn GetRenderbufferParameteriv(target: GLenum, pname: GLenum, params: *mut i32);
+#[link_name="glGetSamplerParameterfv"]      pub fn GetSamplerParameterfv(sampler: u32, pname: GLenum, params: *mut f32);
+#[link_name="glGetSamplerParameteriv"]      pub fn GetSamplerParameteriv(sampler: u32, pname: GLenum, params: *mut i32);
 #[link_name="glGetShaderInfoLog"]           pub fn GetShaderInfoLog(shader: u32, bufSize: i32, length: *mut i32, infoLog: *mut GLchar);
 #[link_name="glGetShaderPrecisionFormat"]   pub fn GetShaderPrecisionFormat(shadertype: GLenum, precisiontype: GLenum, range: *mut i32, precision: *mut i32);
 #[link_name="glGetShaderSource"]            pub fn GetShaderSource(shader: u32, bufSize: i32, length: *mut i32, source: *mut GLchar);
 #[link_name="glGetShaderiv"]                pub fn GetShaderiv(shader: u32, pname: GLenum, params: *mut i32);
 #[link_name="glGetString"]                  pub fn GetString(name: GLenum) -> *const GLubyte;
+#[link_name="glGetStringi"]                 pub fn GetStringi(name: GLenum, index: u32) -> *const GLubyte;
 #[link_name="glGetTexParameterfv"]          pub fn GetTexParameterfv(target: GLenum, pname: GLenum, params: *mut f32);
 #[link_name="glGetTexParameteriv"]          pub fn GetTexParameteriv(target: GLenum, pname: GLenum, params: *mut i32);
+#[link_name="glGetUniformBlockIndex"]       pub fn GetUniformBlockIndex(program: u32, uniformBlockName: *const GLchar) -> u32;
+#[link_name="glGetUniformIndices"]          pub fn GetUniformIndices(program: u32, uniformCount: i32, uniformNames: *const *const GLchar, uniformIndices: *mut u32);
 #[link_name="glGetUniformLocation"]         pub fn GetUniformLocation(program: u32, name: *const GLchar) -> i32;
 #[link_name="glGetUniformfv"]               pub fn GetUniformfv(program: u32, location: i32, params: *mut f32);
 #[link_name="glGetUniformiv"]               pub fn GetUniformiv(program: u32, location: i32, params: *mut i32);
@@ -503,16 +650,24 @@ extern "system" {
 #[link_name="glIsFramebuffer"]              pub fn IsFramebuffer(framebuffer: u32) -> GLboolean;
 #[link_name="glIsProgram"]                  pub fn IsProgram(program: u32) -> GLboolean;
 #[link_name="glIsRenderbuffer"]             pub fn IsRenderbuffer(renderbuffer: u32) -> GLboolean;
+#[link_name="glIsSampler"]                  pub fn IsSampler(sampler: u32) -> GLboolean;
 #[link_name="glIsShader"]                   pub fn IsShader(shader: u32) -> GLboolean;
 #[link_name="glIsTexture"]                  pub fn IsTexture(texture: u32) -> GLboolean;
+#[link_name="glIsVertexArray"]              pub fn IsVertexArray(array: u32) -> GLboolean;
 #[link_name="glLineWidth"]                  pub fn LineWidth(width: f32);
 #[link_name="glLinkProgram"]                pub fn LinkProgram(program: u32);
 #[link_name="glPixelStorei"]                pub fn PixelStorei(pname: GLenum, param: i32);
 #[link_name="glPolygonOffset"]              pub fn PolygonOffset(factor: f32, units: f32);
+#[link_name="glReadBuffer"]                 pub fn ReadBuffer(src: GLenum);
 #[link_name="glReadPixels"]                 pub fn ReadPixels(x: i32, y: i32, width: i32, height: i32, format: GLenum, type_: GLenum, pixels: *mut GLvoid);
 #[link_name="glReleaseShaderCompiler"]      pub fn ReleaseShaderCompiler();
 #[link_name="glRenderbufferStorage"]        pub fn RenderbufferStorage(target: GLenum, internalformat: GLenum, width: i32, height: i32);
+#[link_name="glRenderbufferStorageMultisample"] pub fn RenderbufferStorageMultisample(target: GLenum, samples: i32, internalformat: GLenum, width: i32, height: i32);
 #[link_name="glSampleCoverage"]             pub fn SampleCoverage(value: f32, invert: GLboolean);
+#[link_name="glSamplerParameterf"]          pub fn SamplerParameterf(sampler: u32, pname: GLenum, param: f32);
+#[link_name="glSamplerParameterfv"]         pub fn SamplerParameterfv(sampler: u32, pname: GLenum, param: *const f32);
+#[link_name="glSamplerParameteri"]          pub fn SamplerParameteri(sampler: u32, pname: GLenum, param: i32);
+#[link_name="glSamplerParameteriv"]         pub fn SamplerParameteriv(sampler: u32, pname: GLenum, param: *const i32);
 #[link_name="glScissor"]                    pub fn Scissor(x: i32, y: i32, width: i32, height: i32);
 #[link_name="glShaderBinary"]               pub fn ShaderBinary(count: i32, shaders: *const u32, binaryformat: GLenum, binary: *const GLvoid, length: i32);
 #[link_name="glShaderSource"]               pub fn ShaderSource(shader: u32, count: i32, string: *const *const GLchar, length: *const i32);
@@ -523,27 +678,40 @@ extern "system" {
 #[link_name="glStencilOp"]                  pub fn StencilOp(fail: GLenum, zfail: GLenum, zpass: GLenum);
 #[link_name="glStencilOpSeparate"]          pub fn StencilOpSeparate(face: GLenum, sfail: GLenum, dpfail: GLenum, dppass: GLenum);
 #[link_name="glTexImage2D"]                 pub fn TexImage2D(target: GLenum, level: i32, internalformat: i32, width: i32, height: i32, border: i32, format: GLenum, type_: GLenum, pixels: *const GLvoid);
+#[link_name="glTexImage3D"]                 pub fn TexImage3D(target: GLenum, level: i32, internalformat: i32, width: i32, height: i32, depth: i32, border: i32, format: GLenum, type_: GLenum, pixels: *const GLvoid);
 #[link_name="glTexParameterf"]              pub fn TexParameterf(target: GLenum, pname: GLenum, param: f32);
 #[link_name="glTexParameterfv"]             pub fn TexParameterfv(target: GLenum, pname: GLenum, params: *const f32);
 #[link_name="glTexParameteri"]              pub fn TexParameteri(target: GLenum, pname: GLenum, param: i32);
 #[link_name="glTexParameteriv"]             pub fn TexParameteriv(target: GLenum, pname: GLenum, params: *const i32);
+#[link_name="glTexStorage2D"]               pub fn TexStorage2D(target: GLenum, levels: i32, internalformat: GLenum, width: i32, height: i32);
+#[link_name="glTexStorage3D"]               pub fn TexStorage3D(target: GLenum, levels: i32, internalformat: GLenum, width: i32, height: i32, depth: i32);
 #[link_name="glTexSubImage2D"]              pub fn TexSubImage2D(target: GLenum, level: i32, xoffset: i32, yoffset: i32, width: i32, height: i32, format: GLenum, type_: GLenum, pixels: *const GLvoid);
+#[link_name="glTexSubImage3D"]              pub fn TexSubImage3D(target: GLenum, level: i32, xoffset: i32, yoffset: i32, zoffset: i32, width: i32, height: i32, depth: i32, format: GLenum, type_: GLenum, pixels: *const GLvoid);
 #[link_name="glUniform1f"]                  pub fn Uniform1f(location: i32, v0: f32);
 #[link_name="glUniform1fv"]                 pub fn Uniform1fv(location: i32, count: i32, value: *const f32);
 #[link_name="glUniform1i"]                  pub fn Uniform1i(location: i32, v0: i32);
 #[link_name="glUniform1iv"]                 pub fn Uniform1iv(location: i32, count: i32, value: *const i32);
+#[link_name="glUniform1ui"]                 pub fn Uniform1ui(location: i32, v0: u32);
+#[link_name="glUniform1uiv"]                pub fn Uniform1uiv(location: i32, count: i32, value: *const u32);
 #[link_name="glUniform2f"]                  pub fn Uniform2f(location: i32, v0: f32, v1: f32);
 #[link_name="glUniform2fv"]                 pub fn Uniform2fv(location: i32, count: i32, value: *const f32);
 #[link_name="glUniform2i"]                  pub fn Uniform2i(location: i32, v0: i32, v1: i32);
 #[link_name="glUniform2iv"]                 pub fn Uniform2iv(location: i32, count: i32, value: *const i32);
+#[link_name="glUniform2ui"]                 pub fn Uniform2ui(location: i32, v0: u32, v1: u32);
+#[link_name="glUniform2uiv"]                pub fn Uniform2uiv(location: i32, count: i32, value: *const u32);
 #[link_name="glUniform3f"]                  pub fn Uniform3f(location: i32, v0: f32, v1: f32, v2: f32);
 #[link_name="glUniform3fv"]                 pub fn Uniform3fv(location: i32, count: i32, value: *const f32);
 #[link_name="glUniform3i"]                  pub fn Uniform3i(location: i32, v0: i32, v1: i32, v2: i32);
 #[link_name="glUniform3iv"]                 pub fn Uniform3iv(location: i32, count: i32, value: *const i32);
+#[link_name="glUniform3ui"]                 pub fn Uniform3ui(location: i32, v0: u32, v1: u32, v2: u32);
+#[link_name="glUniform3uiv"]                pub fn Uniform3uiv(location: i32, count: i32, value: *const u32);
 #[link_name="glUniform4f"]                  pub fn Uniform4f(location: i32, v0: f32, v1: f32, v2: f32, v3: f32);
 #[link_name="glUniform4fv"]                 pub fn Uniform4fv(location: i32, count: i32, value: *const f32);
 #[link_name="glUniform4i"]                  pub fn Uniform4i(location: i32, v0: i32, v1: i32, v2: i32, v3: i32);
 #[link_name="glUniform4iv"]                 pub fn Uniform4iv(location: i32, count: i32, value: *const i32);
+#[link_name="glUniform4ui"]                 pub fn Uniform4ui(location: i32, v0: u32, v1: u32, v2: u32, v3: u32);
+#[link_name="glUniform4uiv"]                pub fn Uniform4uiv(location: i32, count: i32, value: *const u32);
+#[link_name="glUniformBlockBinding"]        pub fn UniformBlockBinding(program: u32, uniformBlockIndex: u32, uniformBlockBinding: u32);
 #[link_name="glUniformMatrix2fv"]           pub fn UniformMatrix2fv(location: i32, count: i32, transpose: GLboolean, value: *const f32);
 #[link_name="glUniformMatrix3fv"]           pub fn UniformMatrix3fv(location: i32, count: i32, transpose: GLboolean, value: *const f32);
 #[link_name="glUniformMatrix4fv"]           pub fn UniformMatrix4fv(location: i32, count: i32, transpose: GLboolean, value: *const f32);
@@ -557,6 +725,8 @@ extern "system" {
 #[link_name="glVertexAttrib3fv"]            pub fn VertexAttrib3fv(index: u32, v: *const f32);
 #[link_name="glVertexAttrib4f"]             pub fn VertexAttrib4f(index: u32, x: f32, y: f32, z: f32, w: f32);
 #[link_name="glVertexAttrib4fv"]            pub fn VertexAttrib4fv(index: u32, v: *const f32);
+#[link_name="glVertexAttribDivisor"]        pub fn VertexAttribDivisor(index: u32, divisor: u32);
+#[link_name="glVertexAttribIPointer"]       pub fn VertexAttribIPointer(index: u32, size: i32, type_: GLenum, stride: i32, pointer: *const GLvoid);
 #[link_name="glVertexAttribPointer"]        pub fn VertexAttribPointer(index: u32, size: i32, type_: GLenum, normalized: GLboolean, stride: i32, pointer: *const GLvoid);
 #[link_name="glViewport"]                   pub fn Viewport(x: i32, y: i32, width: i32, height: i32);
 }
@@ -0,0 +1,1672 @@
+#![allow(non_snake_case, dead_code)]
+
+// A recording stand-in for the GL and emscripten webgl entry points, enabled with the
+// `headless` feature. Every call is appended to a per-thread command stream, and enough
+// object and binding state is tracked for the rendering code to run unmodified.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use bindings::emscripten::{CVoid, EmscriptenWebGLContextAttributes, em_async_wget_onload_func, em_arg_callback_func,
+	em_webgl_context_callback};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GlArg {
+	Int(i64),
+	Float(f32),
+	Ptr(usize),
+	// Copied out of C strings, whose pointers don't outlive the call
+	Str(String),
+}
+
+pub trait IntoGlArg {
+	fn into_gl_arg(self) -> GlArg;
+}
+
+impl IntoGlArg for u8 { fn into_gl_arg(self) -> GlArg { GlArg::Int(self as i64) } }
+impl IntoGlArg for i32 { fn into_gl_arg(self) -> GlArg { GlArg::Int(self as i64) } }
+impl IntoGlArg for u32 { fn into_gl_arg(self) -> GlArg { GlArg::Int(self as i64) } }
+impl IntoGlArg for f32 { fn into_gl_arg(self) -> GlArg { GlArg::Float(self) } }
+impl IntoGlArg for isize { fn into_gl_arg(self) -> GlArg { GlArg::Int(self as i64) } }
+impl IntoGlArg for f64 { fn into_gl_arg(self) -> GlArg { GlArg::Float(self as f32) } }
+impl<T> IntoGlArg for *const T { fn into_gl_arg(self) -> GlArg { GlArg::Ptr(self as usize) } }
+impl<T> IntoGlArg for *mut T { fn into_gl_arg(self) -> GlArg { GlArg::Ptr(self as usize) } }
+impl<'a> IntoGlArg for &'a str { fn into_gl_arg(self) -> GlArg { GlArg::Str(self.to_string()) } }
+
+impl<T> From<T> for GlArg where T: IntoGlArg {
+	fn from(o: T) -> GlArg { o.into_gl_arg() }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlCall {
+	pub name: &'static str,
+	pub args: Vec<GlArg>,
+}
+
+impl GlCall {
+	pub fn int(&self, idx: usize) -> i64 {
+		match self.args[idx] {
+			GlArg::Int(i) => i,
+			ref a => panic!("Argument {} of {} is not an integer: {:?}", idx, self.name, a),
+		}
+	}
+
+	pub fn float(&self, idx: usize) -> f32 {
+		match self.args[idx] {
+			GlArg::Float(f) => f,
+			ref a => panic!("Argument {} of {} is not a float: {:?}", idx, self.name, a),
+		}
+	}
+
+	pub fn ptr(&self, idx: usize) -> usize {
+		match self.args[idx] {
+			GlArg::Ptr(p) => p,
+			ref a => panic!("Argument {} of {} is not a pointer: {:?}", idx, self.name, a),
+		}
+	}
+
+	pub fn str(&self, idx: usize) -> &str {
+		match self.args[idx] {
+			GlArg::Str(ref s) => s,
+			ref a => panic!("Argument {} of {} is not a string: {:?}", idx, self.name, a),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BufferObject {
+	pub data: Vec<u8>,
+	pub usage: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TextureObject {
+	pub size: (i32, i32),
+	/// Depth or layer count of 3D and array textures
+	pub depth: i32,
+	pub internal_format: i32,
+	pub params: HashMap<u32, i32>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ShaderObject {
+	pub ty: u32,
+	pub source: String,
+	pub compiled: bool,
+	pub info_log: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ProgramObject {
+	pub shaders: Vec<u32>,
+	pub attrib_bindings: HashMap<String, u32>,
+	/// (name, type, location) of each attribute declared by the vertex shader, assigned at link time
+	pub active_attributes: Vec<(String, u32, u32)>,
+	/// (name, type, size) of each uniform declared by the attached shaders, in location order
+	pub active_uniforms: Vec<(String, u32, i32)>,
+	pub uniform_locations: HashMap<String, i32>,
+	pub uniform_blocks: Vec<String>,
+	/// Binding point of each uniform block, by block index
+	pub block_bindings: HashMap<u32, u32>,
+	pub linked: bool,
+	pub info_log: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FramebufferObject {
+	pub attachments: HashMap<u32, u32>,
+}
+
+/// The per-VAO state. The bound VAO's state lives in HeadlessState while it's bound
+#[derive(Clone, Debug, Default)]
+pub struct VertexArrayObject {
+	pub element_array_buffer_binding: u32,
+	pub enabled_attribs: HashSet<u32>,
+	pub attrib_divisors: HashMap<u32, u32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ContextObject {
+	pub target: String,
+	pub attributes: EmscriptenWebGLContextAttributes,
+	pub enabled_extensions: HashSet<String>,
+	pub lost: bool,
+}
+
+// A context lost or restored callback, registered per target
+#[derive(Copy, Clone)]
+struct ContextCallback {
+	arg: *mut CVoid,
+	callback: em_webgl_context_callback,
+}
+
+pub struct PendingFetch {
+	pub url: String,
+	arg: *mut CVoid,
+	onload: em_async_wget_onload_func,
+	onerror: em_arg_callback_func,
+}
+
+pub struct HeadlessState {
+	pub calls: Vec<GlCall>,
+	next_handle: u32,
+
+	pub buffers: HashMap<u32, BufferObject>,
+	pub textures: HashMap<u32, TextureObject>,
+	pub shaders: HashMap<u32, ShaderObject>,
+	pub programs: HashMap<u32, ProgramObject>,
+	pub framebuffers: HashMap<u32, FramebufferObject>,
+	pub renderbuffers: HashSet<u32>,
+	/// Includes the default VAO, 0
+	pub vertex_arrays: HashMap<u32, VertexArrayObject>,
+	/// Parameters set on each sampler object
+	pub samplers: HashMap<u32, HashMap<u32, i32>>,
+
+	pub array_buffer_binding: u32,
+	pub element_array_buffer_binding: u32,
+	pub framebuffer_binding: u32,
+	pub renderbuffer_binding: u32,
+	pub current_program: u32,
+	pub active_texture_unit: u32,
+	pub texture_bindings: HashMap<u32, u32>,
+	pub enabled_caps: HashSet<u32>,
+	pub enabled_attribs: HashSet<u32>,
+	pub attrib_divisors: HashMap<u32, u32>,
+	pub vertex_array_binding: u32,
+	pub sampler_bindings: HashMap<u32, u32>,
+	pub draw_buffers: Vec<u32>,
+	/// Bindings of buffer targets other than ARRAY_BUFFER and ELEMENT_ARRAY_BUFFER
+	pub buffer_bindings: HashMap<u32, u32>,
+	/// (buffer, offset, size) bound to each (target, index). Size is 0 for BindBufferBase
+	pub indexed_buffer_bindings: HashMap<(u32, u32), (u32, isize, isize)>,
+
+	pub contexts: HashMap<i32, ContextObject>,
+	pub current_context: i32,
+	pub supported_extensions: HashSet<String>,
+	/// Whether contexts can be created with majorVersion 2
+	pub webgl2_supported: bool,
+	/// Makes emscripten_webgl_make_context_current fail even for contexts that exist
+	pub make_current_fails: bool,
+	lost_callbacks: HashMap<String, ContextCallback>,
+	restored_callbacks: HashMap<String, ContextCallback>,
+
+	/// Files that async wgets can fetch, by url
+	pub served_files: HashMap<String, Vec<u8>>,
+	pub pending_fetches: Vec<PendingFetch>,
+}
+
+impl HeadlessState {
+	fn new() -> Self {
+		let supported_extensions = [
+			"OES_element_index_uint",
+			"OES_standard_derivatives",
+			"OES_texture_float",
+			"OES_vertex_array_object",
+			"ANGLE_instanced_arrays",
+			"WEBGL_depth_texture",
+			"WEBGL_draw_buffers",
+			"EXT_color_buffer_float",
+		].iter().map(|s| s.to_string()).collect();
+
+		HeadlessState {
+			calls: Vec::new(),
+			next_handle: 1,
+
+			buffers: HashMap::new(),
+			textures: HashMap::new(),
+			shaders: HashMap::new(),
+			programs: HashMap::new(),
+			framebuffers: HashMap::new(),
+			renderbuffers: HashSet::new(),
+			vertex_arrays: ::std::iter::once((0, VertexArrayObject::default())).collect(),
+			samplers: HashMap::new(),
+
+			array_buffer_binding: 0,
+			element_array_buffer_binding: 0,
+			framebuffer_binding: 0,
+			renderbuffer_binding: 0,
+			current_program: 0,
+			active_texture_unit: 0,
+			texture_bindings: HashMap::new(),
+			enabled_caps: HashSet::new(),
+			enabled_attribs: HashSet::new(),
+			attrib_divisors: HashMap::new(),
+			vertex_array_binding: 0,
+			sampler_bindings: HashMap::new(),
+			draw_buffers: vec![::bindings::gl::BACK],
+			buffer_bindings: HashMap::new(),
+			indexed_buffer_bindings: HashMap::new(),
+
+			contexts: HashMap::new(),
+			current_context: 0,
+			supported_extensions,
+			webgl2_supported: true,
+			make_current_fails: false,
+			lost_callbacks: HashMap::new(),
+			restored_callbacks: HashMap::new(),
+
+			served_files: HashMap::new(),
+			pending_fetches: Vec::new(),
+		}
+	}
+
+	fn alloc_handle(&mut self) -> u32 {
+		let handle = self.next_handle;
+		self.next_handle += 1;
+		handle
+	}
+
+	fn record(&mut self, name: &'static str, args: Vec<GlArg>) {
+		self.calls.push(GlCall{ name, args });
+	}
+
+	pub fn calls_named(&self, name: &str) -> Vec<GlCall> {
+		self.calls.iter()
+			.filter(|c| c.name == name)
+			.cloned()
+			.collect()
+	}
+
+	pub fn bound_texture(&self) -> u32 {
+		self.texture_bindings.get(&self.active_texture_unit).cloned().unwrap_or(0)
+	}
+
+	pub fn bound_buffer(&self, target: u32) -> u32 {
+		match target {
+			::bindings::gl::ARRAY_BUFFER => self.array_buffer_binding,
+			::bindings::gl::ELEMENT_ARRAY_BUFFER => self.element_array_buffer_binding,
+			_ => self.buffer_bindings.get(&target).cloned().unwrap_or(0),
+		}
+	}
+
+	/// The majorVersion of the current context. GL calls made without a context act like WebGL1
+	pub fn webgl_version(&self) -> i32 {
+		self.contexts.get(&self.current_context)
+			.map_or(1, |c| c.attributes.majorVersion)
+	}
+
+	// Whether a context of `version` exposes `extension`. Extensions promoted to core in WebGL2
+	// aren't exposed there, and EXT_color_buffer_float is WebGL2 only
+	fn extension_available(&self, extension: &str, version: i32) -> bool {
+		let webgl1_only = match extension {
+			"EXT_color_buffer_float" => false,
+			"OES_element_index_uint" | "OES_standard_derivatives" | "OES_texture_float"
+				| "OES_vertex_array_object" | "ANGLE_instanced_arrays" | "WEBGL_depth_texture"
+				| "WEBGL_draw_buffers" => true,
+			_ => return self.supported_extensions.contains(extension),
+		};
+
+		self.supported_extensions.contains(extension) && webgl1_only == (version < 2)
+	}
+
+	fn extension_enabled(&self, extension: &str) -> bool {
+		self.contexts.get(&self.current_context)
+			.map_or(false, |c| c.enabled_extensions.contains(extension))
+	}
+
+	// Implementation limits, as reported by GetIntegerv. GLES3 limits are only there for WebGL2,
+	// or with the extension that provides them
+	fn limit(&self, pname: u32) -> Option<i32> {
+		use bindings::gl::*;
+
+		let draw_buffers = self.webgl_version() >= 2 || self.extension_enabled("WEBGL_draw_buffers");
+
+		let limit = match pname {
+			MAX_TEXTURE_SIZE => 4096,
+			MAX_CUBE_MAP_TEXTURE_SIZE => 4096,
+			MAX_RENDERBUFFER_SIZE => 4096,
+			MAX_TEXTURE_IMAGE_UNITS => 16,
+			MAX_COMBINED_TEXTURE_IMAGE_UNITS => 32,
+			MAX_VERTEX_TEXTURE_IMAGE_UNITS => 16,
+			MAX_VERTEX_ATTRIBS => 16,
+			MAX_VARYING_VECTORS => 15,
+			MAX_VERTEX_UNIFORM_VECTORS => 1024,
+			MAX_FRAGMENT_UNIFORM_VECTORS => 1024,
+			MAX_DRAW_BUFFERS | MAX_COLOR_ATTACHMENTS if draw_buffers => 8,
+			_ if self.webgl_version() < 2 => return None,
+
+			MAX_SAMPLES => 4,
+			MAX_3D_TEXTURE_SIZE => 2048,
+			MAX_ARRAY_TEXTURE_LAYERS => 256,
+			MAX_UNIFORM_BUFFER_BINDINGS => 24,
+			MAX_UNIFORM_BLOCK_SIZE => 16384,
+			MAX_VERTEX_UNIFORM_BLOCKS => 12,
+			MAX_FRAGMENT_UNIFORM_BLOCKS => 12,
+			UNIFORM_BUFFER_OFFSET_ALIGNMENT => 256,
+			_ => return None,
+		};
+
+		Some(limit)
+	}
+
+	fn bound_buffer_mut(&mut self, target: u32) -> Option<&mut BufferObject> {
+		let handle = self.bound_buffer(target);
+		self.buffers.get_mut(&handle)
+	}
+}
+
+thread_local! {
+	static STATE: RefCell<HeadlessState> = RefCell::new(HeadlessState::new());
+}
+
+pub fn with_state<F, R>(f: F) -> R where F: FnOnce(&mut HeadlessState) -> R {
+	STATE.with(|s| f(&mut s.borrow_mut()))
+}
+
+/// Drops all recorded calls and tracked objects for the current thread
+pub fn reset() {
+	STATE.with(|s| *s.borrow_mut() = HeadlessState::new());
+}
+
+pub fn calls() -> Vec<GlCall> {
+	with_state(|s| s.calls.clone())
+}
+
+pub fn take_calls() -> Vec<GlCall> {
+	with_state(|s| ::std::mem::replace(&mut s.calls, Vec::new()))
+}
+
+pub fn calls_named(name: &str) -> Vec<GlCall> {
+	with_state(|s| s.calls_named(name))
+}
+
+pub fn serve_file(url: &str, data: &[u8]) {
+	with_state(|s| s.served_files.insert(url.to_string(), data.to_vec()));
+}
+
+/// Runs the callbacks of every outstanding async wget. Urls that haven't been served with
+/// `serve_file` fail
+pub fn complete_fetches() {
+	let fetches = with_state(|s| ::std::mem::replace(&mut s.pending_fetches, Vec::new()));
+
+	for fetch in fetches {
+		let data = with_state(|s| s.served_files.get(&fetch.url).cloned());
+
+		unsafe {
+			match (data, fetch.onload, fetch.onerror) {
+				(Some(mut data), Some(onload), _) => onload(fetch.arg, data.as_mut_ptr() as _, data.len() as i32),
+				(None, _, Some(onerror)) => onerror(fetch.arg),
+				_ => {}
+			}
+		}
+	}
+}
+
+/// Loses context `ctx` like a browser would: every GL object is gone, extensions have to be
+/// enabled again, and the target's context lost callback is run
+pub fn lose_context(ctx: i32) {
+	let callback = with_state(|s| {
+		let fresh = HeadlessState::new();
+
+		s.buffers = fresh.buffers;
+		s.textures = fresh.textures;
+		s.shaders = fresh.shaders;
+		s.programs = fresh.programs;
+		s.framebuffers = fresh.framebuffers;
+		s.renderbuffers = fresh.renderbuffers;
+		s.vertex_arrays = fresh.vertex_arrays;
+		s.samplers = fresh.samplers;
+
+		s.array_buffer_binding = 0;
+		s.element_array_buffer_binding = 0;
+		s.framebuffer_binding = 0;
+		s.renderbuffer_binding = 0;
+		s.current_program = 0;
+		s.texture_bindings.clear();
+		s.enabled_attribs.clear();
+		s.vertex_array_binding = 0;
+
+		let context = s.contexts.get_mut(&ctx).expect("Tried to lose unknown context");
+		context.lost = true;
+		context.enabled_extensions.clear();
+
+		s.lost_callbacks.get(&context.target).cloned()
+	});
+
+	// EMSCRIPTEN_EVENT_WEBGLCONTEXTLOST
+	run_context_callback(callback, 31);
+}
+
+/// Restores context `ctx`, running the target's context restored callback
+pub fn restore_context(ctx: i32) {
+	let callback = with_state(|s| {
+		let context = s.contexts.get_mut(&ctx).expect("Tried to restore unknown context");
+		context.lost = false;
+
+		s.restored_callbacks.get(&context.target).cloned()
+	});
+
+	// EMSCRIPTEN_EVENT_WEBGLCONTEXTRESTORED
+	run_context_callback(callback, 32);
+}
+
+fn run_context_callback(callback: Option<ContextCallback>, event_type: i32) {
+	if let Some(ContextCallback { arg, callback: Some(callback) }) = callback {
+		unsafe { callback(event_type, ::std::ptr::null(), arg); }
+	}
+}
+
+fn record(name: &'static str, args: Vec<GlArg>) {
+	with_state(|s| s.record(name, args));
+}
+
+// Shaders containing `#error` directives fail to compile with a driver-style log entry for each,
+// which is how tests exercise the failure paths
+fn compile_source(source: &str) -> Result<(), String> {
+	let mut log = String::new();
+
+	for (line, text) in source.lines().enumerate() {
+		let text = text.trim();
+		if text.starts_with("#error") {
+			log.push_str(&format!("ERROR: 0:{}: '#error' : {}\n", line + 1, text[6..].trim()));
+		}
+	}
+
+	if log.is_empty() { Ok(()) } else { Err(log) }
+}
+
+// Pulls `<qualifier> <type> <name>;` declarations out of shader source, as (name, type, array size).
+// Array names are reported as `name[0]`, like GetActiveUniform does. Everything declared is
+// treated as active
+fn declarations(source: &str, qualifier: &str) -> Vec<(String, u32, i32)> {
+	use bindings::gl::*;
+
+	source.split(|c| c == ';' || c == '{' || c == '}')
+		.filter_map(|decl| {
+			let mut words = decl.split_whitespace()
+				.skip_while(|&w| w != qualifier)
+				.skip(1)
+				.skip_while(|&w| w == "lowp" || w == "mediump" || w == "highp");
+
+			let ty = match words.next()? {
+				"float" => FLOAT,
+				"vec2" => FLOAT_VEC2,
+				"vec3" => FLOAT_VEC3,
+				"vec4" => FLOAT_VEC4,
+				"int" => INT,
+				"bool" => BOOL,
+				"mat2" => FLOAT_MAT2,
+				"mat3" => FLOAT_MAT3,
+				"mat4" => FLOAT_MAT4,
+				"sampler2D" => SAMPLER_2D,
+				"samplerCube" => SAMPLER_CUBE,
+				_ => return None,
+			};
+
+			let name = words.next()?;
+			match name.find('[') {
+				Some(bracket) => {
+					let size = name[bracket+1..].trim_end_matches(']').parse().ok()?;
+					Some((format!("{}[0]", &name[..bracket]), ty, size))
+				}
+
+				None => Some((name.to_string(), ty, 1)),
+			}
+		})
+		.collect()
+}
+
+// Names of `uniform <Name> { ... }` blocks
+fn block_declarations(source: &str) -> Vec<String> {
+	let chunks: Vec<&str> = source.split('{').collect();
+
+	chunks[..chunks.len()-1].iter()
+		.filter_map(|chunk| {
+			let statement = chunk.rsplit(|c| c == ';' || c == '}').next()?;
+			let words: Vec<&str> = statement.split_whitespace().collect();
+
+			match words.len() {
+				n if n >= 2 && words[n-2] == "uniform" => Some(words[n-1].to_string()),
+				_ => None,
+			}
+		})
+		.collect()
+}
+
+fn is_es300(source: &str) -> bool {
+	source.trim_start().starts_with("#version 300 es")
+}
+
+// Pulls `layout(location=N) in <type> <name>;` declarations out of vertex shader source
+fn layout_locations(source: &str) -> Vec<(String, u32)> {
+	source.split(';')
+		.filter_map(|decl| {
+			let decl = decl.trim_start();
+			if !decl.starts_with("layout") { return None }
+
+			let (open, close) = (decl.find('(')?, decl.find(')')?);
+			let location = decl[open+1..close].split('=')
+				.map(str::trim)
+				.collect::<Vec<_>>();
+
+			if location.len() != 2 || location[0] != "location" { return None }
+
+			let mut words = decl[close+1..].split_whitespace();
+			if words.next() != Some("in") { return None }
+
+			let name = words.skip_while(|&w| w == "lowp" || w == "mediump" || w == "highp").nth(1)?;
+			Some((name.to_string(), location[1].parse().ok()?))
+		})
+		.collect()
+}
+
+unsafe fn write_c_str(s: &str, buf_size: i32, length: *mut i32, out: *mut i8) {
+	if buf_size <= 0 || out.is_null() { return }
+
+	let len = s.len().min(buf_size as usize - 1);
+	::std::ptr::copy_nonoverlapping(s.as_ptr() as *const i8, out, len);
+	*out.offset(len as isize) = 0;
+
+	if !length.is_null() {
+		*length = len as i32;
+	}
+}
+
+macro_rules! record_only {
+	( $( fn $name:ident ( $( $arg:ident : $ty:ty ),* ); )* ) => {
+		$(
+			pub unsafe fn $name( $( $arg: $ty ),* ) {
+				super::record(stringify!($name), vec![ $( super::IntoGlArg::into_gl_arg($arg) ),* ]);
+			}
+		)*
+	};
+}
+
+pub mod gl {
+	use super::*;
+	use bindings::gl::*;
+
+	use std::ffi::CStr;
+
+	record_only! {
+	fn BlendColor(red: f32, green: f32, blue: f32, alpha: f32);
+	fn BlendEquation(mode: GLenum);
+	fn BlendEquationSeparate(modeRGB: GLenum, modeAlpha: GLenum);
+	fn BlendFunc(sfactor: GLenum, dfactor: GLenum);
+	fn BlendFuncSeparate(sfactorRGB: GLenum, dfactorRGB: GLenum, sfactorAlpha: GLenum, dfactorAlpha: GLenum);
+	fn BlitFramebuffer(srcX0: i32, srcY0: i32, srcX1: i32, srcY1: i32, dstX0: i32, dstY0: i32, dstX1: i32, dstY1: i32, mask: GLbitfield, filter: GLenum);
+	fn Clear(mask: GLbitfield);
+	fn ClearBufferfi(buffer: GLenum, drawbuffer: i32, depth: f32, stencil: i32);
+	fn ClearBufferfv(buffer: GLenum, drawbuffer: i32, value: *const f32);
+	fn ClearBufferiv(buffer: GLenum, drawbuffer: i32, value: *const i32);
+	fn ClearBufferuiv(buffer: GLenum, drawbuffer: i32, value: *const u32);
+	fn ClearColor(red: f32, green: f32, blue: f32, alpha: f32);
+	fn ClearDepthf(d: f32);
+	fn ClearStencil(s: i32);
+	fn ColorMask(red: GLboolean, green: GLboolean, blue: GLboolean, alpha: GLboolean);
+	fn CompressedTexImage2D(target: GLenum, level: i32, internalformat: GLenum, width: i32, height: i32, border: i32, imageSize: i32, data: *const GLvoid);
+	fn CompressedTexSubImage2D(target: GLenum, level: i32, xoffset: i32, yoffset: i32, width: i32, height: i32, format: GLenum, imageSize: i32, data: *const GLvoid);
+	fn CopyBufferSubData(readTarget: GLenum, writeTarget: GLenum, readOffset: GLintptr, writeOffset: GLintptr, size: GLsizeiptr);
+	fn CopyTexImage2D(target: GLenum, level: i32, internalformat: GLenum, x: i32, y: i32, width: i32, height: i32, border: i32);
+	fn CopyTexSubImage2D(target: GLenum, level: i32, xoffset: i32, yoffset: i32, x: i32, y: i32, width: i32, height: i32);
+	fn CopyTexSubImage3D(target: GLenum, level: i32, xoffset: i32, yoffset: i32, zoffset: i32, x: i32, y: i32, width: i32, height: i32);
+	fn CullFace(mode: GLenum);
+	fn DepthFunc(func: GLenum);
+	fn DepthMask(flag: GLboolean);
+	fn DepthRangef(n: f32, f: f32);
+	fn DetachShader(program: u32, shader: u32);
+	fn DrawArrays(mode: GLenum, first: i32, count: i32);
+	fn DrawArraysInstanced(mode: GLenum, first: i32, count: i32, instancecount: i32);
+	fn DrawElements(mode: GLenum, count: i32, type_: GLenum, indices: *const GLvoid);
+	fn DrawElementsInstanced(mode: GLenum, count: i32, type_: GLenum, indices: *const GLvoid, instancecount: i32);
+	fn DrawRangeElements(mode: GLenum, start: u32, end: u32, count: i32, type_: GLenum, indices: *const GLvoid);
+	fn Finish();
+	fn Flush();
+	fn FramebufferRenderbuffer(target: GLenum, attachment: GLenum, renderbuffertarget: GLenum, renderbuffer: u32);
+	fn FramebufferTextureLayer(target: GLenum, attachment: GLenum, texture: u32, level: i32, layer: i32);
+	fn FrontFace(mode: GLenum);
+	fn GenerateMipmap(target: GLenum);
+	fn GetActiveUniformsiv(program: u32, uniformCount: i32, uniformIndices: *const u32, pname: GLenum, params: *mut i32);
+	fn GetAttachedShaders(program: u32, maxCount: i32, count: *mut i32, shaders: *mut u32);
+	fn GetBooleanv(pname: GLenum, data: *mut GLboolean);
+	fn GetBufferParameteriv(target: GLenum, pname: GLenum, params: *mut i32);
+	fn GetFloatv(pname: GLenum, data: *mut f32);
+	fn GetFramebufferAttachmentParameteriv(target: GLenum, attachment: GLenum, pname: GLenum, params: *mut i32);
+	fn GetRenderbufferParameteriv(target: GLenum, pname: GLenum, params: *mut i32);
+	fn GetSamplerParameterfv(sampler: u32, pname: GLenum, params: *mut f32);
+	fn GetShaderPrecisionFormat(shadertype: GLenum, precisiontype: GLenum, range: *mut i32, precision: *mut i32);
+	fn GetShaderSource(shader: u32, bufSize: i32, length: *mut i32, source: *mut GLchar);
+	fn GetTexParameterfv(target: GLenum, pname: GLenum, params: *mut f32);
+	fn GetTexParameteriv(target: GLenum, pname: GLenum, params: *mut i32);
+	fn GetUniformIndices(program: u32, uniformCount: i32, uniformNames: *const *const GLchar, uniformIndices: *mut u32);
+	fn GetUniformfv(program: u32, location: i32, params: *mut f32);
+	fn GetUniformiv(program: u32, location: i32, params: *mut i32);
+	fn GetVertexAttribPointerv(index: u32, pname: GLenum, pointer: *const *mut GLvoid);
+	fn GetVertexAttribfv(index: u32, pname: GLenum, params: *mut f32);
+	fn GetVertexAttribiv(index: u32, pname: GLenum, params: *mut i32);
+	fn Hint(target: GLenum, mode: GLenum);
+	fn LineWidth(width: f32);
+	fn PixelStorei(pname: GLenum, param: i32);
+	fn PolygonOffset(factor: f32, units: f32);
+	fn ReadBuffer(src: GLenum);
+	fn ReadPixels(x: i32, y: i32, width: i32, height: i32, format: GLenum, type_: GLenum, pixels: *mut GLvoid);
+	fn ReleaseShaderCompiler();
+	fn RenderbufferStorage(target: GLenum, internalformat: GLenum, width: i32, height: i32);
+	fn RenderbufferStorageMultisample(target: GLenum, samples: i32, internalformat: GLenum, width: i32, height: i32);
+	fn SampleCoverage(value: f32, invert: GLboolean);
+	fn SamplerParameterf(sampler: u32, pname: GLenum, param: f32);
+	fn SamplerParameterfv(sampler: u32, pname: GLenum, param: *const f32);
+	fn SamplerParameteriv(sampler: u32, pname: GLenum, param: *const i32);
+	fn Scissor(x: i32, y: i32, width: i32, height: i32);
+	fn ShaderBinary(count: i32, shaders: *const u32, binaryformat: GLenum, binary: *const GLvoid, length: i32);
+	fn StencilFunc(func: GLenum, ref_: i32, mask: u32);
+	fn StencilFuncSeparate(face: GLenum, func: GLenum, ref_: i32, mask: u32);
+	fn StencilMask(mask: u32);
+	fn StencilMaskSeparate(face: GLenum, mask: u32);
+	fn StencilOp(fail: GLenum, zfail: GLenum, zpass: GLenum);
+	fn StencilOpSeparate(face: GLenum, sfail: GLenum, dpfail: GLenum, dppass: GLenum);
+	fn TexParameterf(target: GLenum, pname: GLenum, param: f32);
+	fn TexParameterfv(target: GLenum, pname: GLenum, params: *const f32);
+	fn TexParameteriv(target: GLenum, pname: GLenum, params: *const i32);
+	fn TexSubImage2D(target: GLenum, level: i32, xoffset: i32, yoffset: i32, width: i32, height: i32, format: GLenum, type_: GLenum, pixels: *const GLvoid);
+	fn TexSubImage3D(target: GLenum, level: i32, xoffset: i32, yoffset: i32, zoffset: i32, width: i32, height: i32, depth: i32, format: GLenum, type_: GLenum, pixels: *const GLvoid);
+	fn Uniform1f(location: i32, v0: f32);
+	fn Uniform1fv(location: i32, count: i32, value: *const f32);
+	fn Uniform1i(location: i32, v0: i32);
+	fn Uniform1iv(location: i32, count: i32, value: *const i32);
+	fn Uniform1ui(location: i32, v0: u32);
+	fn Uniform1uiv(location: i32, count: i32, value: *const u32);
+	fn Uniform2f(location: i32, v0: f32, v1: f32);
+	fn Uniform2fv(location: i32, count: i32, value: *const f32);
+	fn Uniform2i(location: i32, v0: i32, v1: i32);
+	fn Uniform2iv(location: i32, count: i32, value: *const i32);
+	fn Uniform2ui(location: i32, v0: u32, v1: u32);
+	fn Uniform2uiv(location: i32, count: i32, value: *const u32);
+	fn Uniform3f(location: i32, v0: f32, v1: f32, v2: f32);
+	fn Uniform3fv(location: i32, count: i32, value: *const f32);
+	fn Uniform3i(location: i32, v0: i32, v1: i32, v2: i32);
+	fn Uniform3iv(location: i32, count: i32, value: *const i32);
+	fn Uniform3ui(location: i32, v0: u32, v1: u32, v2: u32);
+	fn Uniform3uiv(location: i32, count: i32, value: *const u32);
+	fn Uniform4f(location: i32, v0: f32, v1: f32, v2: f32, v3: f32);
+	fn Uniform4fv(location: i32, count: i32, value: *const f32);
+	fn Uniform4i(location: i32, v0: i32, v1: i32, v2: i32, v3: i32);
+	fn Uniform4iv(location: i32, count: i32, value: *const i32);
+	fn Uniform4ui(location: i32, v0: u32, v1: u32, v2: u32, v3: u32);
+	fn Uniform4uiv(location: i32, count: i32, value: *const u32);
+	fn UniformMatrix2fv(location: i32, count: i32, transpose: GLboolean, value: *const f32);
+	fn UniformMatrix3fv(location: i32, count: i32, transpose: GLboolean, value: *const f32);
+	fn UniformMatrix4fv(location: i32, count: i32, transpose: GLboolean, value: *const f32);
+	fn ValidateProgram(program: u32);
+	fn VertexAttrib1f(index: u32, x: f32);
+	fn VertexAttrib1fv(index: u32, v: *const f32);
+	fn VertexAttrib2f(index: u32, x: f32, y: f32);
+	fn VertexAttrib2fv(index: u32, v: *const f32);
+	fn VertexAttrib3f(index: u32, x: f32, y: f32, z: f32);
+	fn VertexAttrib3fv(index: u32, v: *const f32);
+	fn VertexAttrib4f(index: u32, x: f32, y: f32, z: f32, w: f32);
+	fn VertexAttrib4fv(index: u32, v: *const f32);
+	fn VertexAttribIPointer(index: u32, size: i32, type_: GLenum, stride: i32, pointer: *const GLvoid);
+	fn VertexAttribPointer(index: u32, size: i32, type_: GLenum, normalized: GLboolean, stride: i32, pointer: *const GLvoid);
+	fn Viewport(x: i32, y: i32, width: i32, height: i32);
+	}
+
+	pub unsafe fn ActiveTexture(texture: GLenum) {
+		with_state(|s| {
+			s.record("ActiveTexture", vec![texture.into()]);
+			s.active_texture_unit = texture - TEXTURE0;
+		});
+	}
+
+	pub unsafe fn BindBuffer(target: GLenum, buffer: u32) {
+		with_state(|s| {
+			s.record("BindBuffer", vec![target.into(), buffer.into()]);
+			match target {
+				ARRAY_BUFFER => s.array_buffer_binding = buffer,
+				ELEMENT_ARRAY_BUFFER => s.element_array_buffer_binding = buffer,
+				_ => { s.buffer_bindings.insert(target, buffer); }
+			}
+		});
+	}
+
+	pub unsafe fn BindBufferBase(target: GLenum, index: u32, buffer: u32) {
+		with_state(|s| {
+			s.record("BindBufferBase", vec![target.into(), index.into(), buffer.into()]);
+			s.buffer_bindings.insert(target, buffer);
+			s.indexed_buffer_bindings.insert((target, index), (buffer, 0, 0));
+		});
+	}
+
+	pub unsafe fn BindBufferRange(target: GLenum, index: u32, buffer: u32, offset: GLintptr, size: GLsizeiptr) {
+		with_state(|s| {
+			s.record("BindBufferRange", vec![target.into(), index.into(), buffer.into(), offset.into(), size.into()]);
+			s.buffer_bindings.insert(target, buffer);
+			s.indexed_buffer_bindings.insert((target, index), (buffer, offset, size));
+		});
+	}
+
+	pub unsafe fn BindFramebuffer(target: GLenum, framebuffer: u32) {
+		with_state(|s| {
+			s.record("BindFramebuffer", vec![target.into(), framebuffer.into()]);
+			s.framebuffer_binding = framebuffer;
+		});
+	}
+
+	pub unsafe fn BindRenderbuffer(target: GLenum, renderbuffer: u32) {
+		with_state(|s| {
+			s.record("BindRenderbuffer", vec![target.into(), renderbuffer.into()]);
+			s.renderbuffer_binding = renderbuffer;
+		});
+	}
+
+	pub unsafe fn BindTexture(target: GLenum, texture: u32) {
+		with_state(|s| {
+			s.record("BindTexture", vec![target.into(), texture.into()]);
+			let unit = s.active_texture_unit;
+			s.texture_bindings.insert(unit, texture);
+		});
+	}
+
+	pub unsafe fn BufferData(target: GLenum, size: i32, data: *const GLvoid, usage: GLenum) {
+		with_state(|s| {
+			s.record("BufferData", vec![target.into(), size.into(), data.into(), usage.into()]);
+
+			if let Some(buffer) = s.bound_buffer_mut(target) {
+				buffer.usage = usage;
+				buffer.data = if data.is_null() {
+					vec![0; size as usize]
+				} else {
+					::std::slice::from_raw_parts(data as *const u8, size as usize).to_vec()
+				};
+			}
+		});
+	}
+
+	pub unsafe fn BufferSubData(target: GLenum, offset: i32, size: i32, data: *const GLvoid) {
+		with_state(|s| {
+			s.record("BufferSubData", vec![target.into(), offset.into(), size.into(), data.into()]);
+
+			if let Some(buffer) = s.bound_buffer_mut(target) {
+				let (start, end) = (offset as usize, (offset + size) as usize);
+				assert!(end <= buffer.data.len(), "BufferSubData out of range");
+
+				let src = ::std::slice::from_raw_parts(data as *const u8, size as usize);
+				buffer.data[start..end].copy_from_slice(src);
+			}
+		});
+	}
+
+	pub unsafe fn GenBuffers(n: i32, buffers: *mut u32) {
+		with_state(|s| {
+			s.record("GenBuffers", vec![n.into(), buffers.into()]);
+			for i in 0..n as isize {
+				let handle = s.alloc_handle();
+				s.buffers.insert(handle, BufferObject::default());
+				*buffers.offset(i) = handle;
+			}
+		});
+	}
+
+	pub unsafe fn DeleteBuffers(n: i32, buffers: *const u32) {
+		with_state(|s| {
+			s.record("DeleteBuffers", vec![n.into(), buffers.into()]);
+			for i in 0..n as isize {
+				let handle = *buffers.offset(i);
+				s.buffers.remove(&handle);
+				if s.array_buffer_binding == handle { s.array_buffer_binding = 0 }
+				if s.element_array_buffer_binding == handle { s.element_array_buffer_binding = 0 }
+				for binding in s.buffer_bindings.values_mut() {
+					if *binding == handle { *binding = 0 }
+				}
+
+				s.indexed_buffer_bindings.retain(|_, b| b.0 != handle);
+			}
+		});
+	}
+
+	pub unsafe fn IsBuffer(buffer: u32) -> GLboolean {
+		with_state(|s| {
+			s.record("IsBuffer", vec![buffer.into()]);
+			s.buffers.contains_key(&buffer) as GLboolean
+		})
+	}
+
+	pub unsafe fn GenTextures(n: i32, textures: *mut u32) {
+		with_state(|s| {
+			s.record("GenTextures", vec![n.into(), textures.into()]);
+			for i in 0..n as isize {
+				let handle = s.alloc_handle();
+				s.textures.insert(handle, TextureObject::default());
+				*textures.offset(i) = handle;
+			}
+		});
+	}
+
+	pub unsafe fn DeleteTextures(n: i32, textures: *const u32) {
+		with_state(|s| {
+			s.record("DeleteTextures", vec![n.into(), textures.into()]);
+			for i in 0..n as isize {
+				let handle = *textures.offset(i);
+				s.textures.remove(&handle);
+				for binding in s.texture_bindings.values_mut() {
+					if *binding == handle { *binding = 0 }
+				}
+			}
+		});
+	}
+
+	pub unsafe fn IsTexture(texture: u32) -> GLboolean {
+		with_state(|s| {
+			s.record("IsTexture", vec![texture.into()]);
+			s.textures.contains_key(&texture) as GLboolean
+		})
+	}
+
+	pub unsafe fn TexImage2D(target: GLenum, level: i32, internalformat: i32, width: i32, height: i32, border: i32, format: GLenum, type_: GLenum, pixels: *const GLvoid) {
+		with_state(|s| {
+			s.record("TexImage2D", vec![target.into(), level.into(), internalformat.into(), width.into(),
+				height.into(), border.into(), format.into(), type_.into(), pixels.into()]);
+
+			let handle = s.bound_texture();
+			if let Some(tex) = s.textures.get_mut(&handle) {
+				if level == 0 {
+					tex.size = (width, height);
+					tex.internal_format = internalformat;
+				}
+			}
+		});
+	}
+
+	pub unsafe fn TexImage3D(target: GLenum, level: i32, internalformat: i32, width: i32, height: i32, depth: i32, border: i32, format: GLenum, type_: GLenum, pixels: *const GLvoid) {
+		with_state(|s| {
+			s.record("TexImage3D", vec![target.into(), level.into(), internalformat.into(), width.into(),
+				height.into(), depth.into(), border.into(), format.into(), type_.into(), pixels.into()]);
+
+			let handle = s.bound_texture();
+			if let Some(tex) = s.textures.get_mut(&handle) {
+				if level == 0 {
+					tex.size = (width, height);
+					tex.depth = depth;
+					tex.internal_format = internalformat;
+				}
+			}
+		});
+	}
+
+	pub unsafe fn TexStorage2D(target: GLenum, levels: i32, internalformat: GLenum, width: i32, height: i32) {
+		with_state(|s| {
+			s.record("TexStorage2D", vec![target.into(), levels.into(), internalformat.into(), width.into(), height.into()]);
+
+			let handle = s.bound_texture();
+			if let Some(tex) = s.textures.get_mut(&handle) {
+				tex.size = (width, height);
+				tex.internal_format = internalformat as i32;
+			}
+		});
+	}
+
+	pub unsafe fn TexStorage3D(target: GLenum, levels: i32, internalformat: GLenum, width: i32, height: i32, depth: i32) {
+		with_state(|s| {
+			s.record("TexStorage3D", vec![target.into(), levels.into(), internalformat.into(), width.into(), height.into(), depth.into()]);
+
+			let handle = s.bound_texture();
+			if let Some(tex) = s.textures.get_mut(&handle) {
+				tex.size = (width, height);
+				tex.depth = depth;
+				tex.internal_format = internalformat as i32;
+			}
+		});
+	}
+
+	pub unsafe fn TexParameteri(target: GLenum, pname: GLenum, param: i32) {
+		with_state(|s| {
+			s.record("TexParameteri", vec![target.into(), pname.into(), param.into()]);
+
+			let handle = s.bound_texture();
+			if let Some(tex) = s.textures.get_mut(&handle) {
+				tex.params.insert(pname, param);
+			}
+		});
+	}
+
+	pub unsafe fn GenFramebuffers(n: i32, framebuffers: *mut u32) {
+		with_state(|s| {
+			s.record("GenFramebuffers", vec![n.into(), framebuffers.into()]);
+			for i in 0..n as isize {
+				let handle = s.alloc_handle();
+				s.framebuffers.insert(handle, FramebufferObject::default());
+				*framebuffers.offset(i) = handle;
+			}
+		});
+	}
+
+	pub unsafe fn DeleteFramebuffers(n: i32, framebuffers: *const u32) {
+		with_state(|s| {
+			s.record("DeleteFramebuffers", vec![n.into(), framebuffers.into()]);
+			for i in 0..n as isize {
+				let handle = *framebuffers.offset(i);
+				s.framebuffers.remove(&handle);
+				if s.framebuffer_binding == handle { s.framebuffer_binding = 0 }
+			}
+		});
+	}
+
+	pub unsafe fn IsFramebuffer(framebuffer: u32) -> GLboolean {
+		with_state(|s| {
+			s.record("IsFramebuffer", vec![framebuffer.into()]);
+			s.framebuffers.contains_key(&framebuffer) as GLboolean
+		})
+	}
+
+	pub unsafe fn FramebufferTexture2D(target: GLenum, attachment: GLenum, textarget: GLenum, texture: u32, level: i32) {
+		with_state(|s| {
+			s.record("FramebufferTexture2D", vec![target.into(), attachment.into(), textarget.into(), texture.into(), level.into()]);
+
+			let handle = s.framebuffer_binding;
+			if let Some(fb) = s.framebuffers.get_mut(&handle) {
+				fb.attachments.insert(attachment, texture);
+			}
+		});
+	}
+
+	pub unsafe fn CheckFramebufferStatus(target: GLenum) -> GLenum {
+		with_state(|s| {
+			s.record("CheckFramebufferStatus", vec![target.into()]);
+
+			match s.framebuffers.get(&s.framebuffer_binding) {
+				Some(fb) if fb.attachments.is_empty() => FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT,
+				_ => FRAMEBUFFER_COMPLETE,
+			}
+		})
+	}
+
+	pub unsafe fn GenRenderbuffers(n: i32, renderbuffers: *mut u32) {
+		with_state(|s| {
+			s.record("GenRenderbuffers", vec![n.into(), renderbuffers.into()]);
+			for i in 0..n as isize {
+				let handle = s.alloc_handle();
+				s.renderbuffers.insert(handle);
+				*renderbuffers.offset(i) = handle;
+			}
+		});
+	}
+
+	pub unsafe fn DeleteRenderbuffers(n: i32, renderbuffers: *const u32) {
+		with_state(|s| {
+			s.record("DeleteRenderbuffers", vec![n.into(), renderbuffers.into()]);
+			for i in 0..n as isize {
+				let handle = *renderbuffers.offset(i);
+				s.renderbuffers.remove(&handle);
+				if s.renderbuffer_binding == handle { s.renderbuffer_binding = 0 }
+			}
+		});
+	}
+
+	pub unsafe fn IsRenderbuffer(renderbuffer: u32) -> GLboolean {
+		with_state(|s| {
+			s.record("IsRenderbuffer", vec![renderbuffer.into()]);
+			s.renderbuffers.contains(&renderbuffer) as GLboolean
+		})
+	}
+
+	pub unsafe fn GenSamplers(count: i32, samplers: *mut u32) {
+		with_state(|s| {
+			s.record("GenSamplers", vec![count.into(), samplers.into()]);
+			for i in 0..count as isize {
+				let handle = s.alloc_handle();
+				s.samplers.insert(handle, HashMap::new());
+				*samplers.offset(i) = handle;
+			}
+		});
+	}
+
+	pub unsafe fn DeleteSamplers(count: i32, samplers: *const u32) {
+		with_state(|s| {
+			s.record("DeleteSamplers", vec![count.into(), samplers.into()]);
+			for i in 0..count as isize {
+				let handle = *samplers.offset(i);
+				s.samplers.remove(&handle);
+				s.sampler_bindings.retain(|_, &mut b| b != handle);
+			}
+		});
+	}
+
+	pub unsafe fn IsSampler(sampler: u32) -> GLboolean {
+		with_state(|s| {
+			s.record("IsSampler", vec![sampler.into()]);
+			s.samplers.contains_key(&sampler) as GLboolean
+		})
+	}
+
+	pub unsafe fn BindSampler(unit: u32, sampler: u32) {
+		with_state(|s| {
+			s.record("BindSampler", vec![unit.into(), sampler.into()]);
+			s.sampler_bindings.insert(unit, sampler);
+		});
+	}
+
+	pub unsafe fn SamplerParameteri(sampler: u32, pname: GLenum, param: i32) {
+		with_state(|s| {
+			s.record("SamplerParameteri", vec![sampler.into(), pname.into(), param.into()]);
+			if let Some(params) = s.samplers.get_mut(&sampler) {
+				params.insert(pname, param);
+			}
+		});
+	}
+
+	pub unsafe fn GetSamplerParameteriv(sampler: u32, pname: GLenum, params: *mut i32) {
+		with_state(|s| {
+			s.record("GetSamplerParameteriv", vec![sampler.into(), pname.into(), params.into()]);
+			if let Some(&param) = s.samplers.get(&sampler).and_then(|p| p.get(&pname)) {
+				*params = param;
+			}
+		});
+	}
+
+	pub unsafe fn GenVertexArrays(n: i32, arrays: *mut u32) {
+		with_state(|s| {
+			s.record("GenVertexArrays", vec![n.into(), arrays.into()]);
+			for i in 0..n as isize {
+				let handle = s.alloc_handle();
+				s.vertex_arrays.insert(handle, VertexArrayObject::default());
+				*arrays.offset(i) = handle;
+			}
+		});
+	}
+
+	pub unsafe fn DeleteVertexArrays(n: i32, arrays: *const u32) {
+		for i in 0..n as isize {
+			let handle = *arrays.offset(i);
+			if handle != 0 && with_state(|s| s.vertex_array_binding == handle) {
+				BindVertexArray(0);
+			}
+		}
+
+		with_state(|s| {
+			s.record("DeleteVertexArrays", vec![n.into(), arrays.into()]);
+			for i in 0..n as isize {
+				let handle = *arrays.offset(i);
+				if handle != 0 { s.vertex_arrays.remove(&handle); }
+			}
+		});
+	}
+
+	pub unsafe fn IsVertexArray(array: u32) -> GLboolean {
+		with_state(|s| {
+			s.record("IsVertexArray", vec![array.into()]);
+			(array != 0 && s.vertex_arrays.contains_key(&array)) as GLboolean
+		})
+	}
+
+	// Swaps the bound VAO's state out of HeadlessState, and the new one's in
+	pub unsafe fn BindVertexArray(array: u32) {
+		with_state(|s| {
+			s.record("BindVertexArray", vec![array.into()]);
+
+			let next = match s.vertex_arrays.get(&array) {
+				Some(vao) => vao.clone(),
+				None => return,
+			};
+
+			let current = VertexArrayObject {
+				element_array_buffer_binding: s.element_array_buffer_binding,
+				enabled_attribs: ::std::mem::replace(&mut s.enabled_attribs, next.enabled_attribs),
+				attrib_divisors: ::std::mem::replace(&mut s.attrib_divisors, next.attrib_divisors),
+			};
+
+			let bound = s.vertex_array_binding;
+			if let Some(vao) = s.vertex_arrays.get_mut(&bound) {
+				*vao = current;
+			}
+
+			s.element_array_buffer_binding = next.element_array_buffer_binding;
+			s.vertex_array_binding = array;
+		});
+	}
+
+	pub unsafe fn VertexAttribDivisor(index: u32, divisor: u32) {
+		with_state(|s| {
+			s.record("VertexAttribDivisor", vec![index.into(), divisor.into()]);
+			s.attrib_divisors.insert(index, divisor);
+		});
+	}
+
+	pub unsafe fn DrawBuffers(n: i32, bufs: *const GLenum) {
+		with_state(|s| {
+			s.record("DrawBuffers", vec![n.into(), bufs.into()]);
+			s.draw_buffers = ::std::slice::from_raw_parts(bufs, n as usize).to_vec();
+		});
+	}
+
+	pub unsafe fn CreateShader(type_: GLenum) -> u32 {
+		with_state(|s| {
+			s.record("CreateShader", vec![type_.into()]);
+			let handle = s.alloc_handle();
+			s.shaders.insert(handle, ShaderObject{ ty: type_, ..ShaderObject::default() });
+			handle
+		})
+	}
+
+	pub unsafe fn DeleteShader(shader: u32) {
+		with_state(|s| {
+			s.record("DeleteShader", vec![shader.into()]);
+			s.shaders.remove(&shader);
+		});
+	}
+
+	pub unsafe fn IsShader(shader: u32) -> GLboolean {
+		with_state(|s| {
+			s.record("IsShader", vec![shader.into()]);
+			s.shaders.contains_key(&shader) as GLboolean
+		})
+	}
+
+	pub unsafe fn ShaderSource(shader: u32, count: i32, string: *const *const GLchar, length: *const i32) {
+		let mut source = String::new();
+		for i in 0..count as isize {
+			let part = *string.offset(i);
+			if length.is_null() || *length.offset(i) < 0 {
+				source.push_str(&CStr::from_ptr(part).to_string_lossy());
+			} else {
+				let bytes = ::std::slice::from_raw_parts(part as *const u8, *length.offset(i) as usize);
+				source.push_str(&String::from_utf8_lossy(bytes));
+			}
+		}
+
+		with_state(|s| {
+			s.record("ShaderSource", vec![shader.into(), count.into(), string.into(), length.into()]);
+			if let Some(sh) = s.shaders.get_mut(&shader) {
+				sh.source = source;
+			}
+		});
+	}
+
+	pub unsafe fn CompileShader(shader: u32) {
+		with_state(|s| {
+			s.record("CompileShader", vec![shader.into()]);
+			if let Some(sh) = s.shaders.get_mut(&shader) {
+				match compile_source(&sh.source) {
+					Ok(()) => { sh.compiled = true; sh.info_log.clear(); }
+					Err(log) => { sh.compiled = false; sh.info_log = log; }
+				}
+			}
+		});
+	}
+
+	pub unsafe fn GetShaderiv(shader: u32, pname: GLenum, params: *mut i32) {
+		with_state(|s| {
+			s.record("GetShaderiv", vec![shader.into(), pname.into(), params.into()]);
+			if let Some(sh) = s.shaders.get(&shader) {
+				match pname {
+					COMPILE_STATUS => *params = sh.compiled as i32,
+					SHADER_TYPE => *params = sh.ty as i32,
+					INFO_LOG_LENGTH => *params = if sh.info_log.is_empty() { 0 } else { sh.info_log.len() as i32 + 1 },
+					SHADER_SOURCE_LENGTH => *params = sh.source.len() as i32 + 1,
+					_ => {}
+				}
+			}
+		});
+	}
+
+	pub unsafe fn GetShaderInfoLog(shader: u32, bufSize: i32, length: *mut i32, infoLog: *mut GLchar) {
+		with_state(|s| {
+			s.record("GetShaderInfoLog", vec![shader.into(), bufSize.into(), length.into(), infoLog.into()]);
+			if let Some(sh) = s.shaders.get(&shader) {
+				write_c_str(&sh.info_log, bufSize, length, infoLog);
+			}
+		});
+	}
+
+	pub unsafe fn CreateProgram() -> u32 {
+		with_state(|s| {
+			s.record("CreateProgram", vec![]);
+			let handle = s.alloc_handle();
+			s.programs.insert(handle, ProgramObject::default());
+			handle
+		})
+	}
+
+	pub unsafe fn DeleteProgram(program: u32) {
+		with_state(|s| {
+			s.record("DeleteProgram", vec![program.into()]);
+			s.programs.remove(&program);
+		});
+	}
+
+	pub unsafe fn IsProgram(program: u32) -> GLboolean {
+		with_state(|s| {
+			s.record("IsProgram", vec![program.into()]);
+			s.programs.contains_key(&program) as GLboolean
+		})
+	}
+
+	pub unsafe fn AttachShader(program: u32, shader: u32) {
+		with_state(|s| {
+			s.record("AttachShader", vec![program.into(), shader.into()]);
+			if let Some(p) = s.programs.get_mut(&program) {
+				p.shaders.push(shader);
+			}
+		});
+	}
+
+	pub unsafe fn BindAttribLocation(program: u32, index: u32, name: *const GLchar) {
+		let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+
+		with_state(|s| {
+			s.record("BindAttribLocation", vec![program.into(), index.into(), name.as_str().into()]);
+			if let Some(p) = s.programs.get_mut(&program) {
+				p.attrib_bindings.insert(name, index);
+			}
+		});
+	}
+
+	pub unsafe fn LinkProgram(program: u32) {
+		with_state(|s| {
+			s.record("LinkProgram", vec![program.into()]);
+
+			let all_compiled = match s.programs.get(&program) {
+				Some(p) => p.shaders.iter().all(|sh| s.shaders.get(sh).map_or(true, |sh| sh.compiled)),
+				None => return,
+			};
+
+			let vertex_sources = s.programs[&program].shaders.iter()
+				.filter_map(|sh| s.shaders.get(sh))
+				.filter(|sh| sh.ty == VERTEX_SHADER)
+				.map(|sh| sh.source.as_str())
+				.collect::<Vec<_>>();
+
+			let declared = vertex_sources.iter()
+				.flat_map(|src| declarations(src, if is_es300(src) { "in" } else { "attribute" }))
+				.map(|(name, ty, _)| (name, ty))
+				.collect::<Vec<_>>();
+
+			let explicit_locations = vertex_sources.iter()
+				.flat_map(|src| layout_locations(src))
+				.collect::<HashMap<_, _>>();
+
+			let mut uniforms: Vec<(String, u32, i32)> = Vec::new();
+			let mut uniform_blocks: Vec<String> = Vec::new();
+			for sh in s.programs[&program].shaders.iter().filter_map(|sh| s.shaders.get(sh)) {
+				for decl in declarations(&sh.source, "uniform") {
+					if !uniforms.iter().any(|u| u.0 == decl.0) { uniforms.push(decl) }
+				}
+
+				for block in block_declarations(&sh.source) {
+					if !uniform_blocks.contains(&block) { uniform_blocks.push(block) }
+				}
+			}
+
+			let p = s.programs.get_mut(&program).unwrap();
+			p.linked = all_compiled;
+			p.info_log = if all_compiled {
+				String::new()
+			} else {
+				"ERROR: One or more attached shaders not successfully compiled\n".to_string()
+			};
+
+			// layout(location) wins over bindings, and anything else gets the lowest location not
+			// taken by either
+			let mut next_free = 0;
+			p.active_attributes = declared.into_iter()
+				.map(|(name, ty)| {
+					let location = match explicit_locations.get(&name).or_else(|| p.attrib_bindings.get(&name)) {
+						Some(&loc) => loc,
+						None => {
+							while p.attrib_bindings.values().chain(explicit_locations.values()).any(|&l| l == next_free) { next_free += 1 }
+							next_free += 1;
+							next_free - 1
+						}
+					};
+
+					(name, ty, location)
+				})
+				.collect();
+
+			// Array elements get consecutive locations, and the bare array name aliases the first
+			p.uniform_locations.clear();
+			let mut next_location = 0;
+			for &(ref name, _, size) in uniforms.iter() {
+				p.uniform_locations.insert(name.clone(), next_location);
+
+				if let Some(base) = name.find("[0]").map(|i| &name[..i]) {
+					p.uniform_locations.insert(base.to_string(), next_location);
+					for i in 1..size {
+						p.uniform_locations.insert(format!("{}[{}]", base, i), next_location + i);
+					}
+				}
+
+				next_location += size;
+			}
+
+			p.active_uniforms = uniforms;
+			p.uniform_blocks = uniform_blocks;
+		});
+	}
+
+	pub unsafe fn GetProgramiv(program: u32, pname: GLenum, params: *mut i32) {
+		with_state(|s| {
+			s.record("GetProgramiv", vec![program.into(), pname.into(), params.into()]);
+			if let Some(p) = s.programs.get(&program) {
+				match pname {
+					LINK_STATUS => *params = p.linked as i32,
+					ATTACHED_SHADERS => *params = p.shaders.len() as i32,
+					INFO_LOG_LENGTH => *params = if p.info_log.is_empty() { 0 } else { p.info_log.len() as i32 + 1 },
+					ACTIVE_ATTRIBUTES => *params = p.active_attributes.len() as i32,
+					ACTIVE_UNIFORMS => *params = p.active_uniforms.len() as i32,
+					ACTIVE_UNIFORM_BLOCKS => *params = p.uniform_blocks.len() as i32,
+					ACTIVE_UNIFORM_MAX_LENGTH => *params = p.active_uniforms.iter()
+						.map(|&(ref name, _, _)| name.len() as i32 + 1)
+						.max().unwrap_or(0),
+					ACTIVE_ATTRIBUTE_MAX_LENGTH => *params = p.active_attributes.iter()
+						.map(|&(ref name, _, _)| name.len() as i32 + 1)
+						.max().unwrap_or(0),
+					_ => {}
+				}
+			}
+		});
+	}
+
+	pub unsafe fn GetActiveAttrib(program: u32, index: u32, bufSize: i32, length: *mut i32, size: *mut i32, type_: *mut GLenum, name: *mut GLchar) {
+		with_state(|s| {
+			s.record("GetActiveAttrib", vec![program.into(), index.into(), bufSize.into(), length.into(), size.into(), type_.into(), name.into()]);
+
+			let attrib = s.programs.get(&program)
+				.and_then(|p| p.active_attributes.get(index as usize));
+
+			if let Some(&(ref attrib_name, ty, _)) = attrib {
+				*size = 1;
+				*type_ = ty;
+				write_c_str(attrib_name, bufSize, length, name);
+			}
+		});
+	}
+
+	pub unsafe fn GetActiveUniform(program: u32, index: u32, bufSize: i32, length: *mut i32, size: *mut i32, type_: *mut GLenum, name: *mut GLchar) {
+		with_state(|s| {
+			s.record("GetActiveUniform", vec![program.into(), index.into(), bufSize.into(), length.into(), size.into(), type_.into(), name.into()]);
+
+			let uniform = s.programs.get(&program)
+				.and_then(|p| p.active_uniforms.get(index as usize));
+
+			if let Some(&(ref uniform_name, ty, uniform_size)) = uniform {
+				*size = uniform_size;
+				*type_ = ty;
+				write_c_str(uniform_name, bufSize, length, name);
+			}
+		});
+	}
+
+	pub unsafe fn GetUniformBlockIndex(program: u32, uniformBlockName: *const GLchar) -> u32 {
+		let name = CStr::from_ptr(uniformBlockName).to_string_lossy().into_owned();
+
+		with_state(|s| {
+			s.record("GetUniformBlockIndex", vec![program.into(), name.as_str().into()]);
+			s.programs.get(&program)
+				.and_then(|p| p.uniform_blocks.iter().position(|b| *b == name))
+				.map_or(INVALID_INDEX, |idx| idx as u32)
+		})
+	}
+
+	pub unsafe fn UniformBlockBinding(program: u32, uniformBlockIndex: u32, uniformBlockBinding: u32) {
+		with_state(|s| {
+			s.record("UniformBlockBinding", vec![program.into(), uniformBlockIndex.into(), uniformBlockBinding.into()]);
+			if let Some(p) = s.programs.get_mut(&program) {
+				p.block_bindings.insert(uniformBlockIndex, uniformBlockBinding);
+			}
+		});
+	}
+
+	pub unsafe fn GetActiveUniformBlockiv(program: u32, uniformBlockIndex: u32, pname: GLenum, params: *mut i32) {
+		with_state(|s| {
+			s.record("GetActiveUniformBlockiv", vec![program.into(), uniformBlockIndex.into(), pname.into(), params.into()]);
+			if let Some(p) = s.programs.get(&program) {
+				match pname {
+					UNIFORM_BLOCK_BINDING => *params = p.block_bindings.get(&uniformBlockIndex).cloned().unwrap_or(0) as i32,
+					_ => {}
+				}
+			}
+		});
+	}
+
+	pub unsafe fn GetActiveUniformBlockName(program: u32, uniformBlockIndex: u32, bufSize: i32, length: *mut i32, uniformBlockName: *mut GLchar) {
+		with_state(|s| {
+			s.record("GetActiveUniformBlockName", vec![program.into(), uniformBlockIndex.into(), bufSize.into(), length.into(), uniformBlockName.into()]);
+			if let Some(name) = s.programs.get(&program).and_then(|p| p.uniform_blocks.get(uniformBlockIndex as usize)) {
+				write_c_str(name, bufSize, length, uniformBlockName);
+			}
+		});
+	}
+
+	pub unsafe fn GetProgramInfoLog(program: u32, bufSize: i32, length: *mut i32, infoLog: *mut GLchar) {
+		with_state(|s| {
+			s.record("GetProgramInfoLog", vec![program.into(), bufSize.into(), length.into(), infoLog.into()]);
+			if let Some(p) = s.programs.get(&program) {
+				write_c_str(&p.info_log, bufSize, length, infoLog);
+			}
+		});
+	}
+
+	pub unsafe fn UseProgram(program: u32) {
+		with_state(|s| {
+			s.record("UseProgram", vec![program.into()]);
+			s.current_program = program;
+		});
+	}
+
+	pub unsafe fn GetAttribLocation(program: u32, name: *const GLchar) -> i32 {
+		let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+
+		with_state(|s| {
+			s.record("GetAttribLocation", vec![program.into(), name.as_str().into()]);
+			s.programs.get(&program)
+				.and_then(|p| p.active_attributes.iter().find(|a| a.0 == name))
+				.map_or(-1, |a| a.2 as i32)
+		})
+	}
+
+	pub unsafe fn GetUniformLocation(program: u32, name: *const GLchar) -> i32 {
+		let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+
+		with_state(|s| {
+			s.record("GetUniformLocation", vec![program.into(), name.as_str().into()]);
+			s.programs.get(&program)
+				.and_then(|p| p.uniform_locations.get(&name))
+				.map_or(-1, |&loc| loc)
+		})
+	}
+
+	pub unsafe fn Enable(cap: GLenum) {
+		with_state(|s| {
+			s.record("Enable", vec![cap.into()]);
+			s.enabled_caps.insert(cap);
+		});
+	}
+
+	pub unsafe fn Disable(cap: GLenum) {
+		with_state(|s| {
+			s.record("Disable", vec![cap.into()]);
+			s.enabled_caps.remove(&cap);
+		});
+	}
+
+	pub unsafe fn IsEnabled(cap: GLenum) -> GLboolean {
+		with_state(|s| {
+			s.record("IsEnabled", vec![cap.into()]);
+			s.enabled_caps.contains(&cap) as GLboolean
+		})
+	}
+
+	pub unsafe fn EnableVertexAttribArray(index: u32) {
+		with_state(|s| {
+			s.record("EnableVertexAttribArray", vec![index.into()]);
+			s.enabled_attribs.insert(index);
+		});
+	}
+
+	pub unsafe fn DisableVertexAttribArray(index: u32) {
+		with_state(|s| {
+			s.record("DisableVertexAttribArray", vec![index.into()]);
+			s.enabled_attribs.remove(&index);
+		});
+	}
+
+	pub unsafe fn GetIntegerv(pname: GLenum, data: *mut i32) {
+		with_state(|s| {
+			s.record("GetIntegerv", vec![pname.into(), data.into()]);
+			match pname {
+				CURRENT_PROGRAM => *data = s.current_program as i32,
+				ACTIVE_TEXTURE => *data = (TEXTURE0 + s.active_texture_unit) as i32,
+				TEXTURE_BINDING_2D => *data = s.bound_texture() as i32,
+				ARRAY_BUFFER_BINDING => *data = s.array_buffer_binding as i32,
+				ELEMENT_ARRAY_BUFFER_BINDING => *data = s.element_array_buffer_binding as i32,
+				FRAMEBUFFER_BINDING => *data = s.framebuffer_binding as i32,
+				RENDERBUFFER_BINDING => *data = s.renderbuffer_binding as i32,
+				VERTEX_ARRAY_BINDING => *data = s.vertex_array_binding as i32,
+				UNIFORM_BUFFER_BINDING => *data = s.bound_buffer(UNIFORM_BUFFER) as i32,
+				SAMPLER_BINDING => *data = s.sampler_bindings.get(&s.active_texture_unit).cloned().unwrap_or(0) as i32,
+				_ => if let Some(limit) = s.limit(pname) { *data = limit },
+			}
+		});
+	}
+
+	pub unsafe fn GetError() -> GLenum {
+		record("GetError", vec![]);
+		NO_ERROR
+	}
+
+	pub unsafe fn GetString(name: GLenum) -> *const GLubyte {
+		record("GetString", vec![name.into()]);
+		b"\0".as_ptr()
+	}
+
+	pub unsafe fn GetStringi(name: GLenum, index: u32) -> *const GLubyte {
+		record("GetStringi", vec![name.into(), index.into()]);
+		b"\0".as_ptr()
+	}
+}
+
+pub mod emscripten {
+	use super::*;
+	use bindings::emscripten::*;
+
+	use std::ffi::CStr;
+
+	pub unsafe fn emscripten_webgl_init_context_attributes(attributes: *mut EmscriptenWebGLContextAttributes) {
+		record("emscripten_webgl_init_context_attributes", vec![attributes.into()]);
+
+		*attributes = EmscriptenWebGLContextAttributes {
+			alpha: 1,
+			depth: 1,
+			stencil: 0,
+			antialias: 1,
+			premultipliedAlpha: 1,
+			preserveDrawingBuffer: 0,
+			preferLowPowerToHighPerformance: 0,
+			failIfMajorPerformanceCaveat: 0,
+			majorVersion: 1,
+			minorVersion: 0,
+			enableExtensionsByDefault: 1,
+			explicitSwapControl: 0,
+		};
+	}
+
+	pub unsafe fn emscripten_webgl_create_context(target: *const CChar, attributes: *const EmscriptenWebGLContextAttributes) -> EMSCRIPTEN_WEBGL_CONTEXT_HANDLE {
+		let target = CStr::from_ptr(target).to_string_lossy().into_owned();
+
+		with_state(|s| {
+			s.record("emscripten_webgl_create_context", vec![target.as_str().into(), attributes.into()]);
+
+			let attributes = *attributes;
+			if attributes.majorVersion >= 2 && !s.webgl2_supported {
+				return 0
+			}
+
+			let enabled_extensions = if attributes.enableExtensionsByDefault != 0 {
+				s.supported_extensions.iter()
+					.filter(|e| s.extension_available(e, attributes.majorVersion))
+					.cloned()
+					.collect()
+			} else {
+				HashSet::new()
+			};
+
+			let handle = s.alloc_handle() as EMSCRIPTEN_WEBGL_CONTEXT_HANDLE;
+			s.contexts.insert(handle, ContextObject{ target, attributes, enabled_extensions, lost: false });
+			handle
+		})
+	}
+
+	pub unsafe fn emscripten_webgl_make_context_current(context: EMSCRIPTEN_WEBGL_CONTEXT_HANDLE) -> i32 {
+		with_state(|s| {
+			s.record("emscripten_webgl_make_context_current", vec![context.into()]);
+
+			if s.make_current_fails {
+				EMSCRIPTEN_RESULT_FAILED
+			} else if s.contexts.contains_key(&context) {
+				s.current_context = context;
+				EMSCRIPTEN_RESULT_SUCCESS
+			} else {
+				EMSCRIPTEN_RESULT_INVALID_PARAM
+			}
+		})
+	}
+
+	pub unsafe fn emscripten_webgl_get_current_context() -> EMSCRIPTEN_WEBGL_CONTEXT_HANDLE {
+		with_state(|s| {
+			s.record("emscripten_webgl_get_current_context", vec![]);
+			s.current_context
+		})
+	}
+
+	pub unsafe fn emscripten_webgl_destroy_context(context: EMSCRIPTEN_WEBGL_CONTEXT_HANDLE) -> i32 {
+		with_state(|s| {
+			s.record("emscripten_webgl_destroy_context", vec![context.into()]);
+
+			if s.current_context == context { s.current_context = 0 }
+			match s.contexts.remove(&context) {
+				Some(_) => EMSCRIPTEN_RESULT_SUCCESS,
+				None => EMSCRIPTEN_RESULT_INVALID_PARAM,
+			}
+		})
+	}
+
+	// Completed by headless::complete_fetches, so callbacks never run inside the call like in a browser
+	pub unsafe fn emscripten_async_wget_data(url: *const CChar, arg: *mut CVoid, onload: em_async_wget_onload_func, onerror: em_arg_callback_func) {
+		let url = CStr::from_ptr(url).to_string_lossy().into_owned();
+
+		with_state(|s| {
+			s.record("emscripten_async_wget_data", vec![url.as_str().into(), arg.into()]);
+			s.pending_fetches.push(PendingFetch { url, arg, onload, onerror });
+		});
+	}
+
+	pub unsafe fn emscripten_set_webglcontextlost_callback(target: *const CChar, userData: *mut CVoid, useCapture: i32, callback: em_webgl_context_callback) -> i32 {
+		let target = CStr::from_ptr(target).to_string_lossy().into_owned();
+
+		with_state(|s| {
+			s.record("emscripten_set_webglcontextlost_callback", vec![target.as_str().into(), userData.into(), useCapture.into()]);
+			s.lost_callbacks.insert(target, ContextCallback { arg: userData, callback });
+			EMSCRIPTEN_RESULT_SUCCESS
+		})
+	}
+
+	pub unsafe fn emscripten_set_webglcontextrestored_callback(target: *const CChar, userData: *mut CVoid, useCapture: i32, callback: em_webgl_context_callback) -> i32 {
+		let target = CStr::from_ptr(target).to_string_lossy().into_owned();
+
+		with_state(|s| {
+			s.record("emscripten_set_webglcontextrestored_callback", vec![target.as_str().into(), userData.into(), useCapture.into()]);
+			s.restored_callbacks.insert(target, ContextCallback { arg: userData, callback });
+			EMSCRIPTEN_RESULT_SUCCESS
+		})
+	}
+
+	pub unsafe fn emscripten_webgl_enable_extension(context: EMSCRIPTEN_WEBGL_CONTEXT_HANDLE, extension: *const CChar) -> i32 {
+		let extension = CStr::from_ptr(extension).to_string_lossy().into_owned();
+
+		with_state(|s| {
+			s.record("emscripten_webgl_enable_extension", vec![context.into(), extension.as_str().into()]);
+
+			let supported = s.contexts.get(&context)
+				.map_or(false, |c| s.extension_available(&extension, c.attributes.majorVersion));
+
+			match s.contexts.get_mut(&context) {
+				Some(ctx) if supported => {
+					ctx.enabled_extensions.insert(extension);
+					1
+				}
+
+				_ => 0,
+			}
+		})
+	}
+}
@@ -1,4 +1,6 @@
-
 #[macro_use]
 pub mod emscripten;
-pub mod gl;
\ No newline at end of file
+pub mod gl;
+
+#[cfg(feature = "headless")]
+pub mod headless;
\ No newline at end of file
@@ -0,0 +1,117 @@
+use std;
+use std::fmt;
+
+use bindings::emscripten::*;
+use math::Vec2i;
+use rendering::uniform::glsl_type_name;
+use rendering::glsl::Diagnostic;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ShaderStage {
+	Vertex,
+	Fragment,
+}
+
+#[derive(Clone, Debug)]
+pub enum Error {
+	/// Holds the EMSCRIPTEN_RESULT_* code returned while creating or activating a context
+	ContextCreation(i32),
+
+	/// `listing` is the compiled source, with the lines `diagnostics` refer to marked
+	ShaderCompile { stage: ShaderStage, log: String, diagnostics: Vec<Diagnostic>, listing: String },
+	ShaderLink { log: String },
+
+	UnknownSnippet(String),
+	/// Holds the chain of includes that formed the cycle
+	SnippetCycle(String),
+
+	/// The shader has an active attribute that the vertex layout doesn't provide
+	MissingAttribute(String),
+	/// Widths are in components - `expected` is the shader's, `given` is the layout's
+	AttributeWidthMismatch { name: String, expected: i32, given: i32 },
+	AttributeLocationMismatch { name: String, expected: u32, given: i32 },
+
+	/// Types are GL type enums - `expected` is the uniform's, `given` is the value's
+	UniformTypeMismatch { name: String, expected: u32, given: u32 },
+
+	/// Holds the url that couldn't be fetched
+	Fetch(String),
+
+	ImageDecode(String),
+	InvalidTextureSize(Vec2i),
+	NotEnoughTextureData { expected: usize, given: usize },
+
+	IndexOverflow { index: usize, max: usize },
+	NotEnoughElements { expected: usize, given: usize },
+	/// Sizes are in bytes - `expected` is the mesh's vertex size
+	VertexSizeMismatch { expected: usize, given: usize },
+	/// Types are GL type enums - `expected` is the mesh's index type
+	IndexTypeMismatch { expected: u32, given: u32 },
+	NotIndexed,
+	/// Holds what was wrong with the argument
+	InvalidArgument(String),
+
+	IncompleteFramebuffer(u32),
+	MissingExtension(&'static str),
+	/// Extensions that were enabled before a context loss, but aren't supported by the restored context
+	ExtensionsLost(Vec<String>),
+}
+
+impl fmt::Display for ShaderStage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ShaderStage::Vertex => write!(f, "vertex"),
+			ShaderStage::Fragment => write!(f, "fragment"),
+		}
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::ContextCreation(EMSCRIPTEN_RESULT_NOT_SUPPORTED) => write!(f, "WebGL not supported"),
+			Error::ContextCreation(EMSCRIPTEN_RESULT_FAILED_NOT_DEFERRED) => write!(f, "WebGL context creation failed (FAILED_NOT_DEFERRED)"),
+			Error::ContextCreation(EMSCRIPTEN_RESULT_FAILED) => write!(f, "WebGL context creation failed (FAILED)"),
+			Error::ContextCreation(code) => write!(f, "WebGL context creation failed ({})", code),
+
+			Error::ShaderCompile { stage, ref log, ref listing, .. } =>
+				write!(f, "Failed to compile {} shader:\n{}\n{}", stage, log, listing),
+			Error::ShaderLink { ref log } => write!(f, "Failed to link shader program:\n{}", log),
+
+			Error::UnknownSnippet(ref name) => write!(f, "Included snippet '{}' is not registered", name),
+			Error::SnippetCycle(ref chain) => write!(f, "Snippets include each other: {}", chain),
+
+			Error::MissingAttribute(ref name) => write!(f, "Vertex layout has no binding for attribute '{}'", name),
+			Error::AttributeWidthMismatch { ref name, expected, given } =>
+				write!(f, "Attribute '{}' has {} components in the shader but {} in the vertex layout", name, expected, given),
+			Error::AttributeLocationMismatch { ref name, expected, given } =>
+				write!(f, "Attribute '{}' is bound to location {} but the vertex layout expects {}", name, given, expected),
+			Error::UniformTypeMismatch { ref name, expected, given } =>
+				write!(f, "Uniform '{}' has type {} but was given a {}", name, glsl_type_name(expected), glsl_type_name(given)),
+
+			Error::Fetch(ref url) => write!(f, "Failed to fetch '{}'", url),
+
+			Error::ImageDecode(ref msg) => write!(f, "Failed to decode image: {}", msg),
+			Error::InvalidTextureSize(size) => write!(f, "Textures must be POW2, got {}x{}", size.x, size.y),
+			Error::NotEnoughTextureData { expected, given } =>
+				write!(f, "Passed slice not large enough: expected {} texels, got {}", expected, given),
+
+			Error::IndexOverflow { index, max } => write!(f, "Index {} exceeds maximum index {}", index, max),
+			Error::NotEnoughElements { expected, given } =>
+				write!(f, "Not enough elements: expected at least {}, got {}", expected, given),
+			Error::VertexSizeMismatch { expected, given } =>
+				write!(f, "Vertices are {} bytes but the mesh's are {}", given, expected),
+			Error::IndexTypeMismatch { expected, given } =>
+				write!(f, "Indices have type 0x{:X} but the mesh's have type 0x{:X}", given, expected),
+			Error::NotIndexed => write!(f, "Mesh has no indices"),
+			Error::InvalidArgument(ref msg) => write!(f, "Invalid argument: {}", msg),
+
+			Error::IncompleteFramebuffer(status) => write!(f, "Framebuffer incomplete (status 0x{:X})", status),
+			Error::MissingExtension(name) => write!(f, "Required extension {} is not available", name),
+			Error::ExtensionsLost(ref names) =>
+				write!(f, "Extensions no longer available after the context was restored: {}", names.join(", ")),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
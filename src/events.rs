@@ -6,7 +6,7 @@ use common::math::vector::*;
 use std::ffi::CStr;
 
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum KeyCode {
 	Space, Tab, Backspace,
 	Insert, Delete,
@@ -34,6 +34,11 @@ pub enum Event {
 
 	KeyDown(KeyCode),
 	KeyUp(KeyCode),
+
+	/// Delivered through WebGLContext::poll_events. GL objects are gone once the context is
+	/// lost, and need recreating with WebGLContext::restore once it's restored
+	ContextLost,
+	ContextRestored,
 }
 
 
@@ -0,0 +1,122 @@
+// Typed handles for the WebGL extensions this crate knows how to use. A handle can only be had
+// from WebGLContext::enable_extension or current_extension, so holding one means the extension
+// is available. Enabled extensions are used through the regular gl entry points - emscripten
+// routes e.g. glDrawArraysInstanced to ANGLE_instanced_arrays under WebGL1.
+
+pub trait Extension: Copy {
+	const NAME: &'static str;
+
+	/// Whether WebGL2 has this built in. Such extensions aren't exposed by WebGL2 contexts, so
+	/// they're handed out without asking the browser
+	const CORE_IN_WEBGL2: bool;
+
+	#[doc(hidden)]
+	fn assume_enabled() -> Self;
+}
+
+macro_rules! extension_handles {
+	( $( $(#[$attr:meta])* $ty:ident = $name:expr, core_in_webgl2: $core:expr; )* ) => {
+		$(
+			$(#[$attr])*
+			#[derive(Copy, Clone, Debug, PartialEq)]
+			pub struct $ty { _private: () }
+
+			impl Extension for $ty {
+				const NAME: &'static str = $name;
+				const CORE_IN_WEBGL2: bool = $core;
+
+				fn assume_enabled() -> Self { $ty { _private: () } }
+			}
+		)*
+
+		pub const KNOWN_EXTENSIONS: &'static [&'static str] = &[ $( $name ),* ];
+	};
+}
+
+extension_handles! {
+	/// 32-bit element indices
+	OesElementIndexUint = "OES_element_index_uint", core_in_webgl2: true;
+	/// FLOAT textures, sampled with NEAREST filtering
+	OesTextureFloat = "OES_texture_float", core_in_webgl2: true;
+	OesVertexArrayObject = "OES_vertex_array_object", core_in_webgl2: true;
+	AngleInstancedArrays = "ANGLE_instanced_arrays", core_in_webgl2: true;
+	WebglDepthTexture = "WEBGL_depth_texture", core_in_webgl2: true;
+	/// dFdx, dFdy and fwidth in GLSL ES 1.00 shaders
+	OesStandardDerivatives = "OES_standard_derivatives", core_in_webgl2: true;
+	/// gl_FragData and multiple color attachments
+	WebglDrawBuffers = "WEBGL_draw_buffers", core_in_webgl2: true;
+	/// Rendering to float color buffers. Only available under WebGL2
+	ExtColorBufferFloat = "EXT_color_buffer_float", core_in_webgl2: false;
+}
+
+#[cfg(all(test, feature = "headless"))] mod tests {
+	use bindings::headless;
+	use webgl::*;
+	use error::Error;
+	use super::*;
+
+	#[test]
+	fn webgl1_extensions() {
+		headless::reset();
+
+		let ctx = WebGLContext::new(false);
+		assert!(ctx.extension::<OesVertexArrayObject>().is_none());
+		assert!(!ctx.capabilities().vertex_array_objects);
+
+		assert!(ctx.enable_extension::<OesVertexArrayObject>().is_some());
+		assert!(ctx.capabilities().vertex_array_objects);
+		assert!(capabilities().vertex_array_objects);
+		assert!(current_extension::<OesVertexArrayObject>().is_some());
+
+		assert!(ctx.enable_extension::<WebglDrawBuffers>().is_some());
+		assert_eq!(ctx.capabilities().max_draw_buffers, 8);
+
+		// Only exposed by WebGL2
+		assert!(ctx.enable_extension::<ExtColorBufferFloat>().is_none());
+		assert!(!ctx.capabilities().color_buffer_float);
+
+		headless::with_state(|s| s.supported_extensions.remove("OES_texture_float"));
+		match ctx.try_enable_extension::<OesTextureFloat>() {
+			Err(Error::MissingExtension("OES_texture_float")) => {}
+			_ => panic!("Expected missing extension error"),
+		}
+
+		// Already enabled extensions aren't asked for again
+		let before = headless::calls_named("emscripten_webgl_enable_extension").len();
+		ctx.enable_extension::<OesVertexArrayObject>();
+		assert_eq!(headless::calls_named("emscripten_webgl_enable_extension").len(), before);
+	}
+
+	#[test]
+	fn webgl2_and_context_switching() {
+		headless::reset();
+
+		let webgl1 = WebGLContextBuilder::new()
+			.enable_extensions_by_default(true)
+			.finalize();
+
+		// Everything emscripten enabled is tracked
+		assert!(webgl1.has_extension("ANGLE_instanced_arrays"));
+		assert!(webgl1.capabilities().instancing && webgl1.capabilities().depth_textures);
+
+		let webgl2 = WebGLContext::new_webgl2(false);
+		headless::take_calls();
+
+		assert!(webgl2.enable_extension::<AngleInstancedArrays>().is_some());
+		assert!(headless::calls_named("emscripten_webgl_enable_extension").is_empty());
+
+		assert!(webgl2.extension::<ExtColorBufferFloat>().is_none());
+		assert!(webgl2.enable_extension::<ExtColorBufferFloat>().is_some());
+		assert!(current_extension::<ExtColorBufferFloat>().is_some());
+
+		webgl1.make_current().unwrap();
+		assert!(current_extension::<ExtColorBufferFloat>().is_none());
+		assert!(current_extension::<AngleInstancedArrays>().is_some());
+
+		// Enabling on a context that isn't current leaves the current one alone
+		headless::with_state(|s| s.supported_extensions.insert("WEBGL_lose_context".to_string()));
+		assert!(webgl2.enable_extension_by_name("WEBGL_lose_context"));
+		assert!(webgl1.is_current());
+		assert_eq!(capabilities(), webgl1.capabilities());
+	}
+}
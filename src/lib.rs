@@ -5,19 +5,29 @@ pub extern crate common;
 pub use common::*;
 
 extern crate png;
+extern crate web_common_derive;
+
+// Lets code generated by web_common_derive name this crate from inside it too
+extern crate self as web_common;
 
 #[macro_use] pub mod bindings;
 #[macro_use] pub mod coro_util;
 
+pub mod error;
 pub mod rendering;
 pub mod events;
 pub mod webgl;
+pub mod extensions;
+pub mod render_state;
 
 pub mod paper;
 
 pub use bindings::emscripten::*;
+pub use error::{Error, ShaderStage};
 pub use coro_util::*;
 pub use webgl::*;
+pub use extensions::*;
+pub use render_state::*;
 
 pub use paper::*;
 
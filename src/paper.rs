@@ -1,27 +1,21 @@
 use rendering::shader::*;
 use rendering::mesh::*;
+use rendering::types::PackedColor;
 use rendering::gl;
 
 use common::math::*;
 
 #[allow(dead_code)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Vertex)]
+#[repr(C)]
 struct PaperVertex {
 	pos: Vec2,
-	color: Color,
+	color: PackedColor,
 }
 
 impl PaperVertex {
 	pub fn new<C>(pos: Vec2, color: C) -> Self where C: Into<Color> {
-		PaperVertex {pos, color: Into::into(color)}
-	}
-}
-
-impl Vertex for PaperVertex {
-	fn get_layout() -> VertexLayout {
-		VertexLayout::new::<Self>()
-			.add_binding(0, 2, 0)
-			.add_binding(1, 4, 8)
+		PaperVertex {pos, color: PackedColor::new(color)}
 	}
 }
 
@@ -39,7 +33,7 @@ impl Paper {
 	pub fn new() -> Self {
 		Paper {
 			builder: MeshBuilder::new(),
-			mesh: Mesh::new(),
+			mesh: Mesh::with_usage(BufferUsage::Stream),
 
 			vert_buffer: Vec::with_capacity(16),
 
@@ -53,6 +47,7 @@ impl Paper {
 
 	pub fn build_shader() -> Shader {
 		ShaderBuilder::new()
+			.vertex_layout::<PaperVertex>()
 			.frag_attribute("color", "vec4")
 			.use_proj()
 			.output("v_color")
@@ -211,4 +206,64 @@ impl Paper {
 
 		self.builder.add_convex_poly(&self.vert_buffer);
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, feature = "headless"))] mod tests {
+	use bindings::headless;
+	use rendering::gl;
+	use paper::*;
+
+	#[test]
+	fn draw() {
+		headless::reset();
+
+		let mut paper = Paper::new();
+		paper.build_line(&[Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(1.0, 1.0)], 0.1, Color::white());
+		paper.build_circle(Vec2::zero(), 0.5, Color::white());
+
+		let num_indices = paper.builder.get_index_count();
+		assert!(num_indices > 0);
+
+		headless::take_calls();
+		paper.draw();
+
+		let pointers = headless::calls_named("VertexAttribPointer");
+		assert_eq!(pointers.len(), 2);
+		assert_eq!(pointers[1].int(1), 4);
+		assert_eq!(pointers[1].int(2), gl::UNSIGNED_BYTE as i64);
+		assert_eq!(pointers[1].int(3), 1);
+		assert_eq!(pointers[1].int(4), 12);
+		assert_eq!(pointers[1].ptr(5), 8);
+
+		let draws = headless::calls_named("DrawElements");
+		assert_eq!(draws.len(), 1);
+		assert_eq!(draws[0].int(1), num_indices as i64);
+
+		// Indices must all reference uploaded vertices
+		headless::with_state(|s| {
+			let vbo = &s.buffers[&paper.mesh.vbo];
+			let ebo = &s.buffers[&paper.mesh.ebo];
+			let num_verts = (vbo.data.len() / ::std::mem::size_of::<PaperVertex>()) as u16;
+
+			assert_eq!(ebo.data.len(), num_indices * 2);
+			assert!(ebo.data.chunks(2).all(|c| (c[0] as u16 | (c[1] as u16) << 8) < num_verts));
+		});
+	}
+
+	#[test]
+	fn draw_large() {
+		headless::reset();
+
+		let mut paper = Paper::new();
+		for _ in 0..2000 {
+			paper.build_circle(Vec2::zero(), 1.0, Color::white());
+		}
+
+		assert!(paper.builder.get_vertex_count() > 65536);
+		paper.draw();
+
+		let draws = headless::calls_named("DrawElements");
+		assert_eq!(draws.len(), 2);
+		assert_eq!(draws.iter().map(|d| d.int(1) as usize).sum::<usize>(), paper.builder.get_index_count());
+	}
+}
@@ -0,0 +1,315 @@
+use bindings::gl;
+use common::vector::Vec2i;
+use webgl::StencilParams;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BlendMode {
+	/// Blending disabled
+	Opaque,
+	/// For colors that aren't premultiplied
+	Alpha,
+	/// Adds the source, scaled by its alpha
+	Additive,
+	/// For colors that are already multiplied by their alpha
+	Premultiplied,
+	/// Multiplies the destination by the source
+	Multiply,
+	Custom { equation: u32, src: u32, dst: u32 },
+}
+
+impl BlendMode {
+	// As (equation, source factor, destination factor). None if blending is disabled
+	fn function(&self) -> Option<(u32, u32, u32)> {
+		match *self {
+			BlendMode::Opaque => None,
+			BlendMode::Alpha => Some((gl::FUNC_ADD, gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA)),
+			BlendMode::Additive => Some((gl::FUNC_ADD, gl::SRC_ALPHA, gl::ONE)),
+			BlendMode::Premultiplied => Some((gl::FUNC_ADD, gl::ONE, gl::ONE_MINUS_SRC_ALPHA)),
+			BlendMode::Multiply => Some((gl::FUNC_ADD, gl::DST_COLOR, gl::ZERO)),
+			BlendMode::Custom { equation, src, dst } => Some((equation, src, dst)),
+		}
+	}
+}
+
+/// Everything that affects how draws are written to the framebuffer, applied as a whole with
+/// WebGLContext::set_render_state. A test that's None is disabled
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RenderState {
+	pub blend: BlendMode,
+
+	pub depth_func: Option<u32>,
+	pub depth_write: bool,
+
+	/// The faces to cull - gl::BACK, gl::FRONT or gl::FRONT_AND_BACK
+	pub cull_face: Option<u32>,
+
+	/// Position and size in framebuffer pixels, from the bottom left
+	pub scissor: Option<(Vec2i, Vec2i)>,
+
+	pub color_mask: [bool; 4],
+	pub stencil_test: bool,
+	/// Front and back face params, which only matter with `stencil_test`. Without any, every bit
+	/// is writable so clears cover the whole stencil buffer
+	pub stencil: Option<(StencilParams, StencilParams)>,
+}
+
+impl RenderState {
+	/// GL's initial state: every test disabled, and every channel written
+	pub fn new() -> Self {
+		RenderState {
+			blend: BlendMode::Opaque,
+
+			depth_func: None,
+			depth_write: true,
+
+			cull_face: None,
+			scissor: None,
+
+			color_mask: [true; 4],
+			stencil_test: false,
+			stencil: None,
+		}
+	}
+
+	pub fn blend(self, blend: BlendMode) -> Self { RenderState { blend, ..self } }
+
+	pub fn depth_test(self, func: u32) -> Self { RenderState { depth_func: Some(func), ..self } }
+	pub fn no_depth_test(self) -> Self { RenderState { depth_func: None, ..self } }
+	pub fn depth_write(self, depth_write: bool) -> Self { RenderState { depth_write, ..self } }
+
+	pub fn cull(self, face: u32) -> Self { RenderState { cull_face: Some(face), ..self } }
+	pub fn no_cull(self) -> Self { RenderState { cull_face: None, ..self } }
+
+	pub fn scissor<P, S>(self, pos: P, size: S) -> Self where P: Into<Vec2i>, S: Into<Vec2i> {
+		RenderState { scissor: Some((pos.into(), size.into())), ..self }
+	}
+
+	pub fn no_scissor(self) -> Self { RenderState { scissor: None, ..self } }
+
+	pub fn color_mask(self, r: bool, g: bool, b: bool, a: bool) -> Self {
+		RenderState { color_mask: [r, g, b, a], ..self }
+	}
+
+	/// Enables the stencil test with `params`
+	pub fn stencil(self, params: StencilParams) -> Self { self.stencil_separate(params, params) }
+
+	pub fn stencil_separate(self, front: StencilParams, back: StencilParams) -> Self {
+		RenderState { stencil_test: true, ..self.stencil_params_separate(front, back) }
+	}
+
+	/// Sets the stencil params without enabling or disabling the test
+	pub fn stencil_params(self, params: StencilParams) -> Self { self.stencil_params_separate(params, params) }
+
+	pub fn stencil_params_separate(self, front: StencilParams, back: StencilParams) -> Self {
+		RenderState { stencil: Some((front, back)), ..self }
+	}
+
+	pub fn stencil_test(self, stencil_test: bool) -> Self { RenderState { stencil_test, ..self } }
+
+	pub fn no_stencil(self) -> Self { RenderState { stencil_test: false, stencil: None, ..self } }
+
+	/// Makes the GL calls needed to get from `previous` to this state, or all of them without
+	/// one. WebGLContext::set_render_state keeps track of `previous`
+	pub fn apply(&self, previous: Option<&RenderState>) {
+		let set_cap = |cap, enabled| unsafe {
+			if enabled { gl::Enable(cap) } else { gl::Disable(cap) }
+		};
+
+		unsafe {
+			if let Some(enabled) = changed(self, previous, |s| s.blend != BlendMode::Opaque) {
+				set_cap(gl::BLEND, enabled);
+			}
+
+			if let Some(Some(equation)) = changed(self, previous, |s| s.blend.function().map(|f| f.0)) {
+				gl::BlendEquation(equation);
+			}
+
+			if let Some(Some((src, dst))) = changed(self, previous, |s| s.blend.function().map(|f| (f.1, f.2))) {
+				gl::BlendFunc(src, dst);
+			}
+
+			if let Some(enabled) = changed(self, previous, |s| s.depth_func.is_some()) {
+				set_cap(gl::DEPTH_TEST, enabled);
+			}
+
+			if let Some(Some(func)) = changed(self, previous, |s| s.depth_func) {
+				gl::DepthFunc(func);
+			}
+
+			if let Some(write) = changed(self, previous, |s| s.depth_write) {
+				gl::DepthMask(write as u8);
+			}
+
+			if let Some(enabled) = changed(self, previous, |s| s.cull_face.is_some()) {
+				set_cap(gl::CULL_FACE, enabled);
+			}
+
+			if let Some(Some(face)) = changed(self, previous, |s| s.cull_face) {
+				gl::CullFace(face);
+			}
+
+			if let Some(enabled) = changed(self, previous, |s| s.scissor.is_some()) {
+				set_cap(gl::SCISSOR_TEST, enabled);
+			}
+
+			if let Some(Some((pos, size))) = changed(self, previous, |s| s.scissor) {
+				gl::Scissor(pos.x, pos.y, size.x, size.y);
+			}
+
+			if let Some([r, g, b, a]) = changed(self, previous, |s| s.color_mask) {
+				gl::ColorMask(r as u8, g as u8, b as u8, a as u8);
+			}
+
+			if let Some(enabled) = changed(self, previous, |s| s.stencil_test) {
+				set_cap(gl::STENCIL_TEST, enabled);
+			}
+
+			let stencil_func = |p: &StencilParams| (p.condition, p.reference, p.read_mask);
+			if let Some(Some(faces)) = changed(self, previous, |s| s.stencil.map(|(f, b)| (stencil_func(&f), stencil_func(&b)))) {
+				for_faces(faces, |face, (condition, reference, read_mask)| {
+					gl::StencilFuncSeparate(face, condition, reference as i32, read_mask as u32);
+				});
+			}
+
+			let stencil_op = |p: &StencilParams| (p.stencil_fail, p.depth_fail, p.pass);
+			if let Some(Some(faces)) = changed(self, previous, |s| s.stencil.map(|(f, b)| (stencil_op(&f), stencil_op(&b)))) {
+				for_faces(faces, |face, (stencil_fail, depth_fail, pass)| {
+					gl::StencilOpSeparate(face, stencil_fail, depth_fail, pass);
+				});
+			}
+
+			let write_masks = |s: &RenderState| s.stencil.map_or((0xff, 0xff), |(f, b)| (f.write_mask, b.write_mask));
+			if let Some(faces) = changed(self, previous, write_masks) {
+				for_faces(faces, |face, write_mask| gl::StencilMaskSeparate(face, write_mask as u32));
+			}
+		}
+	}
+}
+
+impl Default for RenderState {
+	fn default() -> Self { RenderState::new() }
+}
+
+// Sets (front, back) with one call if they're the same
+unsafe fn for_faces<T, F>((front, back): (T, T), set: F) where T: PartialEq, F: Fn(u32, T) {
+	if front == back {
+		set(gl::FRONT_AND_BACK, front);
+	} else {
+		set(gl::FRONT, front);
+		set(gl::BACK, back);
+	}
+}
+
+// The part of `state` picked out by `get`, if it differs from `previous`
+fn changed<T, F>(state: &RenderState, previous: Option<&RenderState>, get: F) -> Option<T>
+	where T: PartialEq, F: Fn(&RenderState) -> T {
+
+	let value = get(state);
+
+	match previous {
+		Some(previous) if get(previous) == value => None,
+		_ => Some(value),
+	}
+}
+
+#[cfg(all(test, feature = "headless"))] mod tests {
+	use bindings::headless;
+	use webgl::WebGLContext;
+	use super::*;
+
+	fn call_names() -> Vec<&'static str> {
+		headless::take_calls().iter().map(|c| c.name).collect()
+	}
+
+	#[test]
+	fn minimal_changes() {
+		headless::reset();
+
+		let ctx = WebGLContext::new(false);
+		headless::take_calls();
+
+		let state = RenderState::new()
+			.blend(BlendMode::Alpha)
+			.depth_test(gl::LEQUAL)
+			.cull(gl::BACK);
+
+		ctx.set_render_state(state);
+		assert_eq!(call_names(), ["Enable", "BlendEquation", "BlendFunc", "Enable", "DepthFunc", "Enable", "CullFace"]);
+		headless::with_state(|s| assert!(s.enabled_caps.contains(&gl::BLEND) && s.enabled_caps.contains(&gl::DEPTH_TEST)));
+
+		ctx.set_render_state(state);
+		assert!(call_names().is_empty());
+
+		// Same equation, so only the factors change
+		ctx.set_render_state(state.blend(BlendMode::Premultiplied).no_cull());
+		assert_eq!(call_names(), ["BlendFunc", "Disable"]);
+
+		ctx.set_render_state(state.scissor(Vec2i::new(1, 2), Vec2i::new(3, 4)).depth_write(false));
+		let calls = headless::take_calls();
+		let names: Vec<_> = calls.iter().map(|c| c.name).collect();
+		assert_eq!(names, ["BlendFunc", "DepthMask", "Enable", "CullFace", "Enable", "Scissor"]);
+		assert_eq!(calls[5].args, [1.into(), 2.into(), 3.into(), 4.into()]);
+
+		ctx.disable_color_write();
+		assert_eq!(call_names(), ["ColorMask"]);
+		assert_eq!(ctx.render_state().color_mask, [false; 4]);
+		assert!(ctx.render_state().scissor.is_some());
+
+		ctx.reset_render_state(RenderState::new());
+		assert_eq!(call_names().len(), 8);
+		headless::with_state(|s| assert!(s.enabled_caps.is_empty()));
+	}
+
+	#[test]
+	fn two_sided_stencil() {
+		headless::reset();
+
+		let ctx = WebGLContext::new(false);
+		ctx.set_stencil_clear_value(0);
+		headless::take_calls();
+
+		// Nonzero winding fill: front faces count up, back faces count down
+		let count = StencilParams::new(0).always().write_mask(0x7f);
+		ctx.set_render_state(RenderState::new()
+			.color_mask(false, false, false, false)
+			.stencil_separate(count.increment_wrap(), count.decrement_wrap()));
+
+		let calls = headless::take_calls();
+		let names: Vec<_> = calls.iter().map(|c| c.name).collect();
+		assert_eq!(names, ["ColorMask", "Enable", "StencilFuncSeparate", "StencilOpSeparate", "StencilOpSeparate", "StencilMaskSeparate"]);
+		assert_eq!(calls[2].args, [gl::FRONT_AND_BACK.into(), gl::ALWAYS.into(), 0i32.into(), 0xffu32.into()]);
+		assert_eq!(calls[3].args, [gl::FRONT.into(), gl::KEEP.into(), gl::KEEP.into(), gl::INCR_WRAP.into()]);
+		assert_eq!(calls[4].args, [gl::BACK.into(), gl::KEEP.into(), gl::KEEP.into(), gl::DECR_WRAP.into()]);
+		assert_eq!(calls[5].args, [gl::FRONT_AND_BACK.into(), 0x7fu32.into()]);
+
+		// Then draw wherever the count is nonzero, resetting it on the way
+		let cover = StencilParams::new(0).pass_if(gl::NOTEQUAL).read_mask(0x7f)
+			.on_pass(gl::ZERO).on_stencil_fail(gl::ZERO);
+		ctx.set_render_state(RenderState::new().stencil(cover));
+
+		let calls = headless::take_calls();
+		let names: Vec<_> = calls.iter().map(|c| c.name).collect();
+		assert_eq!(names, ["ColorMask", "StencilFuncSeparate", "StencilOpSeparate", "StencilMaskSeparate"]);
+		assert_eq!(calls[1].args, [gl::FRONT_AND_BACK.into(), gl::NOTEQUAL.into(), 0i32.into(), 0x7fu32.into()]);
+		assert_eq!(calls[2].args, [gl::FRONT_AND_BACK.into(), gl::ZERO.into(), gl::KEEP.into(), gl::ZERO.into()]);
+		assert_eq!(calls[3].args, [gl::FRONT_AND_BACK.into(), 0xffu32.into()]);
+
+		// Turning the stencil test off a masked state has to unmask it, or clears would miss bits
+		ctx.set_render_state(RenderState::new().stencil(count));
+		headless::take_calls();
+		ctx.set_render_state(RenderState::new());
+
+		let calls = headless::take_calls();
+		let names: Vec<_> = calls.iter().map(|c| c.name).collect();
+		assert_eq!(names, ["Disable", "StencilMaskSeparate"]);
+		assert_eq!(calls[1].args, [gl::FRONT_AND_BACK.into(), 0xffu32.into()]);
+
+		// Params alone leave the test as it is
+		ctx.set_stencil(cover);
+		assert!(!headless::take_calls().iter().any(|c| c.name == "Enable"));
+		assert!(!ctx.render_state().stencil_test);
+
+		ctx.set_render_state(ctx.render_state().stencil_test(true));
+		assert_eq!(headless::calls_named("Enable")[0].int(0), gl::STENCIL_TEST as i64);
+	}
+}
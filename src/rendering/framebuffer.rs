@@ -2,8 +2,10 @@
 
 use math::*;
 use rendering::gl;
+use error::Error;
 
 use rendering::texture::*;
+use rendering::resources::Restore;
 
 use webgl::WebGLContext;
 
@@ -44,23 +46,61 @@ impl Framebuffer {
 	pub fn resize(&mut self, nsize: Vec2i) {
 		if self.size == nsize { return }
 
-		unsafe {
-			for tex in self.targets.iter_mut() {
-				let _guard = tex.bind_guard();
+		for tex in self.targets.iter_mut() {
+			tex.allocate(nsize);
+		}
+
+		if let Some(ref mut tex) = self.depth_target {
+			tex.allocate(nsize);
+		}
 
-				gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, nsize.x, nsize.y, 0, 
-					gl::RGBA, gl::UNSIGNED_BYTE, 0 as *const _);
+		self.size = nsize;
+	}
+
+	fn attach_targets(&self) {
+		unsafe {
+			for (i, tex) in self.targets.iter().enumerate() {
+				gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0 + i as u32, gl::TEXTURE_2D, tex.gl_handle, 0);
 			}
 
 			if let Some(ref tex) = self.depth_target {
-				let _guard = tex.bind_guard();
+				gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_2D, tex.gl_handle, 0);
+			}
+		}
+	}
+}
+
+// The targets delete themselves
+impl Drop for Framebuffer {
+	fn drop(&mut self) {
+		unsafe { gl::DeleteFramebuffers(1, &self.gl_handle); }
+	}
+}
+
+impl Restore for Framebuffer {
+	fn restore(&mut self) -> Result<(), Error> {
+		for tex in self.targets.iter_mut() {
+			tex.restore()?;
+		}
+
+		if let Some(ref mut tex) = self.depth_target {
+			tex.restore()?;
+		}
+
+		unsafe {
+			gl::GenFramebuffers(1, &mut self.gl_handle);
+			self.bind();
+			self.attach_targets();
 
-				gl::TexImage2D(gl::TEXTURE_2D, 0, gl::DEPTH_COMPONENT as i32, nsize.x, nsize.y, 0, 
-					gl::DEPTH_COMPONENT, gl::UNSIGNED_INT, 0 as *const _);
+			let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+			Framebuffer::unbind();
+
+			if status != gl::FRAMEBUFFER_COMPLETE {
+				return Err(Error::IncompleteFramebuffer(status));
 			}
 		}
 
-		self.size = nsize;
+		Ok(())
 	}
 }
 
@@ -98,60 +138,109 @@ impl FramebufferBuilder {
 		FramebufferBuilder { fb }
 	}
 
+	/// Doesn't check the framebuffer is complete - try_finalize does
 	pub fn finalize(self) -> Framebuffer {
 		Framebuffer::unbind();
 
 		self.fb
 	}
 
-	pub fn add_depth(mut self) -> Self {
-		let mut gl_handle = 0;
+	pub fn try_finalize(self) -> Result<Framebuffer, Error> {
+		let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+		Framebuffer::unbind();
 
-		assert!(self.fb.depth_target.is_none(), "Framebuffer can only have one depth target");
+		if status != gl::FRAMEBUFFER_COMPLETE {
+			return Err(Error::IncompleteFramebuffer(status));
+		}
 
-		unsafe {
-			gl::GenTextures(1, &mut gl_handle);
-			let _guard = TextureBindGuard::new_raw(gl_handle);
+		Ok(self.fb)
+	}
 
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+	pub fn add_depth(mut self) -> Self {
+		assert!(self.fb.depth_target.is_none(), "Framebuffer can only have one depth target");
 
-			gl::TexImage2D(gl::TEXTURE_2D, 0, gl::DEPTH_COMPONENT as i32, self.fb.size.x, self.fb.size.y, 0, 
-				gl::DEPTH_COMPONENT, gl::UNSIGNED_INT, 0 as *const _);
+		let tex = Texture::new_render_target(self.fb.size, gl::DEPTH_COMPONENT, gl::UNSIGNED_INT);
 
+		unsafe {
 			gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, 
-				gl::TEXTURE_2D, gl_handle, 0);
+				gl::TEXTURE_2D, tex.gl_handle, 0);
 		}
 
-		self.fb.depth_target = Some(Texture{gl_handle, size: self.fb.size});
+		self.fb.depth_target = Some(tex);
 
 		self
 	}
 
 	pub fn add_target(mut self) -> Self {
-		let mut gl_handle = 0;
-
 		let next_target = self.fb.targets.len() as u32;
+		let tex = Texture::new_render_target(self.fb.size, gl::RGBA, gl::UNSIGNED_BYTE);
 
 		unsafe {
-			gl::GenTextures(1, &mut gl_handle);
-			let _guard = TextureBindGuard::new_raw(gl_handle);
-
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
-
-			gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, self.fb.size.x, self.fb.size.y, 0, 
-				gl::RGBA, gl::UNSIGNED_BYTE, 0 as *const _);
-
-			gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0 + next_target, gl::TEXTURE_2D, gl_handle, 0);
+			gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0 + next_target, gl::TEXTURE_2D, tex.gl_handle, 0);
 		}
 
-		self.fb.targets.push(Texture{gl_handle, size: self.fb.size});
+		self.fb.targets.push(tex);
 
 		self
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, feature = "headless"))] mod tests {
+	use bindings::headless;
+	use rendering::gl;
+	use rendering::framebuffer::*;
+
+	#[test]
+	fn framebuffer_builder() {
+		headless::reset();
+
+		let mut fb = FramebufferBuilder::new(Vec2i::new(64, 32))
+			.add_target()
+			.add_target()
+			.add_depth()
+			.finalize();
+
+		let color0 = fb.get_target(0).unwrap().gl_handle;
+		let color1 = fb.get_target(1).unwrap().gl_handle;
+		let depth = fb.get_depth().unwrap().gl_handle;
+		assert!(fb.get_target(2).is_none());
+
+		headless::with_state(|s| {
+			let fbo = &s.framebuffers[&fb.gl_handle];
+			assert_eq!(fbo.attachments[&gl::COLOR_ATTACHMENT0], color0);
+			assert_eq!(fbo.attachments[&(gl::COLOR_ATTACHMENT0 + 1)], color1);
+			assert_eq!(fbo.attachments[&gl::DEPTH_ATTACHMENT], depth);
+
+			assert_eq!(s.textures[&color0].size, (64, 32));
+			assert_eq!(s.textures[&depth].internal_format, gl::DEPTH_COMPONENT as i32);
+
+			assert_eq!(s.framebuffer_binding, 0);
+			assert_eq!(s.bound_texture(), 0);
+		});
+
+		fb.resize(Vec2i::new(128, 128));
+
+		headless::with_state(|s| {
+			assert_eq!(s.textures[&color1].size, (128, 128));
+			assert_eq!(s.textures[&depth].size, (128, 128));
+		});
+
+		drop(fb);
+
+		headless::with_state(|s| {
+			assert!(s.framebuffers.is_empty());
+			assert!(s.textures.is_empty());
+		});
+
+		// Only try_finalize checks for completeness
+		headless::take_calls();
+		let fb = FramebufferBuilder::new(Vec2i::new(64, 32)).finalize();
+		assert!(headless::calls_named("CheckFramebufferStatus").is_empty());
+		drop(fb);
+
+		match FramebufferBuilder::new(Vec2i::new(64, 32)).try_finalize() {
+			Err(Error::IncompleteFramebuffer(gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT)) => {}
+			_ => panic!("Expected an incomplete framebuffer"),
+		}
+	}
+}
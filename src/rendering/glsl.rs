@@ -0,0 +1,666 @@
+// A minimal GLSL tokenizer, and the statement-level parsing ShaderBuilder needs to pull
+// declarations out of main(). This isn't a validating parser - anything it doesn't recognise
+// is left where it is for the real compiler to complain about.
+
+use std::fmt;
+use error::ShaderStage;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TokenKind {
+	Ident,
+	Number,
+	Punct,
+	/// A whole preprocessor line, including any continuations
+	Preprocessor,
+	Comment,
+	Whitespace,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Token<'a> {
+	pub kind: TokenKind,
+	pub text: &'a str,
+	/// Byte offset into the source
+	pub start: usize,
+	/// Zero based line the token starts on
+	pub line: usize,
+}
+
+impl<'a> Token<'a> {
+	pub fn end(&self) -> usize { self.start + self.text.len() }
+
+	pub fn is_significant(&self) -> bool {
+		self.kind != TokenKind::Whitespace && self.kind != TokenKind::Comment
+	}
+
+	fn is_punct(&self, c: &str) -> bool {
+		self.kind == TokenKind::Punct && self.text == c
+	}
+
+	fn is_ident(&self, s: &str) -> bool {
+		self.kind == TokenKind::Ident && self.text == s
+	}
+}
+
+pub fn tokenize<'a>(src: &'a str) -> Vec<Token<'a>> {
+	let bytes = src.as_bytes();
+	let mut tokens = Vec::new();
+	let mut pos = 0;
+	let mut line = 0;
+
+	// Preprocessor directives are only recognised as the first thing on a line
+	let mut line_start = true;
+
+	while pos < bytes.len() {
+		let start = pos;
+		let c = bytes[pos];
+
+		let kind = match c {
+			b'/' if bytes.get(pos+1) == Some(&b'/') => {
+				while pos < bytes.len() && bytes[pos] != b'\n' { pos += 1 }
+				TokenKind::Comment
+			}
+
+			b'/' if bytes.get(pos+1) == Some(&b'*') => {
+				pos += 2;
+				while pos < bytes.len() && !(bytes[pos] == b'*' && bytes.get(pos+1) == Some(&b'/')) { pos += 1 }
+				pos = (pos + 2).min(bytes.len());
+				TokenKind::Comment
+			}
+
+			b'#' if line_start => {
+				while pos < bytes.len() && bytes[pos] != b'\n' {
+					if bytes[pos] == b'\\' && bytes.get(pos+1) == Some(&b'\n') { pos += 1 }
+					pos += 1;
+				}
+
+				TokenKind::Preprocessor
+			}
+
+			c if (c as char).is_whitespace() => {
+				while pos < bytes.len() && (bytes[pos] as char).is_whitespace() { pos += 1 }
+				TokenKind::Whitespace
+			}
+
+			c if c == b'_' || (c as char).is_ascii_alphabetic() => {
+				while pos < bytes.len() && (bytes[pos] == b'_' || (bytes[pos] as char).is_ascii_alphanumeric()) { pos += 1 }
+				TokenKind::Ident
+			}
+
+			c if (c as char).is_ascii_digit()
+				|| (c == b'.' && bytes.get(pos+1).map_or(false, |&n| (n as char).is_ascii_digit())) => {
+
+				while pos < bytes.len() {
+					let n = bytes[pos];
+					let exponent_sign = (n == b'-' || n == b'+') && (bytes[pos-1] == b'e' || bytes[pos-1] == b'E');
+					if !(n == b'.' || (n as char).is_ascii_alphanumeric() || exponent_sign) { break }
+					pos += 1;
+				}
+
+				TokenKind::Number
+			}
+
+			_ => {
+				// Skip over the whole of a multibyte character
+				pos += 1;
+				while pos < bytes.len() && !src.is_char_boundary(pos) { pos += 1 }
+				TokenKind::Punct
+			}
+		};
+
+		let text = &src[start..pos];
+
+		tokens.push(Token { kind, text, start, line });
+
+		let newlines = text.matches('\n').count();
+		line += newlines;
+
+		line_start = match kind {
+			TokenKind::Whitespace => line_start || newlines > 0,
+			TokenKind::Comment => line_start && newlines == 0 || text.ends_with('\n'),
+			_ => false,
+		};
+	}
+
+	tokens
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DeclKind {
+	Function,
+	Struct,
+	Const,
+	Preprocessor,
+	/// `#extension`, which has to come before anything but other directives
+	Extension,
+}
+
+/// A declaration that needs to live at global scope
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Declaration {
+	pub kind: DeclKind,
+	/// Byte range of the whole declaration in the source
+	pub start: usize,
+	pub end: usize,
+	/// Where the text to emit starts - after the `func` keyword, if there is one
+	pub text_start: usize,
+}
+
+// Keywords that can start a statement shaped like `a b(...) {`, which mustn't be taken
+// for function definitions
+const STATEMENT_KEYWORDS: &'static [&'static str] = &[
+	"if", "else", "for", "while", "do", "return", "switch", "case",
+];
+
+const PRECISION_QUALIFIERS: &'static [&'static str] = &["lowp", "mediump", "highp"];
+
+/// Finds the functions, structs, constants and preprocessor directives at the top level of a
+/// body of statements. Functions can be marked with `func`, but are also recognised without it.
+/// Conditional directives aren't included, since they have to stay with the code they guard
+pub fn find_declarations(src: &str) -> Vec<Declaration> {
+	let tokens = tokenize(src);
+	let toks: Vec<&Token> = tokens.iter().filter(|t| t.is_significant()).collect();
+
+	let mut decls = Vec::new();
+	let mut depth = 0i32;
+	let mut statement_start = true;
+	let mut i = 0;
+
+	while i < toks.len() {
+		let t = toks[i];
+
+		if depth == 0 && statement_start {
+			if let Some(decl) = match_declaration(&toks, i) {
+				i = toks.iter().position(|t| t.start >= decl.end).unwrap_or(toks.len());
+				decls.push(decl);
+				continue
+			}
+		}
+
+		match t.text {
+			"{" if t.kind == TokenKind::Punct => depth += 1,
+			"}" if t.kind == TokenKind::Punct => depth -= 1,
+			_ => {}
+		}
+
+		statement_start = t.kind == TokenKind::Preprocessor
+			|| t.is_punct(";") || t.is_punct("{") || t.is_punct("}");
+
+		i += 1;
+	}
+
+	decls
+}
+
+fn match_declaration(toks: &[&Token], i: usize) -> Option<Declaration> {
+	let t = toks[i];
+
+	if t.kind == TokenKind::Preprocessor {
+		let kind = directive_kind(t.text)?;
+		return Some(Declaration { kind, start: t.start, end: t.end(), text_start: t.start })
+	}
+
+	if t.is_ident("func") {
+		let body_end = function_end(toks, i+1)?;
+		return Some(Declaration { kind: DeclKind::Function, start: t.start, end: body_end, text_start: toks[i+1].start })
+	}
+
+	if t.is_ident("struct") {
+		let open = (i+1..toks.len()).find(|&j| toks[j].is_punct("{"))?;
+		let close = matching(toks, open)?;
+
+		// Also take any instance declarations up to the closing semicolon
+		let semi = (close+1..toks.len()).find(|&j| toks[j].is_punct(";"))?;
+		return Some(Declaration { kind: DeclKind::Struct, start: t.start, end: toks[semi].end(), text_start: t.start })
+	}
+
+	if t.is_ident("const") {
+		let semi = statement_end(toks, i)?;
+		return Some(Declaration { kind: DeclKind::Const, start: t.start, end: toks[semi].end(), text_start: t.start })
+	}
+
+	if t.kind == TokenKind::Ident && !STATEMENT_KEYWORDS.contains(&t.text) {
+		let body_end = function_end(toks, i)?;
+		return Some(Declaration { kind: DeclKind::Function, start: t.start, end: body_end, text_start: t.start })
+	}
+
+	None
+}
+
+// Directives that mean the same wherever they appear, so can be moved out of main
+fn directive_kind(directive: &str) -> Option<DeclKind> {
+	let name = directive[1..].trim_start()
+		.split(|c: char| !c.is_alphanumeric() && c != '_')
+		.next();
+
+	match name {
+		Some("extension") => Some(DeclKind::Extension),
+		Some("define") | Some("undef") | Some("include") => Some(DeclKind::Preprocessor),
+		_ => None,
+	}
+}
+
+// Matches `[precision] type name(...) { ... }` starting at i, returning the byte offset just
+// past the closing brace
+fn function_end(toks: &[&Token], mut i: usize) -> Option<usize> {
+	if toks.get(i).map_or(false, |t| PRECISION_QUALIFIERS.contains(&t.text)) { i += 1 }
+
+	let is_ident = |j: usize| toks.get(j).map_or(false, |t| t.kind == TokenKind::Ident);
+	if !is_ident(i) || !is_ident(i+1) || !toks.get(i+2).map_or(false, |t| t.is_punct("(")) {
+		return None
+	}
+
+	let close_paren = matching(toks, i+2)?;
+	if !toks.get(close_paren+1).map_or(false, |t| t.is_punct("{")) {
+		return None
+	}
+
+	matching(toks, close_paren+1).map(|close| toks[close].end())
+}
+
+// Index of the ';' ending the statement starting at i, skipping over anything bracketed
+fn statement_end(toks: &[&Token], i: usize) -> Option<usize> {
+	let mut depth = 0;
+
+	for j in i..toks.len() {
+		match toks[j].text {
+			"(" | "{" | "[" if toks[j].kind == TokenKind::Punct => depth += 1,
+			")" | "}" | "]" if toks[j].kind == TokenKind::Punct => depth -= 1,
+			";" if depth == 0 => return Some(j),
+			_ => {}
+		}
+	}
+
+	None
+}
+
+// Index of the bracket closing the one at `open`
+fn matching(toks: &[&Token], open: usize) -> Option<usize> {
+	let (open_text, close_text) = match toks[open].text {
+		"(" => ("(", ")"),
+		"{" => ("{", "}"),
+		"[" => ("[", "]"),
+		_ => return None,
+	};
+
+	let mut depth = 0;
+	for j in open..toks.len() {
+		if toks[j].is_punct(open_text) { depth += 1 }
+		if toks[j].is_punct(close_text) {
+			depth -= 1;
+			if depth == 0 { return Some(j) }
+		}
+	}
+
+	None
+}
+
+
+
+/// Where a line of generated source came from
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineOrigin {
+	/// e.g. `fragment() #2`
+	pub source: String,
+	/// One based line within that source
+	pub line: usize,
+}
+
+/// Maps each line of generated source back to where it was written. Lines the builder
+/// generated itself have no origin
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceMap {
+	pub lines: Vec<Option<LineOrigin>>,
+}
+
+impl SourceMap {
+	/// Takes a one based line number, like the ones in compile logs
+	pub fn lookup(&self, line: usize) -> Option<&LineOrigin> {
+		if line == 0 { return None }
+		self.lines.get(line - 1).and_then(|o| o.as_ref())
+	}
+}
+
+/// Source that is built up line by line, tracking where each line came from
+#[derive(Clone, Debug, Default)]
+pub struct MappedSource {
+	pub source: String,
+	pub map: SourceMap,
+}
+
+impl MappedSource {
+	pub fn new() -> Self { MappedSource::default() }
+
+	/// Wraps source that has no known origin
+	pub fn unmapped(source: &str) -> Self {
+		MappedSource { source: source.to_string(), map: SourceMap::default() }
+	}
+
+	/// Appends generated text, which may span several lines
+	pub fn push_generated(&mut self, text: &str) {
+		for line in text.lines() {
+			self.push_line(line, None);
+		}
+	}
+
+	pub fn push_line(&mut self, line: &str, origin: Option<LineOrigin>) {
+		self.source.push_str(line);
+		self.source.push('\n');
+		self.map.lines.push(origin);
+	}
+
+	/// Appends `text`, whose first line is line `first_line` (zero based) of a source with
+	/// the given per-line origins
+	pub fn push_mapped(&mut self, text: &str, first_line: usize, origins: &[Option<LineOrigin>]) {
+		for (i, line) in text.lines().enumerate() {
+			let origin = origins.get(first_line + i).cloned().and_then(|o| o);
+			self.push_line(line, origin);
+		}
+	}
+}
+
+/// Appends the origin of each `ERROR: 0:<line>:` style line in a compile log
+pub fn annotate_log(log: &str, map: &SourceMap) -> String {
+	let mut out = String::with_capacity(log.len());
+
+	for line in log.lines() {
+		out.push_str(line);
+
+		if let Some(origin) = log_line_number(line).and_then(|l| map.lookup(l)) {
+			out.push_str(&format!(" [{}, line {}]", origin.source, origin.line));
+		}
+
+		out.push('\n');
+	}
+
+	out
+}
+
+/// Pulls the line number out of a driver log line like `ERROR: 0:12: ...`
+pub fn log_line_number(line: &str) -> Option<usize> {
+	let mut parts = line.splitn(4, ':');
+	let _severity = parts.next()?;
+	let _file = parts.next()?;
+	parts.next()?.trim().parse().ok()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Severity {
+	Error,
+	Warning,
+	/// Log lines that aren't in the `SEVERITY: file:line: message` form
+	Info,
+}
+
+/// One message from a compile log
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+	pub stage: ShaderStage,
+	pub severity: Severity,
+	/// One based line in the compiled source, if the driver gave one
+	pub line: Option<usize>,
+	pub origin: Option<LineOrigin>,
+	pub message: String,
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Severity::Error => write!(f, "error"),
+			Severity::Warning => write!(f, "warning"),
+			Severity::Info => write!(f, "info"),
+		}
+	}
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} shader ", self.stage)?;
+
+		if let Some(line) = self.line {
+			write!(f, "line {} ", line)?;
+		}
+
+		write!(f, "{}: {}", self.severity, self.message)?;
+
+		if let Some(ref origin) = self.origin {
+			write!(f, " [{}, line {}]", origin.source, origin.line)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Splits a compile log into diagnostics, using `map` to find where each line came from
+pub fn parse_log(log: &str, stage: ShaderStage, map: &SourceMap) -> Vec<Diagnostic> {
+	log.lines()
+		.map(str::trim)
+		.filter(|l| !l.is_empty())
+		.map(|l| {
+			let (severity, rest) = match l.find(':') {
+				Some(split) => match &l[..split] {
+					"ERROR" => (Severity::Error, l[split+1..].trim()),
+					"WARNING" => (Severity::Warning, l[split+1..].trim()),
+					_ => (Severity::Info, l),
+				},
+				None => (Severity::Info, l),
+			};
+
+			let line = if severity == Severity::Info { None } else { log_line_number(l) };
+
+			// Drop the `0:12:` location, now that it's been parsed
+			let message = match line {
+				Some(_) => rest.splitn(3, ':').nth(2).unwrap_or("").trim(),
+				None => rest,
+			};
+
+			Diagnostic {
+				stage,
+				severity,
+				line,
+				origin: line.and_then(|l| map.lookup(l)).cloned(),
+				message: message.to_string(),
+			}
+		})
+		.collect()
+}
+
+// Lines shown either side of a marked line in a listing
+const LISTING_CONTEXT: usize = 2;
+
+/// Numbers the lines of `source`, marking the lines `diagnostics` refer to with `>>` and their
+/// messages. Only marked lines and a little context around them are shown, unless there's
+/// nothing to mark
+pub fn source_listing(source: &str, diagnostics: &[Diagnostic]) -> String {
+	let lines: Vec<&str> = source.lines().collect();
+	let marked: Vec<usize> = diagnostics.iter().filter_map(|d| d.line).collect();
+	let width = lines.len().to_string().len();
+
+	let visible = |line: usize| marked.is_empty()
+		|| marked.iter().any(|&m| line + LISTING_CONTEXT >= m && line <= m + LISTING_CONTEXT);
+
+	let mut out = String::new();
+	let mut skipped = false;
+
+	for (i, text) in lines.iter().enumerate() {
+		let line = i + 1;
+
+		if !visible(line) {
+			if !skipped { out.push_str("   ...\n") }
+			skipped = true;
+			continue
+		}
+
+		skipped = false;
+
+		let marker = if marked.contains(&line) { ">>" } else { "  " };
+		out.push_str(&format!("{} {:>w$} | {}\n", marker, line, text, w = width));
+
+		for d in diagnostics.iter().filter(|d| d.line == Some(line)) {
+			out.push_str(&format!("   {:w$} | ^ {}: {}", "", d.severity, d.message, w = width));
+
+			if let Some(ref origin) = d.origin {
+				out.push_str(&format!(" [{}, line {}]", origin.source, origin.line));
+			}
+
+			out.push('\n');
+		}
+	}
+
+	out
+}
+
+
+
+#[cfg(test)] mod tests {
+	use super::*;
+
+	fn significant(src: &str) -> Vec<(TokenKind, &str)> {
+		tokenize(src).into_iter()
+			.filter(|t| t.is_significant())
+			.map(|t| (t.kind, t.text))
+			.collect()
+	}
+
+	fn hoisted(src: &str) -> Vec<(DeclKind, &str)> {
+		find_declarations(src).into_iter()
+			.map(|d| (d.kind, &src[d.text_start..d.end]))
+			.collect()
+	}
+
+	#[test]
+	fn tokens() {
+		use self::TokenKind::*;
+
+		assert_eq!(significant("vec3 a = vec3(1.0, 2e-3, .5);"), vec![
+			(Ident, "vec3"), (Ident, "a"), (Punct, "="), (Ident, "vec3"), (Punct, "("),
+			(Number, "1.0"), (Punct, ","), (Number, "2e-3"), (Punct, ","), (Number, ".5"),
+			(Punct, ")"), (Punct, ";"),
+		]);
+
+		let toks = tokenize("a // {\n/* } \n */ b\n  #define X {\nc");
+		let kinds: Vec<_> = toks.iter().map(|t| t.kind).collect();
+		assert_eq!(kinds, vec![Ident, Whitespace, Comment, Whitespace, Comment, Whitespace, Ident, Whitespace, Preprocessor, Whitespace, Ident]);
+
+		let lines: Vec<_> = toks.iter().filter(|t| t.is_significant()).map(|t| t.line).collect();
+		assert_eq!(lines, vec![0, 2, 3, 4]);
+
+		// '#' anywhere but the start of a line isn't a directive
+		assert_eq!(significant("a #b")[1], (Punct, "#"));
+	}
+
+	#[test]
+	fn functions() {
+		let src = "
+			func vec3 invert(vec3 c) {
+				// a stray } in a comment
+				if (c.r > 0.5) { return vec3(0.0); }
+				return vec3(1.0) - c;
+			}
+
+			highp float square(float x) { return x * x; }
+
+			vec3 color = invert(vec3(square(0.5)));
+			if (color.r < 0.5) { color = vec3(1.0); } else if (color.g > 0.2) { color.b = 0.0; }
+			my_func (color);
+		";
+
+		let decls = hoisted(src);
+		assert_eq!(decls.len(), 2);
+		assert_eq!(decls[0].0, DeclKind::Function);
+		assert!(decls[0].1.starts_with("vec3 invert(vec3 c) {"));
+		assert!(decls[0].1.ends_with("return vec3(1.0) - c;\n\t\t\t}"));
+		assert_eq!(decls[1], (DeclKind::Function, "highp float square(float x) { return x * x; }"));
+	}
+
+	#[test]
+	fn structs_consts_and_directives() {
+		let src = "
+			#define SCALE 2.0
+			#extension GL_OES_standard_derivatives : enable
+			struct Light { vec3 dir; float power; };
+			struct Pair { float a, b; } pair;
+			const float PI = 3.14159;
+			const vec2 OFFSETS[2] = vec2[2](vec2(0.0), vec2(1.0));
+			float x = PI;
+			{ const float INNER = 1.0; }
+			#ifdef SCALE
+			x *= SCALE;
+			#endif
+		";
+
+		assert_eq!(hoisted(src), vec![
+			(DeclKind::Preprocessor, "#define SCALE 2.0"),
+			(DeclKind::Extension, "#extension GL_OES_standard_derivatives : enable"),
+			(DeclKind::Struct, "struct Light { vec3 dir; float power; };"),
+			(DeclKind::Struct, "struct Pair { float a, b; } pair;"),
+			(DeclKind::Const, "const float PI = 3.14159;"),
+			(DeclKind::Const, "const vec2 OFFSETS[2] = vec2[2](vec2(0.0), vec2(1.0));"),
+		]);
+	}
+
+	#[test]
+	fn unterminated_declarations_stay_put() {
+		assert!(hoisted("func vec3 broken(vec3 c) { return c;").is_empty());
+		assert!(hoisted("struct S { float a; }").is_empty());
+	}
+
+	#[test]
+	fn log_annotation() {
+		let mut src = MappedSource::new();
+		src.push_generated("precision mediump float;");
+		src.push_mapped("a\nb", 0, &[Some(LineOrigin { source: "fragment() #1".into(), line: 1 }),
+			Some(LineOrigin { source: "fragment() #1".into(), line: 2 })]);
+
+		assert_eq!(log_line_number("ERROR: 0:3: 'b' : undeclared identifier"), Some(3));
+		assert_eq!(log_line_number("Some other message"), None);
+
+		let log = annotate_log("ERROR: 0:3: 'b' : undeclared identifier\nERROR: 0:1: 'x' : oops", &src.map);
+		assert_eq!(log, "ERROR: 0:3: 'b' : undeclared identifier [fragment() #1, line 2]\nERROR: 0:1: 'x' : oops\n");
+	}
+
+	#[test]
+	fn log_diagnostics() {
+		let mut map = SourceMap::default();
+		map.lines = vec![None, Some(LineOrigin { source: "fragment() #1".into(), line: 1 })];
+
+		let log = "WARNING: 0:1: 'extension' : not supported\nERROR: 0:2: 'b' : undeclared identifier\n\nERROR: 1 compilation errors.  No code generated.\n";
+		let diagnostics = parse_log(log, ShaderStage::Fragment, &map);
+
+		assert_eq!(diagnostics.len(), 3);
+		assert_eq!((diagnostics[0].severity, diagnostics[0].line), (Severity::Warning, Some(1)));
+		assert_eq!(diagnostics[0].origin, None);
+
+		assert_eq!(diagnostics[1], Diagnostic {
+			stage: ShaderStage::Fragment,
+			severity: Severity::Error,
+			line: Some(2),
+			origin: map.lookup(2).cloned(),
+			message: "'b' : undeclared identifier".into(),
+		});
+
+		assert_eq!(diagnostics[2].line, None);
+		assert_eq!(diagnostics[2].message, "1 compilation errors.  No code generated.");
+
+		assert_eq!(parse_log("Linker says no", ShaderStage::Vertex, &map)[0].severity, Severity::Info);
+	}
+
+	#[test]
+	fn listing() {
+		let source: String = (1..=12).map(|i| format!("line{}\n", i)).collect();
+
+		let diagnostic = Diagnostic {
+			stage: ShaderStage::Vertex,
+			severity: Severity::Error,
+			line: Some(6),
+			origin: Some(LineOrigin { source: "vertex() #3".into(), line: 2 }),
+			message: "oops".into(),
+		};
+
+		let listing = source_listing(&source, &[diagnostic]);
+		assert_eq!(listing, "   ...\n    4 | line4\n    5 | line5\n>>  6 | line6\n      | ^ error: oops [vertex() #3, line 2]\n    7 | line7\n    8 | line8\n   ...\n");
+
+		// With nothing to point at, everything is listed
+		assert_eq!(source_listing(&source, &[]).lines().count(), 12);
+	}
+}
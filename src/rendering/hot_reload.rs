@@ -0,0 +1,225 @@
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::rc::Rc;
+
+use bindings::emscripten::*;
+use events::{Event, KeyCode};
+use rendering::shader::{Shader, ShaderHandle};
+use error::Error;
+
+struct FetchState {
+	// Vertex and fragment source, in that order
+	sources: [Option<String>; 2],
+	outstanding: u32,
+	error: Option<Error>,
+}
+
+struct FetchRequest {
+	state: Rc<RefCell<FetchState>>,
+	stage: usize,
+	url: String,
+}
+
+/// A shader whose sources are fetched by url, so they can be edited and relinked without
+/// rebuilding. Meant for development - a program that fails to build is reported and the
+/// previous one is kept.
+///
+/// Reloading deletes the old program, so fetch the shader with `shader()` each frame rather
+/// than holding onto the handle.
+pub struct ReloadableShader {
+	shader: Shader,
+
+	vertex_url: String,
+	fragment_url: String,
+	compile: Box<dyn Fn(&str, &str) -> Result<Shader, Error>>,
+
+	reload_key: Option<KeyCode>,
+	fetch: Rc<RefCell<FetchState>>,
+}
+
+impl ReloadableShader {
+	pub fn new(vertex_url: &str, fragment_url: &str) -> Self {
+		ReloadableShader::with_compiler(vertex_url, fragment_url, Shader::new)
+	}
+
+	/// Builds the program from the fetched sources with `compile` instead of Shader::new,
+	/// e.g. to feed them through a ShaderBuilder
+	pub fn with_compiler<F>(vertex_url: &str, fragment_url: &str, compile: F) -> Self
+		where F: Fn(&str, &str) -> Result<Shader, Error> + 'static {
+
+		let mut reloadable = ReloadableShader {
+			shader: Shader::invalid(),
+
+			vertex_url: vertex_url.to_string(),
+			fragment_url: fragment_url.to_string(),
+			compile: Box::new(compile),
+
+			reload_key: None,
+			fetch: Rc::new(RefCell::new(FetchState {
+				sources: [None, None],
+				outstanding: 0,
+				error: None,
+			})),
+		};
+
+		reloadable.reload();
+		reloadable
+	}
+
+	pub fn reload_on(mut self, key: KeyCode) -> Self {
+		self.reload_key = Some(key); self
+	}
+
+	pub fn shader(&self) -> ShaderHandle { self.shader.handle() }
+	pub fn is_loaded(&self) -> bool { self.shader.gl_handle != 0 }
+
+	/// Starts fetching both sources again. Does nothing if a fetch is already in flight
+	pub fn reload(&mut self) {
+		{
+			let mut fetch = self.fetch.borrow_mut();
+			if fetch.outstanding > 0 { return }
+
+			fetch.sources = [None, None];
+			fetch.error = None;
+			fetch.outstanding = 2;
+		}
+
+		for (stage, url) in [&self.vertex_url, &self.fragment_url].iter().enumerate() {
+			let request = Box::new(FetchRequest {
+				state: self.fetch.clone(),
+				stage,
+				url: url.to_string(),
+			});
+
+			let c_url = CString::new(url.as_str()).unwrap();
+
+			unsafe {
+				emscripten_async_wget_data(c_url.as_ptr(), Box::into_raw(request) as _,
+					Some(on_fetch_load), Some(on_fetch_error));
+			}
+		}
+	}
+
+	pub fn handle_event(&mut self, e: &Event) {
+		match *e {
+			Event::KeyDown(key) if Some(key) == self.reload_key => self.reload(),
+			_ => {}
+		}
+	}
+
+	/// Should be called once a frame. Once both sources of a reload have arrived they are
+	/// built, and the result is returned. Errors are also printed
+	pub fn update(&mut self) -> Option<Result<(), Error>> {
+		let (vertex_src, fragment_src) = {
+			let mut fetch = self.fetch.borrow_mut();
+			if fetch.outstanding > 0 { return None }
+
+			if let Some(e) = fetch.error.take() {
+				fetch.sources = [None, None];
+				println!("{}", e);
+				return Some(Err(e))
+			}
+
+			match (fetch.sources[0].take(), fetch.sources[1].take()) {
+				(Some(vs), Some(fs)) => (vs, fs),
+				_ => return None,
+			}
+		};
+
+		match (self.compile)(&vertex_src, &fragment_src) {
+			Ok(shader) => {
+				self.shader = shader;
+				Some(Ok(()))
+			}
+
+			Err(e) => {
+				println!("Failed to reload '{}' / '{}' - keeping previous program\n{}",
+					self.vertex_url, self.fragment_url, e);
+				Some(Err(e))
+			}
+		}
+	}
+}
+
+unsafe extern "C"
+fn on_fetch_load(arg: *mut CVoid, data: *mut CVoid, size: i32) {
+	let request = Box::from_raw(arg as *mut FetchRequest);
+	let data = ::std::slice::from_raw_parts(data as *const u8, size as usize);
+
+	let mut state = request.state.borrow_mut();
+	state.sources[request.stage] = Some(String::from_utf8_lossy(data).into_owned());
+	state.outstanding -= 1;
+}
+
+unsafe extern "C"
+fn on_fetch_error(arg: *mut CVoid) {
+	let request = Box::from_raw(arg as *mut FetchRequest);
+
+	let mut state = request.state.borrow_mut();
+	state.error = Some(Error::Fetch(request.url.clone()));
+	state.outstanding -= 1;
+}
+
+#[cfg(all(test, feature = "headless"))] mod tests {
+	use bindings::headless;
+	use rendering::*;
+	use events::{Event, KeyCode};
+	use error::{Error, ShaderStage};
+
+	const VERT: &'static [u8] = b"attribute vec2 position; void main() { gl_Position = vec4(position, 0.0, 1.0); }";
+	const FRAG: &'static [u8] = b"void main() { gl_FragColor = vec4(1.0); }";
+
+	#[test]
+	fn reload() {
+		headless::reset();
+		headless::serve_file("shader.vert", VERT);
+		headless::serve_file("shader.frag", FRAG);
+
+		let mut reloadable = ReloadableShader::new("shader.vert", "shader.frag")
+			.reload_on(KeyCode::F(9));
+
+		assert!(reloadable.update().is_none());
+		assert!(!reloadable.is_loaded());
+
+		headless::complete_fetches();
+		assert!(reloadable.update().unwrap().is_ok());
+		assert!(reloadable.is_loaded());
+
+		let first = reloadable.shader();
+
+		// A broken edit is reported, and the working program kept
+		headless::serve_file("shader.frag", b"#error oops\nvoid main() {}");
+		reloadable.handle_event(&Event::KeyDown(KeyCode::F(9)));
+		headless::complete_fetches();
+
+		match reloadable.update() {
+			Some(Err(Error::ShaderCompile { stage: ShaderStage::Fragment, log, .. })) => assert!(log.contains("oops")),
+			_ => panic!("Expected compile error"),
+		}
+
+		assert_eq!(reloadable.shader().gl_handle, first.gl_handle);
+
+		headless::serve_file("shader.frag", FRAG);
+		reloadable.reload();
+		headless::complete_fetches();
+		assert!(reloadable.update().unwrap().is_ok());
+		assert!(reloadable.shader().gl_handle != first.gl_handle);
+		assert!(headless::with_state(|s| !s.programs.contains_key(&first.gl_handle)));
+	}
+
+	#[test]
+	fn fetch_error() {
+		headless::reset();
+		headless::serve_file("shader.vert", VERT);
+
+		let mut reloadable = ReloadableShader::new("shader.vert", "missing.frag");
+		headless::complete_fetches();
+
+		match reloadable.update() {
+			Some(Err(Error::Fetch(ref url))) if url == "missing.frag" => {}
+			_ => panic!("Expected fetch error"),
+		}
+
+		assert!(!reloadable.is_loaded());
+	}
+}
@@ -0,0 +1,281 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use common::math::*;
+use rendering::shader::ShaderHandle;
+use rendering::texture::Texture;
+use rendering::uniform::*;
+use error::Error;
+
+thread_local! {
+	// The id of the material that last uploaded uniforms to each program, keyed by program handle
+	static LAST_APPLIED: RefCell<HashMap<u32, u32>> = RefCell::new(HashMap::new());
+	static NEXT_MATERIAL_ID: RefCell<u32> = RefCell::new(1);
+}
+
+/// Makes the next apply of every material upload everything, since program handles are reused
+/// once a context is lost
+pub fn invalidate_material_state() {
+	LAST_APPLIED.with(|l| l.borrow_mut().clear());
+}
+
+#[derive(Clone)]
+pub enum MaterialValue {
+	Float(f32),
+	Int(i32),
+	Vec2(Vec2),
+	Vec3(Vec3),
+	Vec4(Vec4),
+	Mat4(Mat4),
+	/// Bound to the next free texture slot, with the sampler uniform set to that slot. Shared so
+	/// it can be tracked by a ResourceRegistry
+	Texture(Rc<RefCell<Texture>>),
+}
+
+impl MaterialValue {
+	fn gl_types(&self) -> &'static [u32] {
+		match *self {
+			MaterialValue::Float(_) => f32::GL_TYPES,
+			MaterialValue::Int(_) | MaterialValue::Texture(_) => i32::GL_TYPES,
+			MaterialValue::Vec2(_) => Vec2::GL_TYPES,
+			MaterialValue::Vec3(_) => Vec3::GL_TYPES,
+			MaterialValue::Vec4(_) => Vec4::GL_TYPES,
+			MaterialValue::Mat4(_) => Mat4::GL_TYPES,
+		}
+	}
+
+	unsafe fn upload(&self, location: i32, slot: i32) {
+		match *self {
+			MaterialValue::Float(ref v) => v.upload(location),
+			MaterialValue::Int(ref v) => v.upload(location),
+			MaterialValue::Vec2(ref v) => v.upload(location),
+			MaterialValue::Vec3(ref v) => v.upload(location),
+			MaterialValue::Vec4(ref v) => v.upload(location),
+			MaterialValue::Mat4(ref v) => v.upload(location),
+			MaterialValue::Texture(_) => slot.upload(location),
+		}
+	}
+}
+
+impl PartialEq for MaterialValue {
+	fn eq(&self, o: &MaterialValue) -> bool {
+		use self::MaterialValue::*;
+
+		match (self, o) {
+			(&Float(a), &Float(b)) => a == b,
+			(&Int(a), &Int(b)) => a == b,
+			(&Vec2(a), &Vec2(b)) => a == b,
+			(&Vec3(a), &Vec3(b)) => a == b,
+			(&Vec4(a), &Vec4(b)) => a == b,
+			(&Mat4(ref a), &Mat4(ref b)) => a.rows == b.rows,
+			(&Texture(ref a), &Texture(ref b)) => Rc::ptr_eq(a, b),
+			_ => false,
+		}
+	}
+}
+
+impl From<f32> for MaterialValue { fn from(v: f32) -> Self { MaterialValue::Float(v) } }
+impl From<i32> for MaterialValue { fn from(v: i32) -> Self { MaterialValue::Int(v) } }
+impl From<Vec2> for MaterialValue { fn from(v: Vec2) -> Self { MaterialValue::Vec2(v) } }
+impl From<Vec3> for MaterialValue { fn from(v: Vec3) -> Self { MaterialValue::Vec3(v) } }
+impl From<Vec4> for MaterialValue { fn from(v: Vec4) -> Self { MaterialValue::Vec4(v) } }
+impl From<Color> for MaterialValue { fn from(v: Color) -> Self { MaterialValue::Vec4(Vec4::new(v.r, v.g, v.b, v.a)) } }
+impl From<Mat4> for MaterialValue { fn from(v: Mat4) -> Self { MaterialValue::Mat4(v) } }
+impl From<Rc<RefCell<Texture>>> for MaterialValue { fn from(v: Rc<RefCell<Texture>>) -> Self { MaterialValue::Texture(v) } }
+
+struct MaterialParam {
+	name: String,
+	value: MaterialValue,
+	changed: u32,
+}
+
+/// A set of uniform values that can be applied to a shader in one go.
+///
+/// Values are only uploaded if they've changed since this material was last applied to the same
+/// program, and no other material has been applied to it in between. Setting a material's
+/// uniforms directly through the Shader in the meantime isn't detected.
+pub struct Material {
+	id: u32,
+	params: Vec<MaterialParam>,
+
+	generation: u32,
+	// The generation each program was last brought up to date with
+	applied: HashMap<u32, u32>,
+}
+
+impl Material {
+	pub fn new() -> Self {
+		let id = NEXT_MATERIAL_ID.with(|id| {
+			let mut id = id.borrow_mut();
+			*id += 1;
+			*id - 1
+		});
+
+		Material {
+			id,
+			params: Vec::new(),
+
+			generation: 0,
+			applied: HashMap::new(),
+		}
+	}
+
+	pub fn with<V>(mut self, uniform: &str, v: V) -> Self where V: Into<MaterialValue> {
+		self.set(uniform, v); self
+	}
+
+	pub fn set<V>(&mut self, uniform: &str, v: V) where V: Into<MaterialValue> {
+		let value = v.into();
+
+		if let Some(p) = self.params.iter_mut().find(|p| p.name == uniform) {
+			if p.value != value {
+				self.generation += 1;
+				p.value = value;
+				p.changed = self.generation;
+			}
+
+			return
+		}
+
+		self.generation += 1;
+		self.params.push(MaterialParam {
+			name: uniform.to_string(),
+			value,
+			changed: self.generation,
+		});
+	}
+
+	pub fn get(&self, uniform: &str) -> Option<&MaterialValue> {
+		self.params.iter()
+			.find(|p| p.name == uniform)
+			.map(|p| &p.value)
+	}
+
+	pub fn apply(&mut self, shader: &ShaderHandle) {
+		self.try_apply(shader)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// Parameters whose type doesn't match their uniform are skipped, and the first of them is
+	/// returned as an error once everything else is uploaded
+	pub fn try_apply(&mut self, shader: &ShaderHandle) -> Result<(), Error> {
+		assert!(shader.is_bound(), "Tried to apply material to unbound shader");
+
+		let program = shader.gl_handle;
+		let was_last = LAST_APPLIED.with(|l| l.borrow_mut().insert(program, self.id) == Some(self.id));
+
+		let up_to = match self.applied.get(&program) {
+			Some(&generation) if was_last => generation,
+			_ => 0,
+		};
+
+		let mut slot = 0;
+		let mut result = Ok(());
+
+		for p in self.params.iter() {
+			let texture_slot = slot;
+
+			// Texture bindings aren't program state, so they always need rebinding
+			if let MaterialValue::Texture(ref tex) = p.value {
+				tex.borrow().bind_to_slot(slot as u32);
+				slot += 1;
+			}
+
+			if p.changed <= up_to { continue }
+
+			let info = match shader.get_uniform_info(&p.name) {
+				Some(info) => info,
+				None => continue,
+			};
+
+			let gl_types = p.value.gl_types();
+			if !gl_types.contains(&info.ty) {
+				if result.is_ok() {
+					result = Err(Error::UniformTypeMismatch { name: p.name.clone(), expected: info.ty, given: gl_types[0] });
+				}
+
+				continue
+			}
+
+			unsafe {
+				p.value.upload(info.location, texture_slot);
+			}
+		}
+
+		self.applied.insert(program, self.generation);
+		result
+	}
+}
+
+#[cfg(all(test, feature = "headless"))] mod tests {
+	use bindings::headless;
+	use rendering::*;
+	use common::math::*;
+	use error::Error;
+	use std::cell::RefCell;
+	use std::rc::Rc;
+
+	#[test]
+	fn apply() {
+		headless::reset();
+
+		let shader = ShaderBuilder::new()
+			.uniform("tint", "vec4")
+			.uniform("scale", "float")
+			.uniform("tex", "sampler2D")
+			.output("u_tint * u_scale")
+			.finalize().unwrap();
+
+		let texture = Rc::new(RefCell::new(Texture::new()));
+
+		let mut material = Material::new()
+			.with("u_tint", Color::white())
+			.with("u_scale", 2.0)
+			.with("u_tex", texture.clone());
+
+		shader.use_program();
+		headless::take_calls();
+
+		material.apply(&shader);
+		assert_eq!(headless::calls_named("Uniform4f").len(), 1);
+		assert_eq!(headless::calls_named("Uniform1f").len(), 1);
+		assert_eq!(headless::calls_named("Uniform1i")[0].int(1), 0);
+		assert_eq!(headless::with_state(|s| s.texture_bindings[&0]), texture.borrow().gl_handle);
+
+		// Nothing changed, so only the texture is rebound
+		headless::take_calls();
+		material.set("u_scale", 2.0);
+		material.apply(&shader);
+		assert!(headless::calls_named("Uniform1f").is_empty());
+		assert!(headless::calls_named("Uniform4f").is_empty());
+		assert_eq!(headless::calls_named("BindTexture").len(), 1);
+
+		headless::take_calls();
+		material.set("u_scale", 3.0);
+		material.apply(&shader);
+		assert_eq!(headless::calls_named("Uniform1f").len(), 1);
+		assert!(headless::calls_named("Uniform4f").is_empty());
+
+		// Another material touching the program forces a full upload
+		Material::new().with("u_scale", 1.0).apply(&shader);
+		headless::take_calls();
+		material.apply(&shader);
+		assert_eq!(headless::calls_named("Uniform1f").len(), 1);
+		assert_eq!(headless::calls_named("Uniform4f").len(), 1);
+
+		// Mismatched parameters are reported, without holding up the rest
+		let mut mismatched = Material::new()
+			.with("u_scale", Vec2::zero())
+			.with("u_tint", Color::white());
+
+		headless::take_calls();
+		match mismatched.try_apply(&shader) {
+			Err(Error::UniformTypeMismatch { ref name, expected: gl::FLOAT, given: gl::FLOAT_VEC2 }) if name == "u_scale" => {}
+			x => panic!("Unexpected result {:?}", x),
+		}
+
+		assert!(headless::calls_named("Uniform2f").is_empty());
+		assert_eq!(headless::calls_named("Uniform4f").len(), 1);
+	}
+}
@@ -1,47 +1,131 @@
 #![allow(dead_code)]
 
+use std::cell::Cell;
+use std::marker::PhantomData;
 use std::mem::size_of;
 use std::ptr::null;
+use std::slice;
 
 use rendering::gl;
+use rendering::resources::Restore;
+use rendering::types::PackedColor;
+use error::Error;
+use webgl::{capabilities, element_index_uint_enabled};
 use common::math::*;
 
 pub struct VertexAttributeBinding {
 	pub index: u32,
 	pub width: i32,
 	pub offset: u32,
+
+	pub component_type: u32,
+	pub normalized: bool,
+
+	/// The shader attribute this binding feeds, used to bind and validate attribute locations
+	pub name: Option<String>,
 }
 
 pub struct VertexLayout {
 	pub size: u32,
 	pub attributes: Vec<VertexAttributeBinding>,
+
+	/// How many instances each element is used for when bound by an InstanceBuffer,
+	/// or 0 for regular per-vertex data
+	pub divisor: u32,
 }
 
 impl VertexLayout {
 	pub fn new<V: Vertex>() -> Self {
 		VertexLayout {
 			size: size_of::<V>() as _,
-			attributes: Vec::new()
+			attributes: Vec::new(),
+			divisor: 0,
 		}
 	}
 
 	pub fn null() -> Self {
-		VertexLayout { size: 0, attributes: Vec::new() }
+		VertexLayout { size: 0, attributes: Vec::new(), divisor: 0 }
+	}
+
+	pub fn divisor(self, divisor: u32) -> Self {
+		VertexLayout { divisor, ..self }
+	}
+
+	/// One past the highest binding index. Instance attributes are bound from here on
+	pub fn next_index(&self) -> u32 {
+		self.attributes.iter()
+			.map(|ab| ab.index + 1)
+			.max()
+			.unwrap_or(0)
 	}
 
-	pub fn add_binding(mut self, index: u32, width: i32, offset: u32) -> Self {
-		self.attributes.push(VertexAttributeBinding{index, width, offset});
+	pub fn add_binding(self, index: u32, width: i32, offset: u32) -> Self {
+		self.add_typed_binding(index, width, gl::FLOAT, false, offset)
+	}
+
+	pub fn add_typed_binding(mut self, index: u32, width: i32, component_type: u32, normalized: bool, offset: u32) -> Self {
+		self.attributes.push(VertexAttributeBinding{index, width, offset, component_type, normalized, name: None});
 		self
 	}
+
+	/// Names the most recently added binding
+	pub fn named(mut self, name: &str) -> Self {
+		self.attributes.last_mut()
+			.expect("Tried to name a binding before adding one")
+			.name = Some(name.to_string());
+
+		self
+	}
+
+	pub fn get_binding(&self, name: &str) -> Option<&VertexAttributeBinding> {
+		self.attributes.iter()
+			.find(|ab| ab.name.as_ref().map_or(false, |n| n == name))
+	}
 }
 
+/// Vertices are uploaded as their raw bytes, so implementors should be #[repr(C)] without
+/// padding between fields. #[derive(Vertex)] checks for the repr
 pub trait Vertex: Copy + Clone {
 	fn get_layout() -> VertexLayout;
 }
 
+pub use web_common_derive::Vertex;
+
+/// The (name, GLSL type) of each attribute of a vertex type, in binding order
+pub trait ShaderAttributes {
+	fn shader_attributes() -> Vec<(&'static str, &'static str)>;
+}
+
+/// Describes how a field type is bound by #[derive(Vertex)]
+pub trait VertexAttribute {
+	const WIDTH: i32;
+	const COMPONENT_TYPE: u32;
+	const NORMALIZED: bool;
+	const GLSL_TYPE: &'static str;
+}
+
+macro_rules! impl_vertex_attribute {
+	($ty:ty, $width:expr, $component_type:expr, $normalized:expr, $glsl_ty:expr) => {
+		impl VertexAttribute for $ty {
+			const WIDTH: i32 = $width;
+			const COMPONENT_TYPE: u32 = $component_type;
+			const NORMALIZED: bool = $normalized;
+			const GLSL_TYPE: &'static str = $glsl_ty;
+		}
+	};
+}
+
+impl_vertex_attribute!(f32, 1, gl::FLOAT, false, "float");
+impl_vertex_attribute!(Vec2, 2, gl::FLOAT, false, "vec2");
+impl_vertex_attribute!(Vec3, 3, gl::FLOAT, false, "vec3");
+impl_vertex_attribute!(Vec4, 4, gl::FLOAT, false, "vec4");
+impl_vertex_attribute!(Color, 4, gl::FLOAT, false, "vec4");
+impl_vertex_attribute!(PackedColor, 4, gl::UNSIGNED_BYTE, true, "vec4");
 
 
-#[derive(Copy, Clone)]
+
+#[derive(Copy, Clone, Vertex)]
+#[repr(C)]
 pub struct DefaultVertex {
 	pos: Vec3,
 }
@@ -52,116 +136,875 @@ impl DefaultVertex {
 	}
 }
 
-impl Vertex for DefaultVertex {
-	fn get_layout() -> VertexLayout {
-		VertexLayout::new::<Self>()
-			.add_binding(0, 3, 0)
+
+
+pub trait MeshIndex: Copy + Clone {
+	const GL_TYPE: u32;
+
+	/// The most vertices a single draw batch can address with this index type
+	const MAX_VERTICES: usize;
+
+	fn from_usize(i: usize) -> Self;
+	fn to_usize(self) -> usize;
+
+	fn is_supported() -> bool { true }
+	fn required_extension() -> &'static str { "" }
+}
+
+impl MeshIndex for u16 {
+	const GL_TYPE: u32 = gl::UNSIGNED_SHORT;
+	const MAX_VERTICES: usize = 1 << 16;
+
+	fn from_usize(i: usize) -> Self { i as u16 }
+	fn to_usize(self) -> usize { self as usize }
+}
+
+impl MeshIndex for u32 {
+	const GL_TYPE: u32 = gl::UNSIGNED_INT;
+	const MAX_VERTICES: usize = ::std::u32::MAX as usize;
+
+	fn from_usize(i: usize) -> Self { i as u32 }
+	fn to_usize(self) -> usize { self as usize }
+
+	fn is_supported() -> bool { element_index_uint_enabled() }
+	fn required_extension() -> &'static str { "OES_element_index_uint" }
+}
+
+/// A logical object within a mesh, drawn with Mesh::draw_submesh. In indices, or vertices for
+/// meshes without an index buffer
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SubMesh {
+	pub first: u32,
+	pub count: u32,
+}
+
+/// A range of indices that are relative to `base_vertex`, and so must be drawn separately
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshBatch {
+	pub base_vertex: u32,
+	pub first_index: u32,
+	pub count: u32,
+}
+
+
+
+/// How often a mesh's contents are expected to change, passed on to GL as a usage hint
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BufferUsage {
+	/// Uploaded once and drawn many times
+	Static,
+	/// Changed often, and drawn several times between changes
+	Dynamic,
+	/// Rebuilt for every draw
+	Stream,
+}
+
+impl BufferUsage {
+	pub fn gl_enum(self) -> u32 {
+		match self {
+			BufferUsage::Static => gl::STATIC_DRAW,
+			BufferUsage::Dynamic => gl::DYNAMIC_DRAW,
+			BufferUsage::Stream => gl::STREAM_DRAW,
+		}
 	}
 }
 
+thread_local! {
+	// Bitmask of the attributes enabled by the last Mesh::bind without a VAO.
+	// None when unknown, e.g. after switching contexts
+	static ENABLED_ATTRIBUTES: Cell<Option<u32>> = Cell::new(None);
+
+	// Bitmask of the attributes InstanceBuffer::bind gave a divisor outside of a VAO. None when unknown
+	static INSTANCED_ATTRIBUTES: Cell<Option<u32>> = Cell::new(None);
+}
+
+/// Forgets which vertex attributes are enabled, so the next Mesh::bind without a VAO sets all
+/// of them. Needed if attributes are enabled or disabled without going through Mesh
+pub fn invalidate_attribute_state() {
+	ENABLED_ATTRIBUTES.with(|e| e.set(None));
+	INSTANCED_ATTRIBUTES.with(|i| i.set(None));
+}
+
+// The indices of the bits set in `mask`
+fn attribute_indices(mask: u32) -> impl Iterator<Item=u32> {
+	(0..32).filter(move |i| mask & 1 << i != 0)
+}
 
+// Only sound for types without padding, which Vertex implementors are expected to be
+unsafe fn as_bytes<T>(items: &[T]) -> &[u8] {
+	slice::from_raw_parts(items.as_ptr() as *const u8, items.len() * size_of::<T>())
+}
 
 pub struct Mesh {
 	pub vbo: u32,
 	pub ebo: u32,
+	/// 0 if vertex array objects aren't available
+	pub vao: u32,
 	pub count: u32,
+	pub index_type: u32,
+	pub batches: Vec<MeshBatch>,
 	pub layout: VertexLayout,
+
+	/// Without an index buffer, `count` is the number of vertices and draws use DrawArrays
+	pub indexed: bool,
+	pub submeshes: Vec<SubMesh>,
+
+	usage: BufferUsage,
+	// Allocated sizes of the buffers in bytes, which can be more than the data they hold
+	vertex_capacity: usize,
+	index_capacity: usize,
+
+	// Bitmask of the attributes an InstanceBuffer enabled in this mesh's VAO
+	instanced_attributes: Cell<u32>,
+
+	vertex_count: usize,
+
+	// Copies of the buffer contents, so they can be reuploaded after a context loss. Only kept
+	// with retain_data, and empty otherwise
+	retain_data: bool,
+	vertex_data: Vec<u8>,
+	index_data: Vec<u8>,
 }
 
 impl Mesh {
 	pub fn new() -> Self {
+		Mesh::with_usage(BufferUsage::Static)
+	}
+
+	pub fn with_usage(usage: BufferUsage) -> Self {
 		Mesh {
 			vbo: gl::pls_make_buffer(),
 			ebo: gl::pls_make_buffer(),
+			vao: make_vertex_array(),
 			count: 0,
+			index_type: gl::UNSIGNED_SHORT,
+			batches: Vec::new(),
 			layout: VertexLayout::null(),
+
+			indexed: true,
+			submeshes: Vec::new(),
+
+			usage,
+			vertex_capacity: 0,
+			index_capacity: 0,
+
+			instanced_attributes: Cell::new(0),
+			vertex_count: 0,
+
+			retain_data: false,
+			vertex_data: Vec::new(),
+			index_data: Vec::new(),
+		}
+	}
+
+	pub fn usage(&self) -> BufferUsage { self.usage }
+
+	/// Keeps a copy of everything uploaded from here on, so the mesh can be restored after a
+	/// context loss. Without it, a restored mesh is empty until it's uploaded to again
+	pub fn retain_data(&mut self, retain: bool) {
+		self.retain_data = retain;
+
+		if !retain {
+			self.vertex_data = Vec::new();
+			self.index_data = Vec::new();
+		}
+	}
+
+	/// The number of vertices uploaded
+	pub fn vertex_count(&self) -> usize { self.vertex_count }
+
+	// With a VAO, this also records the attribute setup in it so bind has nothing left to do
+	fn upload_buffers(&mut self, vertex_data: &[u8], index_data: &[u8]) {
+		unsafe {
+			self.bind_vertex_array();
+
+			gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+			write_buffer(gl::ARRAY_BUFFER, vertex_data, &mut self.vertex_capacity, self.usage);
+
+			gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+			write_buffer(gl::ELEMENT_ARRAY_BUFFER, index_data, &mut self.index_capacity, self.usage);
+
+			if self.vao != 0 {
+				for ab in self.layout.attributes.iter() {
+					gl::EnableVertexAttribArray(ab.index);
+				}
+
+				self.bind_attributes(0);
+				gl::BindVertexArray(0);
+			}
 		}
 	}
 
+	/// Overwrites the vertices starting at `first`, without touching the rest of the buffer.
+	/// `V` must be the vertex type the mesh was built with
+	pub fn update_vertices<V>(&mut self, first: usize, verts: &[V]) where V: Vertex {
+		self.try_update_vertices(first, verts)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_update_vertices<V>(&mut self, first: usize, verts: &[V]) -> Result<(), Error> where V: Vertex {
+		let size = V::get_layout().size as usize;
+		if size != self.layout.size as usize {
+			return Err(Error::VertexSizeMismatch { expected: self.layout.size as usize, given: size });
+		}
+
+		let end = first.saturating_add(verts.len());
+		if end > self.vertex_count() {
+			return Err(Error::IndexOverflow { index: end - 1, max: self.vertex_count().saturating_sub(1) });
+		}
+
+		let data = unsafe { as_bytes(verts) };
+		if self.retain_data {
+			self.vertex_data[first * size .. end * size].copy_from_slice(data);
+		}
+
+		unsafe {
+			self.bind_vertex_array();
+			gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+			gl::BufferSubData(gl::ARRAY_BUFFER, (first * size) as _, data.len() as _, data.as_ptr() as _);
+		}
+
+		Ok(())
+	}
+
+	/// Overwrites the indices starting at `first`. Like those added through MeshBuilder, they're
+	/// relative to the base vertex of the batch they end up in
+	pub fn update_indices<I>(&mut self, first: usize, indices: &[I]) where I: MeshIndex {
+		self.try_update_indices(first, indices)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_update_indices<I>(&mut self, first: usize, indices: &[I]) -> Result<(), Error> where I: MeshIndex {
+		if !self.indexed {
+			return Err(Error::NotIndexed);
+		}
+
+		if I::GL_TYPE != self.index_type {
+			return Err(Error::IndexTypeMismatch { expected: self.index_type, given: I::GL_TYPE });
+		}
+
+		let end = first.saturating_add(indices.len());
+		if end > self.count as usize {
+			return Err(Error::IndexOverflow { index: end - 1, max: (self.count as usize).saturating_sub(1) });
+		}
+
+		let size = size_of::<I>();
+		let data = unsafe { as_bytes(indices) };
+		if self.retain_data {
+			self.index_data[first * size .. end * size].copy_from_slice(data);
+		}
+
+		unsafe {
+			self.bind_vertex_array();
+			gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+			gl::BufferSubData(gl::ELEMENT_ARRAY_BUFFER, (first * size) as _, data.len() as _, data.as_ptr() as _);
+		}
+
+		Ok(())
+	}
+
 	pub fn bind(&self) {
+		unsafe { self.bind_vertex_array(); }
+
+		if self.vao != 0 {
+			// Instance attributes are only wanted until the next bind, and their buffer may be gone by now
+			for index in attribute_indices(self.instanced_attributes.replace(0)) {
+				unsafe { gl::DisableVertexAttribArray(index); }
+			}
+
+			return
+		}
+
 		unsafe {
 			gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
 			gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+		}
+
+		self.update_enabled_attributes();
+		self.reset_divisors();
+		self.bind_attributes(0);
+	}
+
+	// Binds this mesh's VAO, or the default one if it doesn't have its own, so that element and
+	// attribute state set for it can't end up in another mesh's VAO
+	unsafe fn bind_vertex_array(&self) {
+		if self.vao != 0 || capabilities().vertex_array_objects {
+			gl::BindVertexArray(self.vao);
+		}
+	}
+
+	fn attribute_mask(&self) -> u32 {
+		self.layout.attributes.iter()
+			.fold(0u32, |mask, ab| mask | 1 << ab.index)
+	}
+
+	// Enables this mesh's attributes, and disables any a previous bind left enabled
+	fn update_enabled_attributes(&self) {
+		let wanted = self.attribute_mask();
+
+		ENABLED_ATTRIBUTES.with(|enabled| {
+			// When it's unknown, every attribute the context has is assumed to be in the wrong state
+			let current = enabled.get().unwrap_or_else(|| {
+				let max = capabilities().max_vertex_attribs.max(0).min(32) as u32;
+				!wanted & ((1u64 << max) - 1) as u32
+			});
+
+			for index in attribute_indices(wanted ^ current) {
+				unsafe {
+					if wanted & 1 << index != 0 {
+						gl::EnableVertexAttribArray(index);
+					} else {
+						gl::DisableVertexAttribArray(index);
+					}
+				}
+			}
+
+			enabled.set(Some(wanted));
+		});
+	}
 
+	// Undoes any divisors an InstanceBuffer left on this mesh's attributes
+	fn reset_divisors(&self) {
+		let wanted = self.attribute_mask();
+
+		INSTANCED_ATTRIBUTES.with(|instanced| {
+			let unknown = if capabilities().instancing { !0 } else { 0 };
+			let current = instanced.get().unwrap_or(unknown);
+
+			for index in attribute_indices(current & wanted) {
+				unsafe { gl::VertexAttribDivisor(index, 0); }
+			}
+
+			instanced.set(Some(current & !wanted));
+		});
+	}
+
+	fn bind_attributes(&self, base_vertex: u32) {
+		let base_offset = base_vertex * self.layout.size;
+
+		unsafe {
 			for ab in self.layout.attributes.iter() {
-				gl::EnableVertexAttribArray(ab.index);
-				gl::VertexAttribPointer(ab.index, ab.width, gl::FLOAT, gl::FALSE, self.layout.size as i32, ab.offset as _);
+				gl::VertexAttribPointer(ab.index, ab.width, ab.component_type, ab.normalized as u8,
+					self.layout.size as i32, (base_offset + ab.offset) as _);
 			}
 		}
 	}
 
+	fn index_size(&self) -> u32 {
+		match self.index_type {
+			gl::UNSIGNED_BYTE => 1,
+			gl::UNSIGNED_INT => 4,
+			_ => 2,
+		}
+	}
+
 	pub fn draw(&self, mode: u32) {
+		self.draw_range(mode, 0, self.count);
+	}
+
+	/// Draws `count` indices starting at `first`, or vertices if the mesh isn't indexed
+	pub fn draw_range(&self, mode: u32, first: u32, count: u32) {
+		assert!(first + count <= self.count, "Draw range {}..{} is outside of the mesh", first, first + count);
+
+		if !self.indexed {
+			unsafe { gl::DrawArrays(mode, first as _, count as _); }
+			return
+		}
+
+		self.draw_batches(first, count, |count, offset| unsafe {
+			gl::DrawElements(mode, count, self.index_type, offset as _);
+		});
+	}
+
+	pub fn draw_submesh(&self, mode: u32, submesh: usize) {
+		let SubMesh { first, count } = self.submeshes[submesh];
+		self.draw_range(mode, first, count);
+	}
+
+	/// Draws `instances` copies of the mesh, using the attributes of any InstanceBuffer bound
+	/// since the mesh was. Needs ANGLE_instanced_arrays under WebGL1
+	pub fn draw_instanced(&self, mode: u32, instances: u32) {
+		assert!(capabilities().instancing, "Instanced drawing needs ANGLE_instanced_arrays or WebGL2");
+
+		if !self.indexed {
+			unsafe { gl::DrawArraysInstanced(mode, 0, self.count as _, instances as _); }
+			return
+		}
+
+		self.draw_batches(0, self.count, |count, offset| unsafe {
+			gl::DrawElementsInstanced(mode, count, self.index_type, offset as _, instances as _);
+		});
+	}
+
+	// Calls `draw` with the index count and byte offset of each batch's part of the range
+	fn draw_batches<F>(&self, first: u32, count: u32, draw: F) where F: Fn(i32, u32) {
+		let index_size = self.index_size();
+
+		if self.batches.len() <= 1 {
+			draw(count as _, first * index_size);
+			return
+		}
+
+		// WebGL has no base vertex support, so each batch gets its own attribute pointers.
+		// A bound VAO doesn't include the array buffer binding the pointers are taken from
+		unsafe { gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo); }
+
+		let end = first + count;
+
+		// bind() leaves the pointers at the first vertex
+		let mut bound_base = 0;
+
+		for batch in self.batches.iter() {
+			let start = batch.first_index.max(first);
+			let stop = (batch.first_index + batch.count).min(end);
+			if start >= stop { continue }
+
+			if batch.base_vertex != bound_base {
+				self.bind_attributes(batch.base_vertex);
+				bound_base = batch.base_vertex;
+			}
+
+			draw((stop - start) as _, start * index_size);
+		}
+
+		// A VAO is only set up when it's first bound, so has to be left pointing at the first vertex
+		if bound_base != 0 {
+			self.bind_attributes(0);
+		}
+	}
+}
+
+
+
+impl Drop for Mesh {
+	fn drop(&mut self) {
+		unsafe {
+			gl::DeleteBuffers(1, &self.vbo);
+			gl::DeleteBuffers(1, &self.ebo);
+
+			if self.vao != 0 {
+				gl::DeleteVertexArrays(1, &self.vao);
+			}
+		}
+	}
+}
+
+impl Restore for Mesh {
+	fn restore(&mut self) -> Result<(), Error> {
+		self.vbo = gl::pls_make_buffer();
+		self.ebo = gl::pls_make_buffer();
+		self.vao = make_vertex_array();
+		self.vertex_capacity = 0;
+		self.index_capacity = 0;
+		self.instanced_attributes.set(0);
+
+		if !self.retain_data {
+			self.count = 0;
+			self.vertex_count = 0;
+			self.batches.clear();
+			self.submeshes.clear();
+		}
+
+		let vertex_data = ::std::mem::replace(&mut self.vertex_data, Vec::new());
+		let index_data = ::std::mem::replace(&mut self.index_data, Vec::new());
+		self.upload_buffers(&vertex_data, &index_data);
+		self.vertex_data = vertex_data;
+		self.index_data = index_data;
+		Ok(())
+	}
+}
+
+/// Per-instance attributes for Mesh::draw_instanced, laid out by `T` - usually a vertex type with
+/// #[vertex(per_instance)]. Meant to be reuploaded as often as every frame
+pub struct InstanceBuffer<T: Vertex> {
+	pub vbo: u32,
+	layout: VertexLayout,
+
+	usage: BufferUsage,
+	capacity: usize,
+	len: usize,
+
+	// Kept for reuploading after a context loss, with retain_data
+	retain_data: bool,
+	data: Vec<u8>,
+	_instance: PhantomData<T>,
+}
+
+impl<T> InstanceBuffer<T> where T: Vertex {
+	pub fn new() -> Self {
+		InstanceBuffer::with_usage(BufferUsage::Stream)
+	}
+
+	pub fn with_usage(usage: BufferUsage) -> Self {
+		InstanceBuffer {
+			vbo: gl::pls_make_buffer(),
+			layout: T::get_layout(),
+
+			usage,
+			capacity: 0,
+			len: 0,
+
+			retain_data: false,
+			data: Vec::new(),
+			_instance: PhantomData,
+		}
+	}
+
+	/// Keeps a copy of the instances so they survive a context loss, like Mesh::retain_data
+	pub fn retain_data(&mut self, retain: bool) {
+		self.retain_data = retain;
+		if !retain { self.data = Vec::new(); }
+	}
+
+	pub fn len(&self) -> usize { self.len }
+
+	/// Replaces the contents of the buffer, growing it if needed
+	pub fn upload(&mut self, instances: &[T]) {
+		let data = unsafe { as_bytes(instances) };
+		self.len = instances.len();
+
+		unsafe {
+			gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+			write_buffer(gl::ARRAY_BUFFER, data, &mut self.capacity, self.usage);
+		}
+
+		if self.retain_data {
+			self.data = data.to_vec();
+		}
+	}
+
+	/// Overwrites the instances starting at `first`
+	pub fn update(&mut self, first: usize, instances: &[T]) {
+		self.try_update(first, instances)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_update(&mut self, first: usize, instances: &[T]) -> Result<(), Error> {
+		let end = first.saturating_add(instances.len());
+		if end > self.len() {
+			return Err(Error::IndexOverflow { index: end - 1, max: self.len().saturating_sub(1) });
+		}
+
+		let size = size_of::<T>();
+		let data = unsafe { as_bytes(instances) };
+		if self.retain_data {
+			self.data[first * size .. end * size].copy_from_slice(data);
+		}
+
+		unsafe {
+			gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+			gl::BufferSubData(gl::ARRAY_BUFFER, (first * size) as _, data.len() as _, data.as_ptr() as _);
+		}
+
+		Ok(())
+	}
+
+	/// Binds the instance attributes to the locations following `mesh`'s, which should already be
+	/// bound. They stay bound until the mesh is bound again. ShaderBuilder::instance_layout binds
+	/// shader attributes to match
+	pub fn bind(&self, mesh: &Mesh) {
+		let first = mesh.layout.next_index();
+		let divisor = self.layout.divisor.max(1);
+
+		let mask = self.layout.attributes.iter()
+			.fold(0u32, |mask, ab| mask | 1 << (first + ab.index));
+
+		unsafe {
+			gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+
+			for ab in self.layout.attributes.iter() {
+				let index = first + ab.index;
+				gl::VertexAttribPointer(index, ab.width, ab.component_type, ab.normalized as u8,
+					self.layout.size as i32, ab.offset as _);
+				gl::VertexAttribDivisor(index, divisor);
+			}
+		}
+
+		// A VAO keeps all of this to itself, otherwise the next Mesh::bind needs to know to undo it
+		if mesh.vao != 0 {
+			for index in attribute_indices(mask) {
+				unsafe { gl::EnableVertexAttribArray(index); }
+			}
+
+			mesh.instanced_attributes.set(mesh.instanced_attributes.get() | mask);
+			return
+		}
+
+		ENABLED_ATTRIBUTES.with(|enabled| {
+			for index in attribute_indices(mask & !enabled.get().unwrap_or(0)) {
+				unsafe { gl::EnableVertexAttribArray(index); }
+			}
+
+			enabled.set(enabled.get().map(|e| e | mask));
+		});
+
+		INSTANCED_ATTRIBUTES.with(|instanced| instanced.set(instanced.get().map(|i| i | mask)));
+	}
+}
+
+impl<T> Drop for InstanceBuffer<T> where T: Vertex {
+	fn drop(&mut self) {
+		unsafe {
+			gl::DeleteBuffers(1, &self.vbo);
+		}
+	}
+}
+
+impl<T> Restore for InstanceBuffer<T> where T: Vertex {
+	fn restore(&mut self) -> Result<(), Error> {
+		self.vbo = gl::pls_make_buffer();
+		self.capacity = 0;
+		if !self.retain_data { self.len = 0; }
+
 		unsafe {
-			gl::DrawElements(mode, self.count as _, gl::UNSIGNED_SHORT, null());
+			gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+			write_buffer(gl::ARRAY_BUFFER, &self.data, &mut self.capacity, self.usage);
+		}
+
+		Ok(())
+	}
+}
+
+// Writes `data` to the start of the buffer bound to `target`, only reallocating it if `data` doesn't
+// fit. Buffers that aren't static get twice the room they had, so steady growth doesn't reallocate
+// every upload
+unsafe fn write_buffer(target: u32, data: &[u8], capacity: &mut usize, usage: BufferUsage) {
+	if *capacity > 0 && data.len() <= *capacity {
+		if !data.is_empty() {
+			gl::BufferSubData(target, 0, data.len() as _, data.as_ptr() as _);
 		}
+
+		return
 	}
+
+	let size = match usage {
+		BufferUsage::Static => data.len(),
+		_ => data.len().max(*capacity * 2),
+	};
+
+	if size == data.len() {
+		gl::BufferData(target, size as _, data.as_ptr() as _, usage.gl_enum());
+	} else {
+		gl::BufferData(target, size as _, null(), usage.gl_enum());
+		gl::BufferSubData(target, 0, data.len() as _, data.as_ptr() as _);
+	}
+
+	*capacity = size;
+}
+
+// Only with OES_vertex_array_object or WebGL2, otherwise 0
+fn make_vertex_array() -> u32 {
+	if !capabilities().vertex_array_objects {
+		return 0
+	}
+
+	let mut vao = 0;
+	unsafe { gl::GenVertexArrays(1, &mut vao); }
+	vao
 }
 
 
 
-pub struct MeshBuilder<V: Vertex> {
+pub struct MeshBuilder<V: Vertex, I: MeshIndex = u16> {
 	verts: Vec<V>,
-	indices: Vec<u16>, // NOTE: index type be an option
+	indices: Vec<I>,
+	batches: Vec<MeshBatch>,
+
+	indexed: bool,
+	// The first index of each sub-mesh. Each ends where the next starts
+	submesh_starts: Vec<u32>,
+
+	// How many vertices add_vert has added since another primitive was started
+	loose_verts: usize,
 }
 
 impl<V> MeshBuilder<V> where V: Vertex {
 	pub fn new() -> Self {
+		MeshBuilder::with_index_type()
+	}
+
+	/// Builds meshes without an index buffer, drawn with DrawArrays. Primitives are added the
+	/// same way, and their vertices are duplicated as the indices would have them on upload
+	pub fn new_unindexed() -> Self {
+		MeshBuilder { indexed: false, ..MeshBuilder::new() }
+	}
+}
+
+impl<V, I> MeshBuilder<V, I> where V: Vertex, I: MeshIndex {
+	pub fn with_index_type() -> Self {
 		MeshBuilder {
 			verts: Vec::new(),
 			indices: Vec::new(),
+			batches: Vec::new(),
+
+			indexed: true,
+			submesh_starts: Vec::new(),
+
+			loose_verts: 0,
 		}
 	}
 
 	pub fn clear(&mut self) {
 		self.verts.clear();
 		self.indices.clear();
+		self.batches.clear();
+		self.submesh_starts.clear();
+		self.loose_verts = 0;
 	}
 
 	pub fn get_vertex_count(&self) -> usize { self.verts.len() }
 	pub fn get_index_count(&self) -> usize { self.indices.len() }
+	pub fn get_batch_count(&self) -> usize { self.batches.len() }
+	pub fn get_submesh_count(&self) -> usize { self.submesh_starts.len() }
+
+	/// Ends the current sub-mesh, and starts a new one with whatever is added next.
+	/// Returns its index in Mesh::submeshes. Anything added before the first call isn't part of one
+	pub fn begin_submesh(&mut self) -> usize {
+		self.submesh_starts.push(self.indices.len() as u32);
+		self.submesh_starts.len() - 1
+	}
+
+	fn submeshes(&self) -> Vec<SubMesh> {
+		let ends = self.submesh_starts.iter().skip(1).cloned()
+			.chain(Some(self.indices.len() as u32));
+
+		self.submesh_starts.iter().zip(ends)
+			.map(|(&first, end)| SubMesh { first, count: end - first })
+			.collect()
+	}
+
+	// Each vertex as it's referenced by the indices
+	fn unindexed_verts(&self) -> Vec<V> {
+		self.batches.iter()
+			.flat_map(|b| {
+				let indices = &self.indices[b.first_index as usize .. (b.first_index + b.count) as usize];
+				indices.iter().map(move |i| self.verts[b.base_vertex as usize + i.to_usize()])
+			})
+			.collect()
+	}
 
 	pub fn upload_to(&self, mesh: &mut Mesh) {
-		unsafe {
-			mesh.layout = V::get_layout();
-			mesh.count = self.indices.len() as _;
-			let vert_size = mesh.layout.size * self.verts.len() as u32;
-			let idx_size = size_of::<u16>() * self.indices.len();
+		self.try_upload_to(mesh)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_upload_to(&self, mesh: &mut Mesh) -> Result<(), Error> {
+		if !I::is_supported() {
+			return Err(Error::MissingExtension(I::required_extension()));
+		}
+
+		mesh.layout = V::get_layout();
+		mesh.count = self.indices.len() as _;
+		mesh.index_type = I::GL_TYPE;
+		mesh.indexed = self.indexed;
+		mesh.submeshes = self.submeshes();
+
+		let unindexed_verts;
+		let (verts, indices): (&[V], &[I]) = if self.indexed {
+			mesh.batches = self.batches.clone();
+			(&self.verts, &self.indices)
+		} else {
+			mesh.batches = Vec::new();
+			unindexed_verts = self.unindexed_verts();
+			(&unindexed_verts, &[])
+		};
+
+		mesh.vertex_count = verts.len();
+
+		let (vertex_data, index_data) = unsafe { (as_bytes(verts), as_bytes(indices)) };
+		mesh.upload_buffers(vertex_data, index_data);
+
+		if mesh.retain_data {
+			mesh.vertex_data = vertex_data.to_vec();
+			mesh.index_data = index_data.to_vec();
+		}
+
+		Ok(())
+	}
+
+	// Makes sure the current batch can address `num_verts` more vertices, starting a new one if not.
+	// Returns the index of the next vertex relative to the current batch
+	fn begin_primitive(&mut self, num_verts: usize) -> Result<usize, Error> {
+		if num_verts > I::MAX_VERTICES {
+			return Err(Error::IndexOverflow { index: num_verts - 1, max: I::MAX_VERTICES - 1 });
+		}
 
-			gl::BindBuffer(gl::ARRAY_BUFFER, mesh.vbo);
-			gl::BufferData(gl::ARRAY_BUFFER, vert_size as _, self.verts.as_ptr() as _, gl::STATIC_DRAW);
+		let needs_new_batch = match self.batches.last() {
+			Some(b) => self.verts.len() - b.base_vertex as usize + num_verts > I::MAX_VERTICES,
+			None => true,
+		};
 
-			gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, mesh.ebo);
-			gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, idx_size as _, self.indices.as_ptr() as _, gl::STATIC_DRAW);
+		if needs_new_batch {
+			self.batches.push(MeshBatch {
+				base_vertex: self.verts.len() as u32,
+				first_index: self.indices.len() as u32,
+				count: 0,
+			});
 		}
+
+		self.loose_verts = 0;
+
+		let base_vertex = self.batches.last().unwrap().base_vertex as usize;
+		Ok(self.verts.len() - base_vertex)
+	}
+
+	fn push_index(&mut self, index: usize) {
+		self.indices.push(I::from_usize(index));
+		self.batches.last_mut().unwrap().count += 1;
 	}
 
+	/// Adds triangles a vertex at a time. Room for a whole triangle is made at its first vertex,
+	/// so a triangle is never split between batches
 	pub fn add_vert(&mut self, v: V) {
-		self.indices.push(self.verts.len() as _);
+		if self.loose_verts % 3 == 0 {
+			self.begin_primitive(3).unwrap();
+		}
+
+		let index = self.verts.len() - self.batches.last().unwrap().base_vertex as usize;
 		self.verts.push(v);
+		self.push_index(index);
+		self.loose_verts += 1;
 	}
 
-	pub fn add_direct(&mut self, vs: &[V], es: &[u16]) {
-		assert!(es.len() >= 3);
+	pub fn add_direct(&mut self, vs: &[V], es: &[I]) {
+		self.try_add_direct(vs, es)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
 
-		let base = self.verts.len() as u16;
+	pub fn try_add_direct(&mut self, vs: &[V], es: &[I]) -> Result<(), Error> {
+		if es.len() < 3 {
+			return Err(Error::NotEnoughElements { expected: 3, given: es.len() });
+		}
+
+		if let Some(e) = es.iter().map(|e| e.to_usize()).max() {
+			if e >= vs.len() {
+				return Err(Error::IndexOverflow { index: e, max: vs.len().saturating_sub(1) });
+			}
+		}
+
+		let base = self.begin_primitive(vs.len())?;
 
 		self.verts.extend_from_slice(vs);
-		self.indices.extend(es.iter().map(|&e| e + base));
+		for e in es.iter() {
+			self.push_index(base + e.to_usize());
+		}
+
+		Ok(())
 	}
 
 	pub fn add_quad(&mut self, vs: &[V]) {
 		assert!(vs.len() >= 4);
 
-		let base = self.verts.len() as u16;
+		let base = self.begin_primitive(4).unwrap();
 		self.verts.extend_from_slice(&vs[..4]);
 
-		self.indices.push(base + 0);
-		self.indices.push(base + 1);
-		self.indices.push(base + 2);
+		self.push_index(base + 0);
+		self.push_index(base + 1);
+		self.push_index(base + 2);
 
-		self.indices.push(base + 0);
-		self.indices.push(base + 2);
-		self.indices.push(base + 3);
+		self.push_index(base + 0);
+		self.push_index(base + 2);
+		self.push_index(base + 3);
 	}
 
 	pub fn add_convex_poly(&mut self, vs: &[V]) {
@@ -171,36 +1014,642 @@ impl<V> MeshBuilder<V> where V: Vertex {
 	pub fn add_tri_fan(&mut self, vs: &[V]) {
 		assert!(vs.len() >= 3);
 
-		let base = self.verts.len() as u16;
+		let base = self.begin_primitive(vs.len())
+			.unwrap_or_else(|e| panic!("{}", e));
+
 		self.verts.extend_from_slice(vs);
 
 		for i in 1..vs.len()-1 {
-			let i = i as u16;
-			self.indices.push(base + 0);
-			self.indices.push(base + i);
-			self.indices.push(base + i+1);
+			self.push_index(base + 0);
+			self.push_index(base + i);
+			self.push_index(base + i+1);
 		}
 	}
 
 	pub fn add_tri_strip(&mut self, vs: &[V]) {
 		assert!(vs.len() >= 3);
 
-		let base = self.verts.len() as u16;
+		let base = self.begin_primitive(vs.len())
+			.unwrap_or_else(|e| panic!("{}", e));
+
 		self.verts.extend_from_slice(vs);
 
 		for i in 0..vs.len()-2 {
-			let i = i as u16;
-			self.indices.push(base + i);
-			self.indices.push(base + i+1);
-			self.indices.push(base + i+2);
+			self.push_index(base + i);
+			self.push_index(base + i+1);
+			self.push_index(base + i+2);
 		}
 	}
 }
 
-impl<V> From<MeshBuilder<V>> for Mesh where V: Vertex {
-	fn from(mb: MeshBuilder<V>) -> Self {
+impl<V, I> From<MeshBuilder<V, I>> for Mesh where V: Vertex, I: MeshIndex {
+	fn from(mb: MeshBuilder<V, I>) -> Self {
 		let mut mesh = Mesh::new();
 		mb.upload_to(&mut mesh);
 		mesh
 	}
 }
+
+#[cfg(all(test, feature = "headless"))] pub mod tests {
+	use bindings::headless;
+	use rendering::gl;
+	use rendering::mesh::*;
+	use rendering::primitives::SurfacePoint;
+
+	/// A vertex with more than one attribute, shared by tests that need one
+	#[allow(dead_code)]
+	#[derive(Copy, Clone, Vertex)]
+	#[repr(C)]
+	pub struct LitVertex {
+		pub pos: Vec3,
+		pub normal: Vec3,
+		pub uv: Vec2,
+	}
+
+	impl LitVertex {
+		pub fn new(pos: Vec3) -> Self {
+			LitVertex { pos, normal: Vec3::zero(), uv: Vec2::zero() }
+		}
+	}
+
+	pub fn lit(p: SurfacePoint) -> LitVertex {
+		LitVertex { pos: p.pos, normal: p.normal, uv: p.uv }
+	}
+
+	#[test]
+	fn upload_to() {
+		headless::reset();
+
+		let mut mb = MeshBuilder::new();
+		mb.add_quad(&[
+			DefaultVertex::new(Vec3::new(0.0, 0.0, 0.0)),
+			DefaultVertex::new(Vec3::new(1.0, 0.0, 0.0)),
+			DefaultVertex::new(Vec3::new(1.0, 1.0, 0.0)),
+			DefaultVertex::new(Vec3::new(0.0, 1.0, 0.0)),
+		]);
+
+		let mesh: Mesh = mb.into();
+		assert_eq!(mesh.count, 6);
+
+		headless::with_state(|s| {
+			let vbo = &s.buffers[&mesh.vbo];
+			let ebo = &s.buffers[&mesh.ebo];
+
+			assert_eq!(vbo.data.len(), 4 * 12);
+			assert_eq!(vbo.usage, gl::STATIC_DRAW);
+			assert_eq!(ebo.data, [0u8,0, 1,0, 2,0, 0,0, 2,0, 3,0]);
+		});
+
+		drop(mesh);
+		assert!(headless::with_state(|s| s.buffers.is_empty()));
+	}
+
+	#[test]
+	fn bind_and_draw() {
+		headless::reset();
+
+		let mut mb = MeshBuilder::new();
+		mb.add_tri_fan(&[
+			DefaultVertex::new(Vec3::zero()),
+			DefaultVertex::new(Vec3::zero()),
+			DefaultVertex::new(Vec3::zero()),
+			DefaultVertex::new(Vec3::zero()),
+		]);
+
+		let mesh: Mesh = mb.into();
+		headless::take_calls();
+
+		mesh.bind();
+		mesh.draw(gl::TRIANGLES);
+
+		let pointers = headless::calls_named("VertexAttribPointer");
+		assert_eq!(pointers.len(), 1);
+		assert_eq!(pointers[0].int(0), 0);
+		assert_eq!(pointers[0].int(1), 3);
+		assert_eq!(pointers[0].int(2), gl::FLOAT as i64);
+		assert_eq!(pointers[0].int(4), 12);
+
+		let draws = headless::calls_named("DrawElements");
+		assert_eq!(draws.len(), 1);
+		assert_eq!(draws[0].int(0), gl::TRIANGLES as i64);
+		assert_eq!(draws[0].int(1), 6);
+		assert_eq!(draws[0].int(2), gl::UNSIGNED_SHORT as i64);
+
+		headless::with_state(|s| {
+			assert_eq!(s.array_buffer_binding, mesh.vbo);
+			assert_eq!(s.element_array_buffer_binding, mesh.ebo);
+			assert!(s.enabled_attribs.contains(&0));
+		});
+	}
+
+	#[test]
+	fn buffer_growth_and_updates() {
+		headless::reset();
+
+		let quad = [DefaultVertex::new(Vec3::zero()); 4];
+		let mut mesh = Mesh::with_usage(BufferUsage::Dynamic);
+
+		let mut mb = MeshBuilder::new();
+		mb.add_quad(&quad);
+		mb.upload_to(&mut mesh);
+		assert_eq!(headless::calls_named("BufferData").len(), 2);
+
+		// Smaller data fits in what's already allocated
+		mb.clear();
+		mb.add_tri_fan(&quad[..3]);
+		headless::take_calls();
+		mb.upload_to(&mut mesh);
+		assert!(headless::calls_named("BufferData").is_empty());
+		assert_eq!(headless::calls_named("BufferSubData").len(), 2);
+
+		// Growing doubles the capacity
+		mb.clear();
+		mb.add_tri_fan(&[DefaultVertex::new(Vec3::zero()); 5]);
+		headless::take_calls();
+		mb.upload_to(&mut mesh);
+
+		let allocs = headless::calls_named("BufferData");
+		assert_eq!(allocs.len(), 2);
+		assert_eq!(allocs[0].int(1), 4 * 12 * 2);
+		assert_eq!(allocs[0].int(3), gl::DYNAMIC_DRAW as i64);
+		assert_eq!(mesh.vertex_count(), 5);
+
+		mesh.update_vertices(3, &[DefaultVertex::new(Vec3::new(1.0, 2.0, 3.0))]);
+		mesh.update_indices(6, &[4u16, 2, 1]);
+
+		headless::with_state(|s| {
+			let vbo = &s.buffers[&mesh.vbo].data;
+			assert_eq!(&vbo[36..40], &1.0f32.to_bits().to_le_bytes());
+			assert_eq!(&vbo[44..48], &3.0f32.to_bits().to_le_bytes());
+			assert_eq!(&s.buffers[&mesh.ebo].data[12..18], &[4u8,0, 2,0, 1,0]);
+		});
+
+		// Only what's been uploaded can be updated
+		match mesh.try_update_vertices(4, &quad[..2]) {
+			Err(Error::IndexOverflow { index: 5, max: 4 }) => {}
+			x => panic!("Unexpected result {:?}", x),
+		}
+
+		match mesh.try_update_indices(9, &[0u16]) {
+			Err(Error::IndexOverflow { index: 9, max: 8 }) => {}
+			x => panic!("Unexpected result {:?}", x),
+		}
+
+		match mesh.try_update_indices(::std::usize::MAX, &[0u16]) {
+			Err(Error::IndexOverflow { .. }) => {}
+			x => panic!("Unexpected result {:?}", x),
+		}
+
+		// As are only the types it was built with
+		match mesh.try_update_vertices(0, &[LitVertex::new(Vec3::zero())]) {
+			Err(Error::VertexSizeMismatch { expected: 12, given: 32 }) => {}
+			x => panic!("Unexpected result {:?}", x),
+		}
+
+		match mesh.try_update_indices(0, &[0u32]) {
+			Err(Error::IndexTypeMismatch { expected: gl::UNSIGNED_SHORT, given: gl::UNSIGNED_INT }) => {}
+			x => panic!("Unexpected result {:?}", x),
+		}
+	}
+
+	#[test]
+	fn instanced_draw() {
+		use webgl::WebGLContext;
+		use extensions::AngleInstancedArrays;
+
+		#[allow(dead_code)]
+		#[derive(Copy, Clone, Vertex)]
+		#[vertex(per_instance)]
+		#[repr(C)]
+		struct Particle {
+			offset: Vec3,
+			color: Vec4,
+		}
+
+		headless::reset();
+
+		let ctx = WebGLContext::new(false);
+		ctx.enable_extension::<AngleInstancedArrays>().unwrap();
+
+		let shader = ::ShaderBuilder::new()
+			.vertex_layout::<DefaultVertex>()
+			.instance_layout::<Particle>()
+			.output("color")
+			.finalize().unwrap();
+
+		let attributes = headless::with_state(|s| s.programs[&shader.gl_handle].active_attributes.clone());
+		let location = |name: &str| attributes.iter().find(|a| a.0 == name).unwrap().2;
+		assert_eq!((location("offset"), location("color")), (1, 2));
+
+		let mut mb = MeshBuilder::new();
+		mb.add_quad(&[DefaultVertex::new(Vec3::zero()); 4]);
+		let mesh: Mesh = mb.into();
+
+		let mut particles = InstanceBuffer::new();
+		particles.upload(&[Particle { offset: Vec3::zero(), color: Vec4::zero() }; 100]);
+		assert_eq!(particles.len(), 100);
+
+		headless::take_calls();
+		mesh.bind();
+		particles.bind(&mesh);
+		mesh.draw_instanced(gl::TRIANGLES, 100);
+
+		let pointers = headless::calls_named("VertexAttribPointer");
+		assert_eq!(pointers.iter().map(|c| c.int(0)).collect::<Vec<_>>(), [0, 1, 2]);
+		assert_eq!(pointers[2].int(4), 28);
+		assert_eq!(pointers[2].ptr(5), 12);
+
+		let draws = headless::calls_named("DrawElementsInstanced");
+		assert_eq!(draws.len(), 1);
+		assert_eq!((draws[0].int(1), draws[0].int(4)), (6, 100));
+
+		headless::with_state(|s| {
+			assert_eq!(s.attrib_divisors[&1], 1);
+			assert_eq!(s.attrib_divisors[&2], 1);
+			assert_eq!(s.enabled_attribs.len(), 3);
+		});
+
+		// A plain mesh using the same locations gets its divisors back
+		let mut mb = MeshBuilder::new();
+		mb.add_tri_fan(&[LitVertex::new(Vec3::zero()); 3]);
+		let colored: Mesh = mb.into();
+		colored.bind();
+
+		headless::with_state(|s| {
+			assert_eq!(s.attrib_divisors[&1], 0);
+			assert_eq!(s.attrib_divisors[&2], 0);
+			assert_eq!(s.enabled_attribs.len(), 3);
+		});
+	}
+
+	#[test]
+	fn instance_attributes_dont_outlive_draw() {
+		use webgl::WebGLContext;
+
+		#[allow(dead_code)]
+		#[derive(Copy, Clone, Vertex)]
+		#[vertex(per_instance)]
+		#[repr(C)]
+		struct Offset {
+			offset: Vec3,
+		}
+
+		headless::reset();
+
+		let _ctx = WebGLContext::new_webgl2(false);
+
+		let mut mb = MeshBuilder::new();
+		mb.add_quad(&[DefaultVertex::new(Vec3::zero()); 4]);
+		let mesh: Mesh = mb.into();
+		assert!(mesh.vao != 0);
+
+		let mut offsets = InstanceBuffer::new();
+		offsets.upload(&[Offset { offset: Vec3::zero() }; 10]);
+
+		mesh.bind();
+		offsets.bind(&mesh);
+		mesh.draw_instanced(gl::TRIANGLES, 10);
+		assert!(headless::with_state(|s| s.enabled_attribs.contains(&1)));
+
+		drop(offsets);
+		mesh.bind();
+		mesh.draw(gl::TRIANGLES);
+
+		headless::with_state(|s| {
+			assert_eq!(s.vertex_array_binding, mesh.vao);
+			assert!(!s.enabled_attribs.contains(&1));
+			assert!(s.enabled_attribs.contains(&0));
+		});
+	}
+
+	#[test]
+	fn vertex_array_objects() {
+		use webgl::WebGLContext;
+		use extensions::OesVertexArrayObject;
+
+		headless::reset();
+
+		let ctx = WebGLContext::new(false);
+		ctx.enable_extension::<OesVertexArrayObject>().unwrap();
+
+		let mut mb = MeshBuilder::new();
+		mb.add_tri_fan(&[DefaultVertex::new(Vec3::zero()); 3]);
+		let mesh: Mesh = mb.into();
+		assert!(mesh.vao != 0);
+
+		// The attribute setup was recorded at upload, so binding is a single call
+		headless::take_calls();
+		mesh.bind();
+		mesh.draw(gl::TRIANGLES);
+
+		let names: Vec<_> = headless::take_calls().iter().map(|c| c.name).collect();
+		assert_eq!(names, ["BindVertexArray", "DrawElements"]);
+
+		headless::with_state(|s| {
+			assert_eq!(s.element_array_buffer_binding, mesh.ebo);
+			assert!(s.enabled_attribs.contains(&0));
+
+			// Nothing leaked into the default VAO
+			assert!(s.vertex_arrays[&0].enabled_attribs.is_empty());
+			assert_eq!(s.vertex_arrays[&0].element_array_buffer_binding, 0);
+		});
+
+		let vao = mesh.vao;
+		drop(mesh);
+		assert!(headless::with_state(|s| !s.vertex_arrays.contains_key(&vao)));
+
+		let _webgl2 = WebGLContext::new_webgl2(false);
+		assert!(Mesh::new().vao != 0);
+	}
+
+	#[test]
+	fn meshes_without_vaos_leave_others_alone() {
+		use webgl::WebGLContext;
+		use extensions::OesVertexArrayObject;
+
+		headless::reset();
+
+		// Made before VAOs were available, so it never gets one
+		let ctx = WebGLContext::new(false);
+		let mut plain_mb = MeshBuilder::new();
+		plain_mb.add_tri_fan(&[LitVertex::new(Vec3::zero()); 3]);
+		let mut plain = Mesh::new();
+		plain_mb.upload_to(&mut plain);
+		assert_eq!(plain.vao, 0);
+
+		ctx.enable_extension::<OesVertexArrayObject>().unwrap();
+
+		let mut mb = MeshBuilder::new();
+		mb.add_tri_fan(&[DefaultVertex::new(Vec3::zero()); 3]);
+		let mut with_vao: Mesh = mb.into();
+		let vao = with_vao.vao;
+		assert!(vao != 0);
+
+		with_vao.bind();
+		plain.bind();
+		plain.draw(gl::TRIANGLES);
+		plain.update_indices(0, &[2u16, 1, 0]);
+		plain.update_vertices(0, &[LitVertex::new(Vec3::zero())]);
+		plain_mb.upload_to(&mut plain);
+
+		headless::with_state(|s| {
+			assert_eq!(s.vertex_array_binding, 0);
+			assert_eq!(s.vertex_arrays[&vao].element_array_buffer_binding, with_vao.ebo);
+			assert_eq!(s.vertex_arrays[&vao].enabled_attribs.len(), 1);
+		});
+
+		// Uploading to the VAO mesh doesn't leave its VAO bound for the plain one to change
+		let mut mb = MeshBuilder::new();
+		mb.add_quad(&[DefaultVertex::new(Vec3::zero()); 4]);
+		mb.upload_to(&mut with_vao);
+		plain.bind();
+
+		headless::with_state(|s| {
+			assert_eq!(s.vertex_arrays[&vao].element_array_buffer_binding, with_vao.ebo);
+			assert_eq!(s.vertex_arrays[&vao].enabled_attribs.len(), 1);
+		});
+	}
+
+	#[test]
+	fn stale_attributes_disabled() {
+		headless::reset();
+
+		let _ctx = ::webgl::WebGLContext::new(false);
+
+		let mut mb = MeshBuilder::new();
+		mb.add_tri_fan(&[LitVertex::new(Vec3::zero()); 3]);
+		let colored: Mesh = mb.into();
+
+		let mut mb = MeshBuilder::new();
+		mb.add_tri_fan(&[DefaultVertex::new(Vec3::zero()); 3]);
+		let plain: Mesh = mb.into();
+		assert_eq!(plain.vao, 0);
+
+		// Attributes left over from before are unknown, so they're all disabled
+		headless::with_state(|s| { s.enabled_attribs.insert(5); });
+		colored.bind();
+		assert_eq!(headless::calls_named("DisableVertexAttribArray").len(), 13);
+		headless::with_state(|s| assert_eq!(s.enabled_attribs.len(), 3));
+
+		headless::take_calls();
+		plain.bind();
+		let disabled = headless::calls_named("DisableVertexAttribArray");
+		assert_eq!(disabled.iter().map(|c| c.int(0)).collect::<Vec<_>>(), [1, 2]);
+		assert!(headless::calls_named("EnableVertexAttribArray").is_empty());
+
+		headless::take_calls();
+		plain.bind();
+		assert!(headless::calls_named("DisableVertexAttribArray").is_empty());
+		assert!(headless::calls_named("EnableVertexAttribArray").is_empty());
+	}
+
+	#[test]
+	fn add_direct_errors() {
+		let mut mb = MeshBuilder::new();
+		let vs = [DefaultVertex::new(Vec3::zero()); 3];
+
+		match mb.try_add_direct(&vs, &[0, 1]) {
+			Err(Error::NotEnoughElements { expected: 3, given: 2 }) => {}
+			x => panic!("Unexpected result {:?}", x),
+		}
+
+		match mb.try_add_direct(&vs, &[0, 1, 3]) {
+			Err(Error::IndexOverflow { index: 3, max: 2 }) => {}
+			x => panic!("Unexpected result {:?}", x),
+		}
+
+		assert_eq!(mb.get_vertex_count(), 0);
+	}
+
+	#[test]
+	fn batch_splitting() {
+		let mut mb = MeshBuilder::new();
+		let vs = [DefaultVertex::new(Vec3::zero()); 4];
+
+		// 16384 quads exactly fill one batch of u16 indices
+		for _ in 0..16384 { mb.add_quad(&vs) }
+		assert_eq!(mb.get_batch_count(), 1);
+
+		mb.add_tri_fan(&vs[..3]);
+		mb.add_direct(&vs[..3], &[2, 1, 0]);
+		assert_eq!(mb.get_batch_count(), 2);
+
+		assert_eq!(mb.batches[1], MeshBatch { base_vertex: 65536, first_index: 16384*6, count: 6 });
+		assert_eq!(&mb.indices[16384*6..], &[0, 1, 2, 5, 4, 3]);
+
+		let mut mb32 = MeshBuilder::<_, u32>::with_index_type();
+		for _ in 0..16385 { mb32.add_quad(&vs) }
+		assert_eq!(mb32.get_batch_count(), 1);
+		assert_eq!(*mb32.indices.last().unwrap(), 65539);
+	}
+
+	#[test]
+	fn draw_batches() {
+		headless::reset();
+
+		let mut mb = MeshBuilder::new();
+		let vs = [DefaultVertex::new(Vec3::zero()); 4];
+		for _ in 0..20000 { mb.add_quad(&vs) }
+
+		let mesh: Mesh = mb.into();
+		mesh.bind();
+		headless::take_calls();
+
+		mesh.draw(gl::TRIANGLES);
+
+		let pointers = headless::calls_named("VertexAttribPointer");
+		assert_eq!(pointers.len(), 2);
+		assert_eq!(pointers[0].ptr(5), 65536 * 12);
+		assert_eq!(pointers[1].ptr(5), 0);
+
+		let draws = headless::calls_named("DrawElements");
+		assert_eq!(draws.len(), 2);
+		assert_eq!(draws[0].int(1), 16384 * 6);
+		assert_eq!(draws[1].int(1), (20000 - 16384) * 6);
+		assert_eq!(draws[1].ptr(3), 16384 * 6 * 2);
+	}
+
+	#[test]
+	fn draw_batches_with_vao() {
+		headless::reset();
+		let _ctx = ::WebGLContext::new_webgl2(false);
+
+		let mut mb = MeshBuilder::new();
+		let vs = [DefaultVertex::new(Vec3::zero()); 4];
+		for _ in 0..20000 { mb.add_quad(&vs) }
+
+		let mesh: Mesh = mb.into();
+		mesh.bind();
+		mesh.draw(gl::TRIANGLES);
+
+		let pointers = headless::calls_named("VertexAttribPointer");
+		assert_eq!(pointers.last().unwrap().ptr(5), 0);
+
+		// Binding again leaves the VAO as it is, so its pointers have to be back at the first batch
+		headless::take_calls();
+		mesh.bind();
+		mesh.draw_range(gl::TRIANGLES, 0, 6);
+		assert!(headless::calls_named("VertexAttribPointer").is_empty());
+		assert_eq!(headless::calls_named("DrawElements").len(), 1);
+	}
+
+	#[test]
+	fn submeshes_and_ranges() {
+		headless::reset();
+
+		let vs = [DefaultVertex::new(Vec3::zero()); 4];
+
+		let mut mb = MeshBuilder::new();
+		mb.add_quad(&vs);
+		assert_eq!(mb.begin_submesh(), 0);
+		mb.add_tri_fan(&vs);
+		assert_eq!(mb.begin_submesh(), 1);
+		mb.add_tri_fan(&vs[..3]);
+
+		let mesh: Mesh = mb.into();
+		assert_eq!(mesh.submeshes, [SubMesh { first: 6, count: 6 }, SubMesh { first: 12, count: 3 }]);
+
+		headless::take_calls();
+		mesh.draw_submesh(gl::TRIANGLES, 1);
+		mesh.draw_range(gl::TRIANGLES, 3, 6);
+
+		let draws = headless::calls_named("DrawElements");
+		assert_eq!((draws[0].int(1), draws[0].ptr(3)), (3, 12 * 2));
+		assert_eq!((draws[1].int(1), draws[1].ptr(3)), (6, 3 * 2));
+
+		// Ranges spanning batches are split between them
+		let mut mb = MeshBuilder::new();
+		for _ in 0..16385 { mb.add_quad(&vs) }
+		let batched: Mesh = mb.into();
+
+		headless::take_calls();
+		batched.draw_range(gl::TRIANGLES, 16383 * 6, 12);
+
+		let draws = headless::calls_named("DrawElements");
+		assert_eq!((draws[0].int(1), draws[0].ptr(3)), (6, 16383 * 6 * 2));
+		assert_eq!((draws[1].int(1), draws[1].ptr(3)), (6, 16384 * 6 * 2));
+		let pointers = headless::calls_named("VertexAttribPointer");
+		assert_eq!((pointers[0].ptr(5), pointers[1].ptr(5)), (65536 * 12, 0));
+
+		// Unindexed meshes have their vertices duplicated to match the indices
+		let mut mb = MeshBuilder::new_unindexed();
+		mb.add_quad(&[
+			DefaultVertex::new(Vec3::new(0.0, 0.0, 0.0)),
+			DefaultVertex::new(Vec3::new(1.0, 0.0, 0.0)),
+			DefaultVertex::new(Vec3::new(1.0, 1.0, 0.0)),
+			DefaultVertex::new(Vec3::new(0.0, 1.0, 0.0)),
+		]);
+		mb.begin_submesh();
+		mb.add_tri_fan(&vs[..3]);
+
+		let unindexed: Mesh = mb.into();
+		assert!(!unindexed.indexed);
+		assert_eq!(unindexed.vertex_count(), 9);
+		assert_eq!(unindexed.submeshes, [SubMesh { first: 6, count: 3 }]);
+
+		headless::with_state(|s| {
+			let data = &s.buffers[&unindexed.vbo].data;
+			assert_eq!(data.len(), 9 * 12);
+			assert_eq!(&data[3*12 .. 3*12 + 4], &0.0f32.to_bits().to_le_bytes());
+			assert_eq!(&data[4*12 .. 4*12 + 4], &1.0f32.to_bits().to_le_bytes());
+		});
+
+		headless::take_calls();
+		unindexed.draw(gl::TRIANGLES);
+		unindexed.draw_submesh(gl::TRIANGLES, 0);
+
+		let draws = headless::calls_named("DrawArrays");
+		assert_eq!((draws[0].int(1), draws[0].int(2)), (0, 9));
+		assert_eq!((draws[1].int(1), draws[1].int(2)), (6, 3));
+		assert!(headless::calls_named("DrawElements").is_empty());
+	}
+
+	#[test]
+	fn add_vert_across_batches() {
+		let mut mb = MeshBuilder::new();
+		let vs = [DefaultVertex::new(Vec3::zero()); 4];
+
+		// Leaves room for one more triangle in the first batch
+		for _ in 0..16383 { mb.add_quad(&vs) }
+		for _ in 0..6 { mb.add_vert(vs[0]) }
+
+		assert_eq!(mb.get_batch_count(), 2);
+		assert_eq!(mb.batches[0].count, 16383 * 6 + 3);
+		assert_eq!(mb.batches[1], MeshBatch { base_vertex: 65535, first_index: 16383 * 6 + 3, count: 3 });
+		assert_eq!(&mb.indices[16383 * 6 ..], &[65532, 65533, 65534, 0, 1, 2]);
+
+		// Vertices are added as they come, even before they make a triangle
+		mb.add_vert(vs[0]);
+		assert_eq!((mb.get_vertex_count(), mb.get_index_count()), (65539, 16383 * 6 + 7));
+
+		// Another primitive starts a new triangle
+		mb.add_quad(&vs);
+		mb.add_vert(vs[0]);
+		assert_eq!(&mb.indices[16383 * 6 + 6 ..], &[3, 4, 5, 6, 4, 6, 7, 8]);
+	}
+
+	#[test]
+	fn u32_indices_need_extension() {
+		headless::reset();
+
+		let mut mb = MeshBuilder::<_, u32>::with_index_type();
+		mb.add_tri_fan(&[DefaultVertex::new(Vec3::zero()); 3]);
+
+		let mut mesh = Mesh::new();
+		match mb.try_upload_to(&mut mesh) {
+			Err(Error::MissingExtension("OES_element_index_uint")) => {}
+			x => panic!("Unexpected result {:?}", x),
+		}
+
+		// Only enabled up front along with every other extension
+		let ctx = ::WebGLContext::new(false);
+		assert!(mb.try_upload_to(&mut mesh).is_err());
+
+		ctx.enable_extension::<::extensions::OesElementIndexUint>().unwrap();
+		mb.upload_to(&mut mesh);
+		mesh.draw(gl::TRIANGLES);
+
+		let draws = headless::calls_named("DrawElements");
+		assert_eq!(draws[0].int(2), gl::UNSIGNED_INT as i64);
+	}
+}
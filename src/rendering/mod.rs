@@ -15,13 +15,26 @@ pub mod gl {
 
 pub mod types;
 pub mod shader;
+pub mod snippets;
+pub mod glsl;
+pub mod uniform;
+pub mod material;
+pub mod hot_reload;
 pub mod texture;
 pub mod framebuffer;
+pub mod resources;
 
 pub mod mesh;
+pub mod primitives;
 
 pub use self::mesh::*;
+pub use self::primitives::*;
 pub use self::types::*;
 pub use self::shader::*;
+pub use self::snippets::*;
+pub use self::uniform::*;
+pub use self::material::*;
+pub use self::hot_reload::*;
 pub use self::texture::*;
-pub use self::framebuffer::*;
\ No newline at end of file
+pub use self::framebuffer::*;
+pub use self::resources::*;
\ No newline at end of file
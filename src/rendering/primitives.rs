@@ -0,0 +1,536 @@
+// Shape generators for MeshBuilder. Shapes are centered on the origin with +Y up, and wound
+// counter-clockwise when seen from outside. Each takes a closure that makes a vertex from a
+// SurfacePoint, so vertex types can take as much of it as they need.
+//
+// A batch only holds 65536 vertices with u16 indices. Shapes built from grids are added a band
+// of rows at a time so they can span several batches, but a single row of more than 32767
+// segments can't fit. Icospheres are added whole, so past 6 subdivisions they need u32 indices.
+// The try_ variants return Error::IndexOverflow, or Error::InvalidArgument for too few segments,
+// where the add_ ones would panic.
+
+use std::collections::HashMap;
+
+use rendering::mesh::{MeshBuilder, MeshIndex, Vertex};
+use error::Error;
+use common::math::*;
+
+/// A point on a generated shape's surface
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SurfacePoint {
+	pub pos: Vec3,
+	pub normal: Vec3,
+	/// Spans [0, 1] on each axis over a face, or over the whole shape if it's curved
+	pub uv: Vec2,
+	/// Points towards increasing u
+	pub tangent: Vec3,
+}
+
+impl<V, I> MeshBuilder<V, I> where V: Vertex, I: MeshIndex {
+	/// A `size.x` by `size.y` plane in XZ facing +Y, split into `subdivisions` squared quads
+	pub fn add_plane<F>(&mut self, size: Vec2, subdivisions: u32, vertex: F) where F: Fn(SurfacePoint) -> V {
+		self.try_add_plane(size, subdivisions, vertex)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_add_plane<F>(&mut self, size: Vec2, subdivisions: u32, vertex: F) -> Result<(), Error> where F: Fn(SurfacePoint) -> V {
+		self.add_face(Vec3::zero(), Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0), size, subdivisions, &vertex)
+	}
+
+	/// A box with a separate set of vertices for each face, so normals and UVs aren't shared
+	pub fn add_cube<F>(&mut self, size: Vec3, vertex: F) where F: Fn(SurfacePoint) -> V {
+		self.try_add_cube(size, vertex)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_add_cube<F>(&mut self, size: Vec3, vertex: F) -> Result<(), Error> where F: Fn(SurfacePoint) -> V {
+		let faces = [
+			(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)),
+			(Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+			(Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)),
+			(Vec3::new(0.0, -1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)),
+			(Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 0.0)),
+			(Vec3::new(0.0, 0.0, -1.0), Vec3::new(-1.0, 0.0, 0.0)),
+		];
+
+		for &(normal, tangent) in faces.iter() {
+			let bitangent = normal.cross(tangent);
+			let face_size = Vec2::new(size.dot(tangent).abs(), size.dot(bitangent).abs());
+			let center = normal * (size.dot(normal).abs() / 2.0);
+
+			self.add_face(center, normal, tangent, face_size, 1, &vertex)?;
+		}
+
+		Ok(())
+	}
+
+	/// A sphere made of `segments` slices around Y and `rings` bands from pole to pole
+	pub fn add_uv_sphere<F>(&mut self, radius: f32, segments: u32, rings: u32, vertex: F) where F: Fn(SurfacePoint) -> V {
+		self.try_add_uv_sphere(radius, segments, rings, vertex)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_add_uv_sphere<F>(&mut self, radius: f32, segments: u32, rings: u32, vertex: F) -> Result<(), Error> where F: Fn(SurfacePoint) -> V {
+		at_least("segments", segments, 3)?;
+		at_least("rings", rings, 2)?;
+
+		self.add_grid(segments, rings, &vertex, |i, j| {
+			let u = i as f32 / segments as f32;
+			let v = j as f32 / rings as f32;
+			let normal = sphere_normal(u, v * PI - PI / 2.0);
+
+			SurfacePoint {
+				pos: normal * radius,
+				normal,
+				uv: Vec2::new(u, v),
+				tangent: around_y(u),
+			}
+		})
+	}
+
+	/// A sphere made by splitting each triangle of an icosahedron into four `subdivisions` times.
+	/// UVs are mapped like add_uv_sphere's, but vertices aren't duplicated along u = 0, so the
+	/// triangles that cross it stretch over the whole texture
+	pub fn add_icosphere<F>(&mut self, radius: f32, subdivisions: u32, vertex: F) where F: Fn(SurfacePoint) -> V {
+		self.try_add_icosphere(radius, subdivisions, vertex)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_add_icosphere<F>(&mut self, radius: f32, subdivisions: u32, vertex: F) -> Result<(), Error> where F: Fn(SurfacePoint) -> V {
+		let (points, triangles) = icosphere(subdivisions);
+
+		let verts: Vec<V> = points.iter()
+			.map(|&normal| {
+				let u = (-normal.z).atan2(normal.x) / (2.0 * PI);
+				let u = if u < 0.0 { u + 1.0 } else { u };
+				let v = normal.y.max(-1.0).min(1.0).asin() / PI + 0.5;
+
+				vertex(SurfacePoint {
+					pos: normal * radius,
+					normal,
+					uv: Vec2::new(u, v),
+					tangent: around_y(u),
+				})
+			})
+			.collect();
+
+		let indices: Vec<I> = triangles.iter()
+			.flat_map(|t| t.iter().cloned())
+			.map(I::from_usize)
+			.collect();
+
+		self.try_add_direct(&verts, &indices)
+	}
+
+	/// A capped cylinder `height` tall, with `segments` sides
+	pub fn add_cylinder<F>(&mut self, radius: f32, height: f32, segments: u32, vertex: F) where F: Fn(SurfacePoint) -> V {
+		self.try_add_cylinder(radius, height, segments, vertex)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_add_cylinder<F>(&mut self, radius: f32, height: f32, segments: u32, vertex: F) -> Result<(), Error> where F: Fn(SurfacePoint) -> V {
+		at_least("segments", segments, 3)?;
+
+		self.add_grid(segments, 1, &vertex, |i, j| {
+			let u = i as f32 / segments as f32;
+			let normal = radial(u);
+
+			SurfacePoint {
+				pos: normal * radius + Vec3::new(0.0, (j as f32 - 0.5) * height, 0.0),
+				normal,
+				uv: Vec2::new(u, j as f32),
+				tangent: around_y(u),
+			}
+		})?;
+
+		self.add_disc(Vec3::new(0.0, height / 2.0, 0.0), Vec3::new(0.0, 1.0, 0.0), radius, segments, &vertex)?;
+		self.add_disc(Vec3::new(0.0, -height / 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0), radius, segments, &vertex)
+	}
+
+	/// A capped cone `height` tall, with its point at the top
+	pub fn add_cone<F>(&mut self, radius: f32, height: f32, segments: u32, vertex: F) where F: Fn(SurfacePoint) -> V {
+		self.try_add_cone(radius, height, segments, vertex)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_add_cone<F>(&mut self, radius: f32, height: f32, segments: u32, vertex: F) -> Result<(), Error> where F: Fn(SurfacePoint) -> V {
+		at_least("segments", segments, 3)?;
+
+		// The apex gets a vertex per segment, so each side keeps its own normal
+		self.add_grid(segments, 1, &vertex, |i, j| {
+			let u = i as f32 / segments as f32;
+			let outward = radial(u);
+			let normal = (outward * height + Vec3::new(0.0, radius, 0.0)).normalize();
+
+			SurfacePoint {
+				pos: outward * (radius * (1 - j) as f32) + Vec3::new(0.0, (j as f32 - 0.5) * height, 0.0),
+				normal,
+				uv: Vec2::new(u, j as f32),
+				tangent: around_y(u),
+			}
+		})?;
+
+		self.add_disc(Vec3::new(0.0, -height / 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0), radius, segments, &vertex)
+	}
+
+	/// A ring in XZ, `radius` from the center to the middle of a tube `tube_radius` thick.
+	/// `segments` go around the ring and `sides` around the tube
+	pub fn add_torus<F>(&mut self, radius: f32, tube_radius: f32, segments: u32, sides: u32, vertex: F) where F: Fn(SurfacePoint) -> V {
+		self.try_add_torus(radius, tube_radius, segments, sides, vertex)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_add_torus<F>(&mut self, radius: f32, tube_radius: f32, segments: u32, sides: u32, vertex: F) -> Result<(), Error> where F: Fn(SurfacePoint) -> V {
+		at_least("segments", segments, 3)?;
+		at_least("sides", sides, 3)?;
+
+		self.add_grid(segments, sides, &vertex, |i, j| {
+			let u = i as f32 / segments as f32;
+			let v = j as f32 / sides as f32;
+
+			let outward = radial(u);
+			let angle = v * 2.0 * PI;
+			let normal = outward * angle.cos() + Vec3::new(0.0, angle.sin(), 0.0);
+
+			SurfacePoint {
+				pos: outward * radius + normal * tube_radius,
+				normal,
+				uv: Vec2::new(u, v),
+				tangent: around_y(u),
+			}
+		})
+	}
+
+	/// A cylinder with hemispheres for caps. `height` is the length of the cylindrical part,
+	/// and `rings` the number of bands in each hemisphere
+	pub fn add_capsule<F>(&mut self, radius: f32, height: f32, segments: u32, rings: u32, vertex: F) where F: Fn(SurfacePoint) -> V {
+		self.try_add_capsule(radius, height, segments, rings, vertex)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_add_capsule<F>(&mut self, radius: f32, height: f32, segments: u32, rings: u32, vertex: F) -> Result<(), Error> where F: Fn(SurfacePoint) -> V {
+		at_least("segments", segments, 3)?;
+		at_least("rings", rings, 1)?;
+
+		// Two hemispheres with the band between them forming the cylinder
+		let rows = rings * 2 + 1;
+
+		self.add_grid(segments, rows, &vertex, |i, j| {
+			let u = i as f32 / segments as f32;
+
+			let (ring, offset) = if j <= rings { (j, -height / 2.0) } else { (j - 1, height / 2.0) };
+			let latitude = ring as f32 / rings as f32 * PI / 2.0 - PI / 2.0;
+			let normal = sphere_normal(u, latitude);
+
+			SurfacePoint {
+				pos: normal * radius + Vec3::new(0.0, offset, 0.0),
+				normal,
+				uv: Vec2::new(u, j as f32 / rows as f32),
+				tangent: around_y(u),
+			}
+		})
+	}
+
+	// A flat `subdivisions` squared grid spanning `size` along `tangent` and normal × tangent
+	fn add_face<F>(&mut self, center: Vec3, normal: Vec3, tangent: Vec3, size: Vec2, subdivisions: u32, vertex: &F)
+		-> Result<(), Error> where F: Fn(SurfacePoint) -> V {
+
+		let bitangent = normal.cross(tangent);
+		let n = subdivisions.max(1);
+
+		self.add_grid(n, n, vertex, |i, j| {
+			let uv = Vec2::new(i as f32 / n as f32, j as f32 / n as f32);
+
+			SurfacePoint {
+				pos: center + tangent * ((uv.x - 0.5) * size.x) + bitangent * ((uv.y - 0.5) * size.y),
+				normal,
+				uv,
+				tangent,
+			}
+		})
+	}
+
+	// A triangle fan around `center`, facing `normal`
+	fn add_disc<F>(&mut self, center: Vec3, normal: Vec3, radius: f32, segments: u32, vertex: &F)
+		-> Result<(), Error> where F: Fn(SurfacePoint) -> V {
+
+		// +X projected onto the disc, unless that's too close to the normal to be usable
+		let reference = if normal.x.abs() < 0.9 { Vec3::new(1.0, 0.0, 0.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+		let tangent = (reference - normal * normal.dot(reference)).normalize();
+		let bitangent = normal.cross(tangent);
+
+		let point = |dir: Vec2| SurfacePoint {
+			pos: center + (tangent * dir.x + bitangent * dir.y) * radius,
+			normal,
+			uv: Vec2::new(0.5, 0.5) + dir * 0.5,
+			tangent,
+		};
+
+		let verts: Vec<V> = ::std::iter::once(point(Vec2::zero()))
+			.chain((0..segments + 1).map(|i| point(Vec2::from_angle(i as f32 / segments as f32 * 2.0 * PI))))
+			.map(|p| vertex(p))
+			.collect();
+
+		let indices: Vec<I> = (1..segments as usize + 1)
+			.flat_map(|i| vec![0, i, i + 1])
+			.map(I::from_usize)
+			.collect();
+
+		self.try_add_direct(&verts, &indices)
+	}
+
+	// (cols + 1) by (rows + 1) vertices from `point`, joined into quads. The quad at (i, j) is
+	// wound counter-clockwise around the direction of ∂pos/∂i × ∂pos/∂j
+	fn add_grid<F, P>(&mut self, cols: u32, rows: u32, vertex: &F, point: P) -> Result<(), Error>
+		where F: Fn(SurfacePoint) -> V, P: Fn(u32, u32) -> SurfacePoint {
+
+		let stride = cols as usize + 1;
+
+		// Added as bands of as many rows as fit in a batch, each repeating the last row of the one before
+		let band_rows = (I::MAX_VERTICES / stride).saturating_sub(1) as u32;
+		if band_rows == 0 {
+			return Err(Error::IndexOverflow { index: 2 * stride - 1, max: I::MAX_VERTICES - 1 });
+		}
+
+		let mut first_row = 0;
+		while first_row < rows {
+			let band = band_rows.min(rows - first_row);
+
+			let verts: Vec<V> = (first_row..first_row + band + 1)
+				.flat_map(|j| (0..cols + 1).map(move |i| (i, j)))
+				.map(|(i, j)| vertex(point(i, j)))
+				.collect();
+
+			let mut indices = Vec::with_capacity(cols as usize * band as usize * 6);
+
+			for j in 0..band as usize {
+				for i in 0..cols as usize {
+					let k = j * stride + i;
+
+					for &e in [k, k + 1, k + stride + 1, k, k + stride + 1, k + stride].iter() {
+						indices.push(I::from_usize(e));
+					}
+				}
+			}
+
+			self.try_add_direct(&verts, &indices)?;
+			first_row += band;
+		}
+
+		Ok(())
+	}
+}
+
+// Horizontal direction `u` turns around Y, starting at +X and going counter-clockwise seen from above
+fn radial(u: f32) -> Vec3 {
+	let angle = u * 2.0 * PI;
+	Vec3::new(angle.cos(), 0.0, -angle.sin())
+}
+
+// The direction of increasing u around Y
+fn around_y(u: f32) -> Vec3 {
+	let angle = u * 2.0 * PI;
+	Vec3::new(-angle.sin(), 0.0, -angle.cos())
+}
+
+fn sphere_normal(u: f32, latitude: f32) -> Vec3 {
+	radial(u) * latitude.cos() + Vec3::new(0.0, latitude.sin(), 0.0)
+}
+
+fn at_least(name: &str, value: u32, min: u32) -> Result<(), Error> {
+	if value < min {
+		return Err(Error::InvalidArgument(format!("{} must be at least {}, got {}", name, min, value)));
+	}
+
+	Ok(())
+}
+
+// Unit sphere points and outward facing triangles
+fn icosphere(subdivisions: u32) -> (Vec<Vec3>, Vec<[usize; 3]>) {
+	let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+
+	let mut points: Vec<Vec3> = [
+		(-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+		(0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+		(t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+	].iter().map(|&(x, y, z)| Vec3::new(x, y, z).normalize()).collect();
+
+	let mut triangles = vec![
+		[0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+		[1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+		[3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+		[4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+	];
+
+	for _ in 0..subdivisions {
+		// Shared so neighbouring triangles use the same midpoint vertices
+		let mut midpoints = HashMap::new();
+
+		let mut midpoint = |a: usize, b: usize| {
+			*midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+				points.push(((points[a] + points[b]) / 2.0).normalize());
+				points.len() - 1
+			})
+		};
+
+		triangles = triangles.iter()
+			.flat_map(|&[a, b, c]| {
+				let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+				vec![[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+			})
+			.collect();
+	}
+
+	// Winding follows from the triangle list above, but is checked rather than trusted
+	for tri in triangles.iter_mut() {
+		let [a, b, c] = *tri;
+		if (points[b] - points[a]).cross(points[c] - points[a]).dot(points[a]) < 0.0 {
+			tri.swap(1, 2);
+		}
+	}
+
+	(points, triangles)
+}
+
+#[cfg(all(test, feature = "headless"))] mod tests {
+	use bindings::headless;
+	use rendering::mesh::*;
+	use rendering::mesh::tests::{LitVertex, lit};
+	use std::mem::size_of;
+	use std::ptr;
+	use super::*;
+
+	// Built unindexed, so every three vertices uploaded are a triangle
+	fn triangles<F>(build: F) -> Vec<[LitVertex; 3]> where F: Fn(&mut MeshBuilder<LitVertex>) {
+		let mut mb = MeshBuilder::new_unindexed();
+		build(&mut mb);
+
+		let mesh: Mesh = mb.into();
+		let data = headless::with_state(|s| s.buffers[&mesh.vbo].data.clone());
+
+		let verts: Vec<LitVertex> = data.chunks(size_of::<LitVertex>())
+			.map(|v| unsafe { ptr::read_unaligned(v.as_ptr() as *const LitVertex) })
+			.collect();
+
+		verts.chunks(3).map(|t| [t[0], t[1], t[2]]).collect()
+	}
+
+	// Every triangle faces the same way as its vertex normals
+	fn check_winding(tris: &[[LitVertex; 3]]) {
+		for t in tris.iter() {
+			let face = (t[1].pos - t[0].pos).cross(t[2].pos - t[0].pos);
+
+			// Skips the degenerate triangles at poles and apexes
+			if face.length() < 1e-6 { continue }
+
+			let normal = t[0].normal + t[1].normal + t[2].normal;
+			assert!(face.dot(normal) > 0.0, "Triangle {:?} faces inward", t[0].pos);
+		}
+	}
+
+	#[test]
+	fn shapes() {
+		headless::reset();
+
+		let cube = triangles(|mb| mb.add_cube(Vec3::new(1.0, 2.0, 3.0), lit));
+		assert_eq!(cube.len(), 12);
+		assert!(cube.iter().flat_map(|t| t.iter()).all(|v| {
+			let p = v.pos;
+			p.x.abs() == 0.5 && v.normal.x != 0.0 || p.y.abs() == 1.0 && v.normal.y != 0.0 || p.z.abs() == 1.5 && v.normal.z != 0.0
+		}));
+		check_winding(&cube);
+
+		let plane = triangles(|mb| mb.add_plane(Vec2::new(4.0, 4.0), 2, lit));
+		assert_eq!(plane.len(), 8);
+		check_winding(&plane);
+
+		let mut mb = MeshBuilder::<LitVertex>::new();
+		mb.add_uv_sphere(2.0, 16, 8, lit);
+		assert_eq!((mb.get_vertex_count(), mb.get_index_count()), (17 * 9, 16 * 8 * 6));
+
+		let sphere = triangles(|mb| mb.add_uv_sphere(2.0, 16, 8, lit));
+		assert!(sphere.iter().flat_map(|t| t.iter()).all(|v| (v.pos.length() - 2.0).abs() < 1e-4));
+		check_winding(&sphere);
+
+		mb.clear();
+		mb.add_icosphere(1.0, 2, lit);
+		assert_eq!((mb.get_vertex_count(), mb.get_index_count()), (162, 320 * 3));
+
+		let icosphere = triangles(|mb| mb.add_icosphere(1.0, 2, lit));
+		assert!(icosphere.iter().flat_map(|t| t.iter()).all(|v| v.uv.x >= 0.0 && v.uv.x < 1.0));
+		check_winding(&icosphere);
+
+		check_winding(&triangles(|mb| mb.add_cylinder(1.0, 2.0, 12, lit)));
+		check_winding(&triangles(|mb| mb.add_cone(1.0, 2.0, 12, lit)));
+		check_winding(&triangles(|mb| mb.add_torus(2.0, 0.5, 24, 12, lit)));
+
+		// Capsules are `height` plus a radius on each end
+		let capsule = triangles(|mb| mb.add_capsule(0.5, 1.0, 12, 4, lit));
+		let ys: Vec<f32> = capsule.iter().flat_map(|t| t.iter()).map(|v| v.pos.y).collect();
+		assert!(ys.iter().all(|y| y.abs() <= 1.0 + 1e-5));
+		assert!(ys.iter().any(|&y| y == -1.0) && ys.iter().any(|&y| (y - 1.0).abs() < 1e-5));
+		check_winding(&capsule);
+
+		// Discs facing along X still get a usable tangent
+		for &normal in [Vec3::new(1.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0)].iter() {
+			let disc = triangles(|mb| mb.add_disc(Vec3::zero(), normal, 1.0, 8, &|p: SurfacePoint| {
+				assert!(p.tangent.dot(normal).abs() < 1e-5 && (p.tangent.length() - 1.0).abs() < 1e-5);
+				lit(p)
+			}).unwrap());
+
+			assert!(disc.iter().all(|t| (t[1].pos - t[0].pos).cross(t[2].pos - t[0].pos).length() > 1e-3));
+			check_winding(&disc);
+		}
+	}
+
+	#[test]
+	fn invalid_arguments() {
+		let mut mb = MeshBuilder::<LitVertex>::new();
+
+		match mb.try_add_uv_sphere(1.0, 2, 8, lit) {
+			Err(Error::InvalidArgument(ref msg)) if msg == "segments must be at least 3, got 2" => {}
+			x => panic!("Unexpected result {:?}", x.map(|_| ())),
+		}
+
+		assert!(mb.try_add_uv_sphere(1.0, 3, 1, lit).is_err());
+		assert!(mb.try_add_cylinder(1.0, 1.0, 0, lit).is_err());
+		assert!(mb.try_add_cone(1.0, 1.0, 2, lit).is_err());
+		assert!(mb.try_add_torus(1.0, 0.5, 3, 2, lit).is_err());
+		assert!(mb.try_add_capsule(1.0, 1.0, 3, 0, lit).is_err());
+		assert_eq!(mb.get_vertex_count(), 0);
+	}
+
+	#[test]
+	fn batch_limits() {
+		headless::reset();
+
+		// 256 rows of 256 vertices fill a batch exactly
+		let mut mb = MeshBuilder::<LitVertex>::new();
+		mb.add_uv_sphere(1.0, 255, 255, lit);
+		assert_eq!((mb.get_vertex_count(), mb.get_batch_count()), (1 << 16, 1));
+
+		// One more ring goes into a second band, which repeats the row it starts from
+		mb.clear();
+		mb.add_uv_sphere(1.0, 255, 256, lit);
+		assert_eq!((mb.get_vertex_count(), mb.get_batch_count()), (258 * 256, 2));
+		assert_eq!(mb.get_index_count(), 255 * 256 * 6);
+
+		// Each row of quads still has to fit in a batch on its own
+		mb.clear();
+		assert!(mb.try_add_torus(2.0, 0.5, 32767, 3, lit).is_ok());
+		match mb.try_add_torus(2.0, 0.5, 32768, 3, lit) {
+			Err(Error::IndexOverflow { .. }) => {}
+			_ => panic!("Expected a row of 32768 quads to overflow"),
+		}
+
+		mb.clear();
+		assert!(mb.try_add_icosphere(1.0, 6, lit).is_ok());
+		match mb.try_add_icosphere(1.0, 7, lit) {
+			Err(Error::IndexOverflow { .. }) => {}
+			_ => panic!("Expected 163842 vertices to overflow"),
+		}
+
+		let mut mb = MeshBuilder::<LitVertex, u32>::with_index_type();
+		mb.add_icosphere(1.0, 7, lit);
+		assert_eq!((mb.get_vertex_count(), mb.get_batch_count()), (163842, 1));
+	}
+}
@@ -0,0 +1,160 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use error::Error;
+
+/// Something that owns GL objects and keeps enough on the CPU side to recreate them.
+/// Called with the restored context current - the old handles are already gone, so
+/// nothing should be deleted
+pub trait Restore {
+	fn restore(&mut self) -> Result<(), Error>;
+}
+
+/// Keeps track of the resources that should be recreated after a context loss. Resources
+/// are held weakly, so dropping the last Rc is enough to stop tracking one
+pub struct ResourceRegistry {
+	resources: RefCell<Vec<Weak<RefCell<dyn Restore>>>>,
+}
+
+impl ResourceRegistry {
+	pub fn new() -> Self {
+		ResourceRegistry { resources: RefCell::new(Vec::new()) }
+	}
+
+	pub fn register<T>(&self, resource: &Rc<RefCell<T>>) where T: Restore + 'static {
+		let resource: Rc<RefCell<dyn Restore>> = resource.clone();
+		self.resources.borrow_mut().push(Rc::downgrade(&resource));
+	}
+
+	/// Wraps `resource` up and registers it
+	pub fn track<T>(&self, resource: T) -> Rc<RefCell<T>> where T: Restore + 'static {
+		let resource = Rc::new(RefCell::new(resource));
+		self.register(&resource);
+		resource
+	}
+
+	/// The number of registered resources that are still alive
+	pub fn len(&self) -> usize {
+		self.resources.borrow().iter()
+			.filter(|r| r.upgrade().is_some())
+			.count()
+	}
+
+	/// Restores every live resource in the order they were registered, so resources that
+	/// depend on others should be registered after them. Stops at the first failure
+	pub fn restore_all(&self) -> Result<(), Error> {
+		let live: Vec<_> = {
+			let mut resources = self.resources.borrow_mut();
+			resources.retain(|r| r.upgrade().is_some());
+			resources.iter().filter_map(|r| r.upgrade()).collect()
+		};
+
+		for resource in live {
+			resource.borrow_mut().restore()?;
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(all(test, feature = "headless"))] mod tests {
+	use bindings::headless;
+	use rendering::*;
+	use events::Event;
+	use extensions::OesVertexArrayObject;
+	use webgl::WebGLContext;
+	use common::math::*;
+
+	#[test]
+	fn context_loss() {
+		headless::reset();
+
+		let ctx = WebGLContext::new(false);
+		ctx.enable_extension::<OesVertexArrayObject>().unwrap();
+
+		let registry = ResourceRegistry::new();
+
+		let mut mb = MeshBuilder::new();
+		mb.add_tri_fan(&[DefaultVertex::new(Vec3::zero()); 3]);
+		let mut mesh = Mesh::new();
+		mesh.retain_data(true);
+		mb.upload_to(&mut mesh);
+		let mesh = registry.track(mesh);
+
+		let mut mb = MeshBuilder::new();
+		mb.add_tri_fan(&[DefaultVertex::new(Vec3::zero()); 3]);
+		let unretained = registry.track(Mesh::from(mb));
+
+		let mut texture = Texture::new();
+		texture.retain_data(true);
+		texture.upload_1d(&[Color::white(); 4]);
+		let texture = registry.track(texture);
+		let shader = registry.track(Shader::new("attribute vec3 position; void main() {}", "uniform sampler2D u_tex; void main() {}").unwrap());
+		let mut material = Material::new().with("u_tex", texture.clone());
+		let framebuffer = registry.track(FramebufferBuilder::new(Vec2i::new(32, 32)).add_target().finalize());
+		drop(registry.track(Texture::new()));
+		assert_eq!(registry.len(), 5);
+
+		let old_vbo = mesh.borrow().vbo;
+		let old_texture = texture.borrow().gl_handle;
+
+		shader.borrow().use_program();
+		material.apply(&shader.borrow());
+		ctx.set_stencil_clear_value(0x80);
+
+		let mut events = Vec::new();
+		headless::lose_context(1);
+		ctx.poll_events(&mut events);
+		assert!(ctx.is_lost());
+		assert!(headless::with_state(|s| s.contexts[&1].enabled_extensions.is_empty()));
+
+		headless::restore_context(1);
+		ctx.poll_events(&mut events);
+		assert!(!ctx.is_lost());
+
+		match events.as_slice() {
+			[Event::ContextLost, Event::ContextRestored] => {}
+			_ => panic!("Expected loss and restore events"),
+		}
+
+		headless::take_calls();
+		ctx.restore(&registry).unwrap();
+		assert!(ctx.has_extension("OES_vertex_array_object"));
+		assert_eq!(headless::calls_named("ClearStencil")[0].int(0), 0x80);
+		assert!(ctx.capabilities().vertex_array_objects);
+
+		let mesh = mesh.borrow();
+		assert!(mesh.vbo != old_vbo);
+
+		// Meshes that didn't keep their data come back empty
+		let unretained = unretained.borrow();
+		assert_eq!((unretained.count, unretained.vertex_count()), (0, 0));
+
+		headless::with_state(|s| {
+			assert!(s.contexts[&1].enabled_extensions.contains("OES_vertex_array_object"));
+
+			assert_eq!(s.buffers[&mesh.vbo].data.len(), 3 * 12);
+			assert_eq!(s.buffers[&mesh.ebo].data, [0u8,0, 1,0, 2,0]);
+			assert!(s.buffers[&unretained.vbo].data.is_empty());
+			assert_eq!(s.textures[&texture.borrow().gl_handle].size, (4, 1));
+			assert!(s.programs[&shader.borrow().gl_handle].linked);
+
+			let target = framebuffer.borrow_mut().get_target(0).unwrap().gl_handle;
+			assert_eq!(s.textures[&target].size, (32, 32));
+			assert_eq!(s.framebuffers.len(), 1);
+			assert!(s.framebuffers.values().all(|f| f.attachments[&gl::COLOR_ATTACHMENT0] == target));
+		});
+
+		// Materials bind the restored texture, and upload everything to the restored program
+		let shader = shader.borrow();
+		assert!(shader.get_uniform_info("u_tex").is_some());
+		assert!(texture.borrow().gl_handle != old_texture);
+
+		shader.use_program();
+		headless::take_calls();
+		material.apply(&shader);
+
+		assert_eq!(headless::calls_named("Uniform1i").len(), 1);
+		assert_eq!(headless::with_state(|s| s.texture_bindings[&0]), texture.borrow().gl_handle);
+	}
+}
@@ -3,77 +3,217 @@
 use std;
 use math::*;
 use rendering::gl;
+use rendering::mesh::{Vertex, VertexLayout, VertexAttributeBinding, ShaderAttributes};
+use rendering::uniform::*;
+use rendering::snippets::resolve_includes_mapped;
+use rendering::glsl::*;
+use rendering::resources::Restore;
+use error::{Error, ShaderStage};
 
 use std::fmt::Write;
+use std::mem;
+use std::ops::Deref;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+thread_local! {
+	// Active uniforms of each linked program, keyed by program handle. Kept out of Shader so ShaderHandles can use it
+	static UNIFORM_CACHE: RefCell<HashMap<u32, HashMap<String, UniformInfo>>> = RefCell::new(HashMap::new());
+}
 
-#[derive(Copy, Clone, Debug)]
-pub struct Shader {
+/// Forgets the uniforms of every program. Handles are reused once a context is lost, so
+/// entries for the old programs would otherwise be taken for the restored ones'
+pub fn invalidate_uniform_cache() {
+	UNIFORM_CACHE.with(|c| c.borrow_mut().clear());
+}
+
+#[derive(Debug)]
+struct ProgramSources {
+	vertex: MappedSource,
+	fragment: MappedSource,
+	bindings: Vec<(u32, String)>,
+}
+
+/// A non-owning reference to a linked program, for where a program needs sharing. Cheap to copy,
+/// but left dangling once the Shader it came from is dropped
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShaderHandle {
 	pub gl_handle: u32,
 
 	pub proj_loc: i32,
 	pub view_loc: i32,
 }
 
+/// Owns a linked program, deleting it on drop. Everything that doesn't need ownership lives on
+/// ShaderHandle, which Shader derefs to
+#[derive(Debug)]
+pub struct Shader {
+	handle: ShaderHandle,
+	// What the program was linked from, so it can be relinked after a context loss
+	sources: Option<ProgramSources>,
+}
+
 impl Shader {
-	pub fn new(vertex_shader_src: &str, fragment_shader_src: &str) -> Result<Shader, String> {
-		use std::ffi::{CStr, CString};
+	pub fn new(vertex_shader_src: &str, fragment_shader_src: &str) -> Result<Shader, Error> {
+		Shader::link(&MappedSource::unmapped(vertex_shader_src), &MappedSource::unmapped(fragment_shader_src),
+			&[(0, "position".to_string())])
+	}
+
+	/// Binds each named attribute in `layout` to its binding index, and fails if the linked
+	/// program has attributes the layout can't feed
+	pub fn new_with_layout(vertex_shader_src: &str, fragment_shader_src: &str, layout: &VertexLayout) -> Result<Shader, Error> {
+		let shader = Shader::link(&MappedSource::unmapped(vertex_shader_src), &MappedSource::unmapped(fragment_shader_src),
+			&layout_bindings(layout))?;
+		shader.validate_or_delete(layout)
+	}
+
+	fn link(vertex_shader_src: &MappedSource, fragment_shader_src: &MappedSource, bindings: &[(u32, String)]) -> Result<Shader, Error> {
+		use std::ffi::CString;
+
+		let vertex_shader_src = resolve_includes_mapped(vertex_shader_src)?;
+		let fragment_shader_src = resolve_includes_mapped(fragment_shader_src)?;
+
 		unsafe {
 			let (vs,fs) = (gl::CreateShader(gl::VERTEX_SHADER), gl::CreateShader(gl::FRAGMENT_SHADER));
 			let program = gl::CreateProgram();
 
-			for &(sh, src) in [(vs, vertex_shader_src), (fs, fragment_shader_src)].iter() {
-				let src = CString::new(src).unwrap();
-				gl::ShaderSource(sh, 1, &src.as_ptr(), std::ptr::null());
+			let stages = [
+				(vs, &vertex_shader_src, ShaderStage::Vertex),
+				(fs, &fragment_shader_src, ShaderStage::Fragment),
+			];
+
+			for &(sh, src, stage) in stages.iter() {
+				let c_src = CString::new(src.source.as_str()).unwrap();
+				gl::ShaderSource(sh, 1, &c_src.as_ptr(), std::ptr::null());
 				gl::CompileShader(sh);
 
 				let mut status = 0i32;
 				gl::GetShaderiv(sh, gl::COMPILE_STATUS, &mut status);
 				if status == 0 {
-					let mut buf = [0u8; 1024];
-					let mut len = 0;
-					gl::GetShaderInfoLog(sh, buf.len() as _, &mut len, buf.as_mut_ptr() as _);
+					let log = shader_info_log(sh);
+
+					gl::DeleteShader(vs);
+					gl::DeleteShader(fs);
+					gl::DeleteProgram(program);
 
-					return Err(CStr::from_bytes_with_nul_unchecked(&buf[..len as usize]).to_string_lossy().into());
+					return Err(compile_error(stage, &log, src));
 				}
 				
 				gl::AttachShader(program, sh);
 			}
 
-			// TODO: Automate the everloving shit out of this please
-			gl::BindAttribLocation(program, 0, b"position\0".as_ptr() as _);
+			for &(idx, ref name) in bindings.iter() {
+				let name = CString::new(name.as_str()).unwrap();
+				gl::BindAttribLocation(program, idx, name.as_ptr());
+			}
 
 			gl::LinkProgram(program);
 
 			let mut status = 0i32;
 			gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
 			if status == 0 {
-				let mut buf = [0u8; 1024];
-				let mut len = 0;
-				gl::GetProgramInfoLog(program, buf.len() as _, &mut len, buf.as_mut_ptr() as _);
+				let log = program_info_log(program);
+
+				gl::DeleteShader(vs);
+				gl::DeleteShader(fs);
+				gl::DeleteProgram(program);
 
-				return Err(CStr::from_bytes_with_nul_unchecked(&buf[..len as usize]).to_string_lossy().into());
+				return Err(Error::ShaderLink { log });
 			}
 
 			gl::DeleteShader(vs);
 			gl::DeleteShader(fs);
 
-			Ok(Shader {
-				gl_handle: program,
+			cache_uniforms(program);
 
-				proj_loc: gl::GetUniformLocation(program, b"u_proj\0".as_ptr() as _),
-				view_loc: gl::GetUniformLocation(program, b"u_view\0".as_ptr() as _),
+			Ok(Shader {
+				handle: ShaderHandle {
+					gl_handle: program,
+
+					proj_loc: gl::GetUniformLocation(program, b"u_proj\0".as_ptr() as _),
+					view_loc: gl::GetUniformLocation(program, b"u_view\0".as_ptr() as _),
+				},
+
+				sources: Some(ProgramSources {
+					vertex: vertex_shader_src,
+					fragment: fragment_shader_src,
+					bindings: bindings.to_vec(),
+				}),
 			})
 		}
 	}
 
+	// Dropping self on failure deletes the program
+	fn validate_or_delete(self, layout: &VertexLayout) -> Result<Shader, Error> {
+		self.validate_layout(layout)?;
+		Ok(self)
+	}
+
 	pub const fn invalid() -> Shader {
 		Shader {
-			gl_handle: 0,
-			proj_loc: 0,
-			view_loc: 0,
+			handle: ShaderHandle {
+				gl_handle: 0,
+				proj_loc: 0,
+				view_loc: 0,
+			},
+
+			sources: None,
 		}
 	}
 
+	pub fn handle(&self) -> ShaderHandle { self.handle }
+}
+
+impl ShaderHandle {
+	pub fn validate_vertex<V>(&self) -> Result<(), Error> where V: Vertex {
+		self.validate_layout(&V::get_layout())
+	}
+
+	/// Checks that every active attribute has a binding of the same name and width in `layout`,
+	/// at the location the program actually assigned it
+	pub fn validate_layout(&self, layout: &VertexLayout) -> Result<(), Error> {
+		use std::ffi::CString;
+
+		for (name, ty) in self.get_active_attributes() {
+			let binding = layout.get_binding(&name)
+				.ok_or_else(|| Error::MissingAttribute(name.clone()))?;
+
+			if let Some(width) = attribute_width(ty) {
+				if width != binding.width {
+					return Err(Error::AttributeWidthMismatch { name, expected: width, given: binding.width });
+				}
+			}
+
+			let c_name = CString::new(name.as_str()).unwrap();
+			let location = unsafe { gl::GetAttribLocation(self.gl_handle, c_name.as_ptr()) };
+			if location != binding.index as i32 {
+				return Err(Error::AttributeLocationMismatch { name, expected: binding.index, given: location });
+			}
+		}
+
+		Ok(())
+	}
+
+	// Returns the name and GL type of each attribute the program uses, skipping builtins
+	fn get_active_attributes(&self) -> Vec<(String, u32)> {
+		let mut count = 0;
+		unsafe { gl::GetProgramiv(self.gl_handle, gl::ACTIVE_ATTRIBUTES, &mut count); }
+
+		let mut buf = [0u8; 256];
+
+		(0..count.max(0) as u32)
+			.filter_map(|idx| {
+				let (mut len, mut size, mut ty) = (0, 0, 0);
+				unsafe {
+					gl::GetActiveAttrib(self.gl_handle, idx, buf.len() as _, &mut len, &mut size, &mut ty, buf.as_mut_ptr() as _);
+				}
+
+				let name = String::from_utf8_lossy(&buf[..len as usize]).into_owned();
+				if name.starts_with("gl_") { None } else { Some((name, ty)) }
+			})
+			.collect()
+	}
+
 	fn get_currently_bound_raw() -> u32 {
 		unsafe {
 			let mut handle = 0;
@@ -89,58 +229,85 @@ impl Shader {
 	}
 
 	pub fn is_bound(&self) -> bool {
-		self.gl_handle == Shader::get_currently_bound_raw()
+		self.gl_handle == ShaderHandle::get_currently_bound_raw()
+	}
+
+	pub fn get_uniform_info(&self, uniform: &str) -> Option<UniformInfo> {
+		UNIFORM_CACHE.with(|c| {
+			c.borrow().get(&self.gl_handle)
+				.and_then(|uniforms| uniforms.get(uniform))
+				.cloned()
+		})
 	}
 
 	pub fn get_uniform_loc(&self, uniform: &str) -> i32 {
 		use std::ffi::CString;
 
+		if let Some(info) = self.get_uniform_info(uniform) {
+			return info.location
+		}
+
+		// Only the first element of an array is cached
+		if !uniform.contains('[') { return -1 }
+
 		unsafe {
 			let cstr = CString::new(uniform).unwrap();
 			gl::GetUniformLocation(self.gl_handle, cstr.as_ptr())
 		}
 	}
 
-	pub fn set_uniform_vec2(&self, uniform: &str, v: Vec2) {
-		assert!(self.is_bound(), "Tried to set uniform '{}' on unbound shader", uniform);
+	pub fn get_uniform<T>(&self, uniform: &str) -> UniformHandle<T> where T: UniformValue {
+		self.try_get_uniform(uniform)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
 
-		unsafe {
-			gl::Uniform2f(self.get_uniform_loc(&uniform), v.x, v.y);
+	/// Fails if the uniform is active but can't be set from a T
+	pub fn try_get_uniform<T>(&self, uniform: &str) -> Result<UniformHandle<T>, Error> where T: UniformValue {
+		match self.get_uniform_info(uniform) {
+			Some(info) if !T::GL_TYPES.contains(&info.ty) => Err(Error::UniformTypeMismatch {
+				name: uniform.to_string(),
+				expected: info.ty,
+				given: T::GL_TYPES[0],
+			}),
+
+			_ => Ok(UniformHandle::new(self.gl_handle, self.get_uniform_loc(uniform))),
 		}
 	}
 
-	pub fn set_uniform_vec3<V>(&self, uniform: &str, v: V) where V: Into<Vec3> {
+	pub fn set_uniform<T>(&self, uniform: &str, v: T) where T: UniformValue {
 		assert!(self.is_bound(), "Tried to set uniform '{}' on unbound shader", uniform);
 
+		if let Some(info) = self.get_uniform_info(uniform) {
+			if !T::GL_TYPES.contains(&info.ty) {
+				println!("Tried to set uniform '{}' of type {} with a {} - ignoring",
+					uniform, glsl_type_name(info.ty), glsl_type_name(T::GL_TYPES[0]));
+				return
+			}
+		}
+
 		unsafe {
-			let v = v.into();
-			gl::Uniform3f(self.get_uniform_loc(&uniform), v.x, v.y, v.z);
+			v.upload(self.get_uniform_loc(uniform));
 		}
 	}
 
-	pub fn set_uniform_vec4<V>(&self, uniform: &str, v: V) where V: Into<Vec4> {
-		assert!(self.is_bound(), "Tried to set uniform '{}' on unbound shader", uniform);
+	pub fn set_uniform_vec2(&self, uniform: &str, v: Vec2) {
+		self.set_uniform(uniform, v);
+	}
 
-		unsafe {
-			let v = v.into();
-			gl::Uniform4f(self.get_uniform_loc(&uniform), v.x, v.y, v.z, v.w);
-		}
+	pub fn set_uniform_vec3<V>(&self, uniform: &str, v: V) where V: Into<Vec3> {
+		self.set_uniform(uniform, v.into());
 	}
 
-	pub fn set_uniform_i32(&self, uniform: &str, v: i32) {
-		assert!(self.is_bound(), "Tried to set uniform '{}' on unbound shader", uniform);
+	pub fn set_uniform_vec4<V>(&self, uniform: &str, v: V) where V: Into<Vec4> {
+		self.set_uniform(uniform, v.into());
+	}
 
-		unsafe {
-			gl::Uniform1i(self.get_uniform_loc(&uniform), v);
-		}		
+	pub fn set_uniform_i32(&self, uniform: &str, v: i32) {
+		self.set_uniform(uniform, v);
 	}
 
 	pub fn set_uniform_f32(&self, uniform: &str, v: f32) {
-		assert!(self.is_bound(), "Tried to set uniform '{}' on unbound shader", uniform);
-
-		unsafe {
-			gl::Uniform1f(self.get_uniform_loc(&uniform), v);
-		}
+		self.set_uniform(uniform, v);
 	}
 	
 	pub fn set_uniform_mat_raw(&self, uniform: i32, mat: &Mat4) {
@@ -152,8 +319,7 @@ impl Shader {
 	}
 
 	pub fn set_uniform_mat(&self, uniform: &str, mat: &Mat4) {
-		assert!(self.is_bound(), "Tried to set uniform '{}' on unbound shader", uniform);
-		self.set_uniform_mat_raw(self.get_uniform_loc(&uniform), &mat);
+		self.set_uniform(uniform, *mat);
 	}
 
 	pub fn set_proj(&self, mat: &Mat4) {
@@ -167,19 +333,73 @@ impl Shader {
 	}
 }
 
+/// Relinks the program under a new handle. ShaderHandles taken before the restore keep the old one
+impl Restore for Shader {
+	fn restore(&mut self) -> Result<(), Error> {
+		let mut restored = match self.sources {
+			Some(ref s) => Shader::link(&s.vertex, &s.fragment, &s.bindings)?,
+			None => return Ok(()),
+		};
+
+		// The old program went with the context, so it's replaced without being deleted
+		self.handle = restored.handle;
+		restored.handle.gl_handle = 0;
+
+		Ok(())
+	}
+}
+
+impl Deref for Shader {
+	type Target = ShaderHandle;
+	fn deref(&self) -> &ShaderHandle { &self.handle }
+}
+
+impl Drop for Shader {
+	fn drop(&mut self) {
+		if self.gl_handle == 0 { return }
+
+		unsafe { gl::DeleteProgram(self.gl_handle); }
+
+		UNIFORM_CACHE.with(|c| c.borrow_mut().remove(&self.gl_handle));
+	}
+}
+
+
+// A piece of a shader body, and the builder call it came from
+struct BodyChunk {
+	origin: String,
+	text: String,
+	// For output()s, the index of the output being written. `text` is then just the expression,
+	// since what it's assigned to depends on the version and how many outputs there are
+	output: Option<usize>,
+}
+
+/// The GLSL dialect ShaderBuilder emits
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GlslVersion {
+	/// GLSL ES 1.00, for WebGL1
+	Es100,
+	/// GLSL ES 3.00 (`#version 300 es`), for WebGL2
+	Es300,
+}
 
 pub struct ShaderBuilder {
 	attributes: Vec<String>,
 	varyings: Vec<String>,
 	uniforms: Vec<String>,
 
-	vertex_body: String,
-	fragment_body: String,
+	vertex_body: Vec<BodyChunk>,
+	fragment_body: Vec<BodyChunk>,
 
 	use_3d: bool,
 	use_proj: bool,
 	use_view: bool,
 	use_highp: bool,
+
+	version: GlslVersion,
+	outputs: Vec<String>,
+
+	layout: Option<VertexLayout>,
 }
 
 impl ShaderBuilder {
@@ -189,13 +409,18 @@ impl ShaderBuilder {
 			varyings: Vec::new(),
 			uniforms: Vec::new(),
 
-			vertex_body: String::new(),
-			fragment_body: String::new(),
+			vertex_body: Vec::new(),
+			fragment_body: Vec::new(),
 
 			use_3d: false,
 			use_proj: false,
 			use_view: false,
 			use_highp: false,
+
+			version: GlslVersion::Es100,
+			outputs: Vec::new(),
+
+			layout: None,
 		}
 	}
 
@@ -204,12 +429,20 @@ impl ShaderBuilder {
 	pub fn use_view(mut self) -> Self { self.use_view = true; self.uniform("view", "mat4") }
 	pub fn use_highp(mut self) -> Self { self.use_highp = true; self }
 
+	/// Emits GLSL ES 3.00 rather than 1.00. Bodies are shared between versions, so should stick
+	/// to the common subset - texture2D and textureCube are defined to `texture` for 3.00
+	pub fn glsl_version(mut self, version: GlslVersion) -> Self { self.version = version; self }
+
 	pub fn vertex(mut self, data: &str) -> Self {
-		write!(&mut self.vertex_body, "{};\n", data).unwrap(); self
+		let origin = format!("vertex() #{}", self.vertex_body.iter().filter(|c| c.origin.starts_with("vertex()")).count() + 1);
+		self.vertex_body.push(BodyChunk { origin, text: body_text(data), output: None });
+		self
 	}
 
 	pub fn fragment(mut self, data: &str) -> Self {
-		write!(&mut self.fragment_body, "{};\n", data).unwrap(); self
+		let origin = format!("fragment() #{}", self.fragment_body.iter().filter(|c| c.origin.starts_with("fragment()")).count() + 1);
+		self.fragment_body.push(BodyChunk { origin, text: body_text(data), output: None });
+		self
 	}
 
 	pub fn uniform(mut self, name: &str, ty: &str) -> Self {
@@ -222,7 +455,7 @@ impl ShaderBuilder {
 			return self
 		}
 
-		self.attributes.push(format!("{} {}", ty, name)); self
+		self.declare_attribute(name, ty); self
 	}
 
 	pub fn varying(mut self, name: &str, ty: &str) -> Self {
@@ -230,112 +463,408 @@ impl ShaderBuilder {
 	}
 
 	pub fn frag_attribute(mut self, name: &str, ty: &str) -> Self {
-		self.attributes.push(format!("{} {}", ty, name));
+		self.declare_attribute(name, ty);
 		self.varyings.push(format!("{} v_{}", ty, name));
 
-		write!(&mut self.vertex_body, "v_{} = {};\n", name, name).unwrap();
+		self.vertex_body.push(BodyChunk {
+			origin: format!("frag_attribute(\"{}\")", name),
+			text: format!("v_{} = {};", name, name),
+			output: None,
+		});
 
 		self
 	}
 
-	pub fn output(mut self, expr: &str) -> Self {
-		write!(&mut self.fragment_body, "gl_FragColor = {};\n", expr).unwrap();
+	/// Declares the attributes of a #[vertex(shader_attributes)] type, in binding order.
+	/// The first field is taken to be the position
+	pub fn vertex_attributes<V>(mut self) -> Self where V: ShaderAttributes {
+		let mut attributes = V::shader_attributes().into_iter();
+
+		if let Some((_, ty)) = attributes.next() {
+			self.use_3d = ty == "vec3";
+		}
+
+		for (name, ty) in attributes {
+			self = self.attribute(name, ty);
+		}
+
 		self
 	}
 
-	pub fn finalize_source(mut self) -> (String, String) {
-		let mut varyings_and_uniforms = String::new();
+	/// Declares an attribute for each named binding in V's layout, and binds attribute locations
+	/// to match it when the shader is finalized. Finalizing fails if the two disagree
+	pub fn vertex_layout<V>(mut self) -> Self where V: Vertex {
+		let layout = V::get_layout();
+
+		for ab in layout.attributes.iter() {
+			let name = match ab.name {
+				Some(ref name) => name,
+				None => continue,
+			};
+
+			if name == "position" {
+				self.use_3d = ab.width == 3;
+			} else {
+				self.declare_attribute(name, glsl_vector_type(ab.width));
+			}
+		}
+
+		self.layout = Some(layout);
+		self
+	}
 
-		for v in self.varyings.iter() { write!(&mut varyings_and_uniforms, "varying {};\n", v).unwrap(); }
-		for u in self.uniforms.iter() { write!(&mut varyings_and_uniforms, "uniform {};\n", u).unwrap(); }
+	/// Declares an attribute for each named binding in T's layout, bound to the locations after
+	/// the vertex layout's like InstanceBuffer::bind expects. Needs vertex_layout to be set first
+	pub fn instance_layout<T>(mut self) -> Self where T: Vertex {
+		let mut layout = self.layout.take()
+			.expect("instance_layout needs vertex_layout to be called first");
 
-		let mut vert_src = String::new();
-		let mut frag_src = String::new();
+		let first = layout.next_index();
+
+		for ab in T::get_layout().attributes {
+			if let Some(ref name) = ab.name {
+				self.declare_attribute(name, glsl_vector_type(ab.width));
+			}
+
+			layout.attributes.push(VertexAttributeBinding { index: first + ab.index, ..ab });
+		}
+
+		self.layout = Some(layout);
+		self
+	}
+
+	// Attributes already declared, e.g. by vertex_layout, are left as they are
+	fn declare_attribute(&mut self, name: &str, ty: &str) {
+		let declared = self.attributes.iter()
+			.any(|a| a.split(' ').nth(1) == Some(name));
+
+		if !declared {
+			self.attributes.push(format!("{} {}", ty, name));
+		}
+	}
+
+	/// Writes `expr` to the default output - `gl_FragColor`, or `o_color` for GLSL ES 3.00
+	pub fn output(self, expr: &str) -> Self {
+		self.output_to("color", expr)
+	}
+
+	/// Writes `expr` to the vec4 output `o_<name>`, declaring it at the next free location if
+	/// it's new. Under GLSL ES 1.00 multiple outputs are written to gl_FragData, which needs
+	/// EXT_draw_buffers
+	pub fn output_to(mut self, name: &str, expr: &str) -> Self {
+		let index = match self.outputs.iter().position(|o| o == name) {
+			Some(index) => index,
+			None => {
+				self.outputs.push(name.to_string());
+				self.outputs.len() - 1
+			}
+		};
+
+		let origin = if name == "color" { "output()".to_string() } else { format!("output_to(\"{}\")", name) };
+
+		self.fragment_body.push(BodyChunk {
+			origin,
+			text: expr.to_string(),
+			output: Some(index),
+		});
+
+		self
+	}
+
+	fn output_target(&self, index: usize) -> String {
+		match self.version {
+			GlslVersion::Es300 => format!("o_{}", self.outputs[index]),
+			GlslVersion::Es100 if self.outputs.len() > 1 => format!("gl_FragData[{}]", index),
+			GlslVersion::Es100 => "gl_FragColor".to_string(),
+		}
+	}
+
+	// Without a layout, attributes are bound in declaration order after position
+	fn attribute_bindings(&self) -> Vec<(u32, String)> {
+		match self.layout {
+			Some(ref layout) => layout_bindings(layout),
+			None => ::std::iter::once((0, "position".to_string()))
+				.chain(self.attributes.iter().enumerate()
+					.map(|(idx, a)| (1 + idx as u32, a.split(' ').nth(1).unwrap().to_string())))
+				.collect()
+		}
+	}
+
+	pub fn finalize_source(self) -> (String, String) {
+		let (vert_src, frag_src) = self.finalize_mapped();
+		(vert_src.source, frag_src.source)
+	}
+
+	/// Like finalize_source, but also maps each line back to the builder call that produced it.
+	///
+	/// Functions, structs, constants and `#define`/`#undef`/`#include` directives in vertex/fragment
+	/// bodies are moved out of main, in the order they appear. `#extension`s go first, and
+	/// conditional directives stay where they are
+	pub fn finalize_mapped(self) -> (MappedSource, MappedSource) {
+		self.build_sources()
+	}
+
+	fn build_sources(&self) -> (MappedSource, MappedSource) {
+		let es300 = self.version == GlslVersion::Es300;
+		let (attribute, varying_out, varying_in) = if es300 { ("in", "out", "in") } else { ("attribute", "varying", "varying") };
+
+		let mut vert_src = MappedSource::new();
+		let mut frag_src = MappedSource::new();
+
+		if es300 {
+			vert_src.push_generated("#version 300 es");
+			frag_src.push_generated("#version 300 es");
+		} else if self.outputs.len() > 1 {
+			frag_src.push_generated("#extension GL_EXT_draw_buffers : require");
+		}
+
+		let vert_body = self.assemble_body(&self.vertex_body);
+		let frag_body = self.assemble_body(&self.fragment_body);
+
+		for &mut (ref mut sh, &(ref body, ref origins, ref decls)) in [(&mut vert_src, &vert_body), (&mut frag_src, &frag_body)].iter_mut() {
+			for d in decls.iter().filter(|d| d.kind == DeclKind::Extension) {
+				sh.push_mapped(&body[d.text_start..d.end], body[..d.text_start].matches('\n').count(), origins);
+			}
+		}
 
 		let precision = if self.use_highp { "precision highp float;" } else { "precision mediump float;" };
-		write!(&mut vert_src, "{}\n", precision).unwrap();
-		write!(&mut frag_src, "{}\n", precision).unwrap();
+		vert_src.push_generated(precision);
+		frag_src.push_generated(precision);
+
+		let bindings = self.attribute_bindings();
+		let attribute_decl = |ty: &str, name: &str| {
+			let location = bindings.iter().find(|b| b.1 == name).map(|b| b.0);
+
+			match location {
+				Some(location) if es300 => format!("layout(location={}) in {} {};", location, ty, name),
+				_ => format!("{} {} {};", attribute, ty, name),
+			}
+		};
 
 		let position_attr_ty = if self.use_3d { "vec3" } else { "vec2" };
 
-		write!(&mut vert_src, "attribute {} position;\n", position_attr_ty).unwrap();
-		for a in self.attributes.iter() { write!(&mut vert_src, "attribute {};\n", a).unwrap(); }
+		vert_src.push_generated(&attribute_decl(position_attr_ty, "position"));
+		for a in self.attributes.iter() {
+			let mut parts = a.splitn(2, ' ');
+			let (ty, name) = (parts.next().unwrap(), parts.next().unwrap());
+			vert_src.push_generated(&attribute_decl(ty, name));
+		}
+
+		let mut uniforms = String::new();
+		for u in self.uniforms.iter() { write!(&mut uniforms, "uniform {};\n", u).unwrap(); }
+
+		let mut vert_globals = String::new();
+		let mut frag_globals = String::new();
+
+		for v in self.varyings.iter() {
+			write!(&mut vert_globals, "{} {};\n", varying_out, v).unwrap();
+			write!(&mut frag_globals, "{} {};\n", varying_in, v).unwrap();
+		}
+
+		vert_globals.push_str(&uniforms);
+		frag_globals.push_str(&uniforms);
+
+		if es300 {
+			for (location, o) in self.outputs.iter().enumerate() {
+				write!(&mut frag_globals, "layout(location={}) out vec4 o_{};\n", location, o).unwrap();
+			}
+		}
 
 		let mut gl_position = String::from("gl_Position = ");
 		if self.use_proj { gl_position.push_str("u_proj * "); }
 		if self.use_view { gl_position.push_str("u_view * "); }
 		if self.use_3d {
-			gl_position.push_str("vec4(position, 1.0);\n");
+			gl_position.push_str("vec4(position, 1.0);");
 		} else {
-			gl_position.push_str("vec4(position, 0.0, 1.0);\n");
+			gl_position.push_str("vec4(position, 0.0, 1.0);");
 		}
 
-		self.vertex_body = format!("{}{}", gl_position, self.vertex_body);
+		let mut stages = [
+			(&mut vert_src, &vert_globals, vert_body, Some(gl_position)),
+			(&mut frag_src, &frag_globals, frag_body, None),
+		];
 
-		let mut bodies = [&mut self.vertex_body, &mut self.fragment_body];
-		for (sh, body) in [&mut vert_src, &mut frag_src].iter_mut().zip(bodies.iter_mut()) {
-			write!(sh, "\n{}\n", varyings_and_uniforms).unwrap();
+		for &mut (ref mut sh, globals, ref mut assembled, ref main_prelude) in stages.iter_mut() {
+			sh.push_line("", None);
+			sh.push_generated(globals);
+			sh.push_line("", None);
+
+			// Always defined, since included snippets aren't resolved yet and may use them too
+			if es300 {
+				sh.push_generated("#define texture2D texture");
+				sh.push_generated("#define textureCube texture");
+			}
 
-			let mut position = 0;
+			let (body, origins, decls) = mem::replace(assembled, (String::new(), Vec::new(), Vec::new()));
 
-			while let Some(start) = body[position..].find("func ") {
-				let length = body[start..].chars()
-					.scan((false, 0), |acc, c| {
-						let (body, nesting) = *acc;
+			// Extensions have already been emitted
+			for d in decls.iter().filter(|d| d.kind != DeclKind::Extension) {
+				let mut text = &body[d.text_start..d.end];
+				if d.kind == DeclKind::Preprocessor {
+					text = text.trim_end().trim_end_matches(';');
+				}
 
-						*acc = match (body, nesting, c) {
-							(false, _, '}') => return None,
-							(true, 1, '}') => return None,
+				sh.push_mapped(text, body[..d.text_start].matches('\n').count(), &origins);
+			}
 
-							(false, 0, '{') => (true, 1),
-							(true, x, '{') => (true, x+1),
-							(true, x, '}') => (true, x-1),
-							_ => *acc,
-						};
+			// Hoisted declarations are blanked rather than removed, so lines keep their origins
+			let mut main_body = body.into_bytes();
+			for d in decls.iter() {
+				for b in main_body[d.start..d.end].iter_mut() {
+					if *b != b'\n' { *b = b' ' }
+				}
+			}
 
-						Some(*acc)
-					})
-					.count();
+			let main_body = String::from_utf8(main_body).unwrap();
 
-				let start = start + position;
-				let end = start + length + 1;
+			sh.push_generated("void main() {");
+			if let Some(ref prelude) = *main_prelude { sh.push_generated(prelude); }
 
-				write!(sh, "{}\n", &body[start+5..end]).unwrap();
-				body.replace_range(start..end, "");
-				position = start;
+			for (line, origin) in main_body.lines().zip(origins.into_iter()) {
+				let trimmed = line.trim();
+				if trimmed.is_empty() || trimmed == ";" { continue }
+				sh.push_line(line, origin);
 			}
 
-			write!(sh, "void main() {{\n{}}}\n", body).unwrap();
+			sh.push_generated("}");
 		}
 
 		(vert_src, frag_src)
 	}
 
-	pub fn finalize(self) -> Result<Shader, String> {
-		use std::ffi::CString;
+	// Joins a stage's chunks into one body, along with where each line came from and the
+	// declarations to move out of main
+	fn assemble_body(&self, chunks: &[BodyChunk]) -> (String, Vec<Option<LineOrigin>>, Vec<Declaration>) {
+		let mut body = String::new();
+		let mut origins = Vec::new();
+
+		for chunk in chunks.iter() {
+			let text = match chunk.output {
+				Some(index) => format!("{} = {};", self.output_target(index), chunk.text),
+				None => chunk.text.clone(),
+			};
+
+			for (i, line) in text.lines().enumerate() {
+				body.push_str(line);
+				body.push('\n');
+				origins.push(Some(LineOrigin { source: chunk.origin.clone(), line: i + 1 }));
+			}
+		}
 
-		let attributes = self.attributes.iter()
-			.map(|a| CString::new(a.split(' ').nth(1).unwrap()).unwrap())
-			.collect::<Vec<_>>();
+		let decls = find_declarations(&body);
+		(body, origins, decls)
+	}
 
-		let (v,f) = self.finalize_source();
-		let mut s = Shader::new(&v, &f)?;
+	pub fn finalize(mut self) -> Result<Shader, Error> {
+		let bindings = self.attribute_bindings();
+		let (vert_src, frag_src) = self.build_sources();
+		let layout = self.layout.take();
 
-		for (idx, attrib_name) in attributes.iter().enumerate() {
-			unsafe {
-				gl::BindAttribLocation(s.gl_handle, 1 + idx as u32, attrib_name.as_ptr());
-			}
+		let shader = Shader::link(&vert_src, &frag_src, &bindings)?;
+
+		match layout {
+			Some(layout) => shader.validate_or_delete(&layout),
+			None => Ok(shader),
 		}
+	}
+}
 
-		unsafe {
-			gl::BindAttribLocation(s.gl_handle, 0, b"position\0".as_ptr() as _);
-			gl::LinkProgram(s.gl_handle);
+// Bodies can leave off their last `;`, but one can't follow a directive
+fn body_text(data: &str) -> String {
+	match data.lines().last() {
+		Some(line) if line.trim_start().starts_with('#') => data.to_string(),
+		_ => format!("{};", data),
+	}
+}
+
+fn compile_error(stage: ShaderStage, log: &str, source: &MappedSource) -> Error {
+	let diagnostics = parse_log(log, stage, &source.map);
+
+	Error::ShaderCompile {
+		stage,
+		log: annotate_log(log, &source.map),
+		listing: source_listing(&source.source, &diagnostics),
+		diagnostics,
+	}
+}
+
+fn shader_info_log(shader: u32) -> String {
+	let mut length = 0;
+	unsafe { gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut length); }
+
+	read_info_log(length, |size, written, buf| unsafe { gl::GetShaderInfoLog(shader, size, written, buf) })
+}
+
+fn program_info_log(program: u32) -> String {
+	let mut length = 0;
+	unsafe { gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut length); }
+
+	read_info_log(length, |size, written, buf| unsafe { gl::GetProgramInfoLog(program, size, written, buf) })
+}
+
+// `length` is INFO_LOG_LENGTH, which counts the nul terminator
+fn read_info_log<F>(length: i32, read: F) -> String where F: FnOnce(i32, &mut i32, *mut gl::GLchar) {
+	if length <= 1 { return String::new() }
+
+	let mut buf = vec![0u8; length as usize];
+	let mut written = 0;
+	read(length, &mut written, buf.as_mut_ptr() as _);
+
+	buf.truncate(written.max(0).min(length - 1) as usize);
+	String::from_utf8_lossy(&buf).into_owned()
+}
 
-			s.proj_loc = gl::GetUniformLocation(s.gl_handle, b"u_proj\0".as_ptr() as _);
-			s.view_loc = gl::GetUniformLocation(s.gl_handle, b"u_view\0".as_ptr() as _);
+fn cache_uniforms(program: u32) {
+	let mut uniforms = HashMap::new();
+	let mut count = 0;
+	let mut buf = [0u8; 256];
+
+	unsafe {
+		gl::GetProgramiv(program, gl::ACTIVE_UNIFORMS, &mut count);
+
+		for idx in 0..count.max(0) as u32 {
+			let (mut len, mut size, mut ty) = (0, 0, 0);
+			gl::GetActiveUniform(program, idx, buf.len() as _, &mut len, &mut size, &mut ty, buf.as_mut_ptr() as _);
+
+			let name = String::from_utf8_lossy(&buf[..len as usize]).into_owned();
+			let location = gl::GetUniformLocation(program, buf.as_ptr() as _);
+			let info = UniformInfo { location, ty, size };
+
+			// Arrays are reported as `name[0]`, but are usually set through the bare name
+			if name.ends_with("[0]") {
+				uniforms.insert(name[..name.len()-3].to_string(), info);
+			}
+
+			uniforms.insert(name, info);
 		}
+	}
 
-		Ok(s)
+	UNIFORM_CACHE.with(|c| c.borrow_mut().insert(program, uniforms));
+}
+
+fn glsl_vector_type(width: i32) -> &'static str {
+	match width {
+		1 => "float",
+		2 => "vec2",
+		3 => "vec3",
+		_ => "vec4",
+	}
+}
+
+fn layout_bindings(layout: &VertexLayout) -> Vec<(u32, String)> {
+	layout.attributes.iter()
+		.filter_map(|ab| ab.name.clone().map(|name| (ab.index, name)))
+		.collect()
+}
+
+// The number of components in an attribute of GL type `ty`, if it's a scalar or vector
+fn attribute_width(ty: u32) -> Option<i32> {
+	match ty {
+		gl::FLOAT => Some(1),
+		gl::FLOAT_VEC2 => Some(2),
+		gl::FLOAT_VEC3 => Some(3),
+		gl::FLOAT_VEC4 => Some(4),
+		_ => None,
 	}
 }
 
@@ -354,6 +883,7 @@ impl ShaderBuilder {
 				}
 
 				func vec3 function_test_2(float c) {
+					// Not the end }
 					if (c < 0.5) {
 						return vec3(c);
 					} else {
@@ -366,15 +896,392 @@ impl ShaderBuilder {
 			.output("vec4(color, 1.0)")
 			.finalize_source();
 
-		println!("vert source\n==========\n{}\n", vsh);
-		println!("frag source\n==========\n{}", fsh);
+		assert!(vsh.contains("attribute vec2 position;\n"));
+		assert!(vsh.contains("attribute vec4 some_random_attribute;\n"));
+		assert!(vsh.contains("varying vec3 v_color;\n"));
+		assert!(vsh.contains("gl_Position = u_proj * u_view * vec4(position, 0.0, 1.0);\n"));
+		assert!(vsh.contains("v_uv = uv;"));
 
-		let (vsh, fsh) = ::ShaderBuilder::new()
+		let main = fsh.find("void main() {").unwrap();
+		let (globals, main_body) = fsh.split_at(main);
+
+		assert!(globals.contains("uniform sampler2D u_tex;\n"));
+		assert!(globals.contains("vec3 function_test(vec3 c) {"));
+		assert!(globals.contains("vec3 function_test_2(float c) {"));
+		assert!(globals.contains("// Not the end }"));
+		assert!(!fsh.contains("func "));
+
+		assert!(!main_body.contains("function_test_2"));
+		assert!(main_body.contains("vec3 color = function_test(v_color);"));
+		assert!(main_body.contains("gl_FragColor = vec4(color, 1.0);"));
+		assert!(main_body.trim_end().ends_with("}"));
+
+		let (vsh, _) = ::ShaderBuilder::new()
 			.use_3d()
 			.output("vec4(1.0)")
 			.finalize_source();
 
-		println!("vert source\n==========\n{}\n", vsh);
-		println!("frag source\n==========\n{}", fsh);
+		assert!(vsh.contains("attribute vec3 position;\n"));
+		assert!(vsh.contains("gl_Position = vec4(position, 1.0);\n"));
+	}
+
+	#[test]
+	fn builder_hoisting() {
+		let (_, fsh) = ::ShaderBuilder::new()
+			.fragment("struct Light { vec3 dir; float power; }")
+			.fragment("const float AMBIENT = 0.1")
+			.fragment("float my_func(Light l) { return l.power + AMBIENT; }")
+			.fragment("Light l = Light(vec3(0.0), 1.0)")
+			.output("vec4(my_func(l))")
+			.finalize_source();
+
+		let main = fsh.find("void main() {").unwrap();
+		let light = fsh.find("struct Light { vec3 dir; float power; };").unwrap();
+		let ambient = fsh.find("const float AMBIENT = 0.1;").unwrap();
+		let func = fsh.find("float my_func(Light l) {").unwrap();
+
+		assert!(light < ambient && ambient < func && func < main);
+		assert!(fsh[main..].contains("Light l = Light(vec3(0.0), 1.0);"));
+	}
+
+	#[test]
+	fn builder_source_map() {
+		let (_, fsh) = ::ShaderBuilder::new()
+			.fragment("float a = 1.0")
+			.fragment("func float twice(float x) {\n\treturn x * 2.0;\n}\nfloat b = twice(a)")
+			.output("vec4(b)")
+			.finalize_mapped();
+
+		let line_of = |needle: &str| fsh.source.lines().position(|l| l.contains(needle)).unwrap() + 1;
+
+		let origin = fsh.map.lookup(line_of("return x * 2.0")).unwrap();
+		assert_eq!((origin.source.as_str(), origin.line), ("fragment() #2", 2));
+
+		let origin = fsh.map.lookup(line_of("float b = twice(a)")).unwrap();
+		assert_eq!((origin.source.as_str(), origin.line), ("fragment() #2", 4));
+
+		let origin = fsh.map.lookup(line_of("gl_FragColor")).unwrap();
+		assert_eq!(origin.source, "output()");
+
+		assert!(fsh.map.lookup(line_of("void main()")).is_none());
+	}
+
+	#[test]
+	fn builder_glsl_versions() {
+		use ::GlslVersion;
+
+		let builder = || ::ShaderBuilder::new()
+			.uniform("tex", "sampler2D")
+			.frag_attribute("uv", "vec2")
+			.fragment("vec4 c = texture2D(u_tex, v_uv)")
+			.output("c")
+			.output_to("bright", "c * 2.0");
+
+		let (vsh, fsh) = builder().glsl_version(GlslVersion::Es300).finalize_source();
+
+		assert!(vsh.starts_with("#version 300 es\n"));
+		assert!(vsh.contains("layout(location=0) in vec2 position;\n"));
+		assert!(vsh.contains("layout(location=1) in vec2 uv;\n"));
+		assert!(vsh.contains("out vec2 v_uv;\n"));
+		assert!(!vsh.contains("attribute") && !vsh.contains("varying"));
+
+		assert!(fsh.starts_with("#version 300 es\n"));
+		assert!(fsh.contains("in vec2 v_uv;\n"));
+		assert!(fsh.contains("layout(location=0) out vec4 o_color;\n"));
+		assert!(fsh.contains("layout(location=1) out vec4 o_bright;\n"));
+		assert!(fsh.contains("#define texture2D texture\n"));
+		assert!(vsh.contains("#define textureCube texture\n"));
+		assert!(fsh.contains("o_color = c;"));
+		assert!(fsh.contains("o_bright = c * 2.0;"));
+		assert!(!fsh.contains("gl_Frag"));
+
+		let (vsh, fsh) = builder().finalize_source();
+
+		assert!(vsh.contains("attribute vec2 uv;\n"));
+		assert!(vsh.contains("varying vec2 v_uv;\n"));
+		assert!(fsh.starts_with("#extension GL_EXT_draw_buffers : require\n"));
+		assert!(fsh.contains("gl_FragData[0] = c;"));
+		assert!(fsh.contains("gl_FragData[1] = c * 2.0;"));
+		assert!(!fsh.contains("#version") && !fsh.contains("#define"));
+
+		// A single output stays gl_FragColor, and needs no extension
+		let (_, fsh) = ::ShaderBuilder::new().output("vec4(1.0)").output("vec4(0.5)").finalize_source();
+		assert_eq!(fsh.matches("gl_FragColor = ").count(), 2);
+		assert!(!fsh.contains("#extension"));
+	}
+
+	#[cfg(feature = "headless")]
+	#[test]
+	fn builder_glsl_300_layout() {
+		use bindings::headless;
+		use rendering::mesh::tests::LitVertex;
+		use ::GlslVersion;
+
+		headless::reset();
+
+		let shader = ::ShaderBuilder::new()
+			.glsl_version(GlslVersion::Es300)
+			.attribute("uv", "vec2")
+			.vertex_layout::<LitVertex>()
+			.output("vec4(1.0)")
+			.finalize().unwrap();
+
+		let attributes = headless::with_state(|s| s.programs[&shader.gl_handle].active_attributes.clone());
+		let location = |name: &str| attributes.iter().find(|a| a.0 == name).unwrap().2;
+
+		assert_eq!((location("position"), location("normal"), location("uv")), (0, 1, 2));
+		assert!(shader.validate_vertex::<LitVertex>().is_ok());
+	}
+
+	#[cfg(feature = "headless")]
+	#[test]
+	fn builder_compile_error() {
+		use ::{Error, ShaderStage};
+
+		let result = ::ShaderBuilder::new()
+			.fragment("float a = 1.0")
+			.fragment("#error bad include")
+			.output("vec4(a)")
+			.finalize();
+
+		match result {
+			Err(Error::ShaderCompile { stage: ShaderStage::Fragment, log, diagnostics, listing }) => {
+				assert!(log.contains("[fragment() #2, line 1]"), "{}", log);
+				assert_eq!(diagnostics[0].origin.as_ref().unwrap().source, "fragment() #2");
+				assert!(listing.contains("| #error bad include\n"), "{}", listing);
+			}
+			Err(e) => panic!("Unexpected error {}", e),
+			Ok(_) => panic!("Shader compiled unexpectedly"),
+		}
+	}
+
+	#[test]
+	fn builder_conditionals() {
+		let (vsh, fsh) = ::ShaderBuilder::new()
+			.vertex("#ifdef WIDE\ngl_Position.x *= 2.0;\n#endif")
+			.fragment("#extension GL_OES_standard_derivatives : enable\n#define SCALE 0.5")
+			.fragment("vec4 c = vec4(SCALE);\n#if defined(GL_OES_standard_derivatives)\nc.a = fwidth(c.r);\n#else\nc.a = 1.0;\n#endif")
+			.output("c")
+			.finalize_source();
+
+		// Conditionals stay around the statements they guard
+		let main = vsh.find("void main()").unwrap();
+		assert!(vsh[main..].contains("#ifdef WIDE\ngl_Position.x *= 2.0;\n#endif\n"), "{}", vsh);
+
+		let main = fsh.find("void main()").unwrap();
+		assert!(fsh.starts_with("#extension GL_OES_standard_derivatives : enable\nprecision"), "{}", fsh);
+		assert!(fsh[..main].contains("#define SCALE 0.5\n"));
+		assert!(fsh[main..].contains("#if defined(GL_OES_standard_derivatives)\nc.a = fwidth(c.r);\n#else\nc.a = 1.0;\n#endif\n"), "{}", fsh);
+		assert_eq!(fsh.matches("#extension").count(), 1);
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn builder_includes() {
+		use rendering::snippets::{register_snippet, resolve_includes};
+
+		register_snippet("test_invert", "vec3 invert(vec3 c) { return vec3(1.0) - c; }");
+
+		let (_, fsh) = ::ShaderBuilder::new()
+			.fragment("#include \"test_invert\"")
+			.output("vec4(invert(vec3(0.2)), 1.0)")
+			.finalize_source();
+
+		let main = fsh.find("void main()").unwrap();
+		assert!(fsh[..main].contains("#include \"test_invert\"\n"));
+		assert!(!fsh[main..].contains("#include"));
+
+		let resolved = resolve_includes(&fsh).unwrap();
+		assert!(resolved.find("vec3 invert(").unwrap() < resolved.find("void main()").unwrap());
+	}
+
+	#[test]
+	fn vertex_attributes() {
+		use common::math::*;
+		use rendering::types::PackedColor;
+		use rendering::mesh::Vertex;
+
+		#[allow(dead_code)]
+		#[derive(Copy, Clone, Vertex)]
+		#[vertex(shader_attributes)]
+		#[repr(C)]
+		struct TestVertex {
+			pos: Vec3,
+			#[vertex(name = "color")] col: PackedColor,
+			uv: Vec2,
+		}
+
+		let (vsh, _) = ::ShaderBuilder::new()
+			.vertex_attributes::<TestVertex>()
+			.output("vec4(1.0)")
+			.finalize_source();
+
+		assert!(vsh.contains("attribute vec3 position;"));
+		assert!(vsh.contains("attribute vec4 color;"));
+		assert!(vsh.contains("attribute vec2 uv;"));
+	}
+
+	#[cfg(feature = "headless")]
+	#[test]
+	fn layout_binding() {
+		use bindings::headless;
+		use rendering::mesh::{Vertex, VertexLayout};
+		use rendering::mesh::tests::LitVertex;
+		use ::{Shader, Error};
+
+		headless::reset();
+
+		// Declared out of layout order, so only name-based binding gets the locations right
+		let shader = ::ShaderBuilder::new()
+			.attribute("uv", "vec2")
+			.vertex_layout::<LitVertex>()
+			.output("vec4(1.0)")
+			.finalize().unwrap();
+
+		let bindings = headless::with_state(|s| s.programs[&shader.gl_handle].attrib_bindings.clone());
+		assert_eq!(bindings["position"], 0);
+		assert_eq!(bindings["normal"], 1);
+		assert_eq!(bindings["uv"], 2);
+		assert!(shader.validate_vertex::<LitVertex>().is_ok());
+
+		let bound: Vec<_> = headless::calls_named("BindAttribLocation").iter()
+			.map(|c| (c.str(2).to_string(), c.int(1)))
+			.collect();
+		assert!(bound.contains(&("uv".to_string(), 2)));
+
+		let vsh = "attribute vec3 position; attribute vec4 color; void main() {}";
+		let fsh = "void main() {}";
+
+		match Shader::new_with_layout(vsh, fsh, &LitVertex::get_layout()) {
+			Err(Error::MissingAttribute(ref name)) if name == "color" => {}
+			Err(e) => panic!("Unexpected error {}", e),
+			Ok(_) => panic!("Shader linked against incompatible layout"),
+		}
+
+		let layout = VertexLayout::new::<LitVertex>()
+			.add_binding(0, 2, 0).named("position");
+
+		match Shader::new_with_layout("attribute vec3 position; void main() {}", fsh, &layout) {
+			Err(Error::AttributeWidthMismatch { expected: 3, given: 2, .. }) => {}
+			Err(e) => panic!("Unexpected error {}", e),
+			Ok(_) => panic!("Shader linked against incompatible layout"),
+		}
+	}
+
+	#[cfg(feature = "headless")]
+	#[test]
+	fn uniform_cache() {
+		use bindings::headless;
+		use common::math::*;
+		use rendering::gl;
+		use rendering::uniform::UniformHandle;
+		use ::Error;
+
+		headless::reset();
+
+		let shader = ::ShaderBuilder::new()
+			.uniform("tint", "vec4")
+			.uniform("tex", "sampler2D")
+			.uniform("offsets[4]", "vec2")
+			.use_proj()
+			.output("u_tint")
+			.finalize().unwrap();
+
+		let tint = shader.get_uniform_info("u_tint").unwrap();
+		assert_eq!(tint.ty, gl::FLOAT_VEC4);
+		assert_eq!(shader.get_uniform_info("u_offsets").unwrap().size, 4);
+		assert!(shader.get_uniform_info("u_nonexistent").is_none());
+
+		shader.use_program();
+		headless::take_calls();
+
+		let handle: UniformHandle<Color> = shader.get_uniform("u_tint");
+		shader.set_uniform("u_tex", 1i32);
+		handle.set(&Color::white());
+
+		// Everything was resolved at link time
+		assert!(headless::calls_named("GetUniformLocation").is_empty());
+
+		let calls = headless::calls_named("Uniform4f");
+		assert_eq!(calls.len(), 1);
+		assert_eq!(calls[0].int(0), tint.location as i64);
+
+		match shader.try_get_uniform::<f32>("u_tint") {
+			Err(Error::UniformTypeMismatch { expected: gl::FLOAT_VEC4, given: gl::FLOAT, .. }) => {}
+			Err(e) => panic!("Unexpected error {}", e),
+			Ok(_) => panic!("Mismatched uniform type accepted"),
+		}
+
+		// Mismatched sets are dropped rather than forwarded to GL
+		shader.set_uniform("u_tint", 1.0f32);
+		assert!(headless::calls_named("Uniform1f").is_empty());
+	}
+
+	#[cfg(feature = "headless")]
+	#[test]
+	fn compile_error() {
+		use ::{Shader, Error, ShaderStage};
+		use bindings::headless;
+		use rendering::glsl::Severity;
+
+		headless::reset();
+
+		let vsh = "void main() { gl_Position = vec4(0.0); }";
+		let fsh = "#error broken\nvoid main() {}";
+
+		match Shader::new(vsh, fsh) {
+			Err(Error::ShaderCompile { stage: ShaderStage::Fragment, log, .. }) => assert!(log.contains("broken")),
+			Err(e) => panic!("Unexpected error {}", e),
+			Ok(_) => panic!("Shader compiled unexpectedly"),
+		}
+
+		// Nothing is left behind by a failed build
+		assert!(headless::with_state(|s| s.shaders.is_empty() && s.programs.is_empty()));
+
+		// Logs longer than any fixed buffer come through whole
+		let fsh: String = (0..100).map(|i| format!("#error problem number {}\n", i)).collect();
+
+		match Shader::new(vsh, &fsh) {
+			Err(Error::ShaderCompile { log, diagnostics, listing, .. }) => {
+				assert!(log.len() > 2048);
+				assert!(log.trim_end().ends_with("problem number 99"));
+
+				assert_eq!(diagnostics.len(), 100);
+				assert_eq!(diagnostics[99].severity, Severity::Error);
+				assert_eq!(diagnostics[99].line, Some(100));
+				assert_eq!(diagnostics[99].message, "'#error' : problem number 99");
+
+				assert!(listing.contains(">> 100 | #error problem number 99\n"), "{}", listing);
+			}
+
+			Err(e) => panic!("Unexpected error {}", e),
+			Ok(_) => panic!("Shader compiled unexpectedly"),
+		}
+	}
+
+	#[cfg(feature = "headless")]
+	#[test]
+	fn drop_deletes_program() {
+		use bindings::headless;
+		use super::{Shader, UNIFORM_CACHE};
+
+		headless::reset();
+
+		let shader = ::ShaderBuilder::new().output("vec4(1.0)").finalize().unwrap();
+		let handle = shader.handle();
+		assert_eq!(handle, *shader);
+
+		drop(shader);
+
+		let deletes = headless::calls_named("DeleteProgram");
+		assert_eq!(deletes.len(), 1);
+		assert_eq!(deletes[0].int(0), handle.gl_handle as i64);
+		assert!(headless::with_state(|s| s.programs.is_empty()));
+
+		// Nothing is kept around for the dangling handle
+		assert!(handle.get_uniform_info("u_proj").is_none());
+		assert!(UNIFORM_CACHE.with(|c| c.borrow().is_empty()));
+
+		drop(Shader::invalid());
+		assert_eq!(headless::calls_named("DeleteProgram").len(), 1);
+	}
+}
@@ -0,0 +1,119 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use error::Error;
+use rendering::glsl::{MappedSource, LineOrigin};
+
+thread_local! {
+	static SNIPPETS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+}
+
+/// Registers a named piece of GLSL that shaders can pull in with `#include "name"`.
+/// Snippets can include other snippets. Registering an existing name replaces it
+pub fn register_snippet(name: &str, source: &str) {
+	SNIPPETS.with(|s| s.borrow_mut().insert(name.to_string(), source.to_string()));
+}
+
+pub fn has_snippet(name: &str) -> bool {
+	SNIPPETS.with(|s| s.borrow().contains_key(name))
+}
+
+/// Replaces each `#include "name"` line with the named snippet, preceded by anything it includes.
+/// Each snippet is only emitted once per source, no matter how many times it's included
+pub fn resolve_includes(source: &str) -> Result<String, Error> {
+	if !source.contains("#include") {
+		return Ok(source.to_string())
+	}
+
+	let mut out = MappedSource::new();
+	expand(source, &|_| None, &mut out, &mut HashSet::new(), &mut Vec::new())?;
+	Ok(out.source)
+}
+
+/// Like resolve_includes, but carries the source map through. Snippet lines are mapped to
+/// the snippet they came from
+pub fn resolve_includes_mapped(source: &MappedSource) -> Result<MappedSource, Error> {
+	if !source.source.contains("#include") {
+		return Ok(source.clone())
+	}
+
+	let origin = |i: usize| source.map.lines.get(i).cloned().and_then(|o| o);
+
+	let mut out = MappedSource::new();
+	expand(&source.source, &origin, &mut out, &mut HashSet::new(), &mut Vec::new())?;
+	Ok(out)
+}
+
+fn expand(source: &str, origin: &dyn Fn(usize) -> Option<LineOrigin>, out: &mut MappedSource,
+	included: &mut HashSet<String>, stack: &mut Vec<String>) -> Result<(), Error> {
+
+	for (i, line) in source.lines().enumerate() {
+		let name = match parse_include(line) {
+			Some(name) => name,
+			None => {
+				out.push_line(line, origin(i));
+				continue
+			}
+		};
+
+		if included.contains(name) { continue }
+
+		if stack.iter().any(|s| s == name) {
+			stack.push(name.to_string());
+			return Err(Error::SnippetCycle(stack.join(" -> ")))
+		}
+
+		let snippet = SNIPPETS.with(|s| s.borrow().get(name).cloned())
+			.ok_or_else(|| Error::UnknownSnippet(name.to_string()))?;
+
+		let snippet_origin = |l: usize| Some(LineOrigin { source: format!("snippet \"{}\"", name), line: l + 1 });
+
+		stack.push(name.to_string());
+		expand(&snippet, &snippet_origin, out, included, stack)?;
+		stack.pop();
+
+		included.insert(name.to_string());
+	}
+
+	Ok(())
+}
+
+/// Returns the snippet name if `line` is an include directive. A trailing ';' is allowed,
+/// since ShaderBuilder appends one to every line it's given
+pub fn parse_include(line: &str) -> Option<&str> {
+	let line = line.trim();
+	if !line.starts_with("#include") { return None }
+
+	let name = line["#include".len()..].trim().trim_end_matches(';').trim();
+
+	if name.len() >= 2 && name.starts_with('"') && name.ends_with('"') {
+		Some(&name[1..name.len()-1])
+	} else {
+		None
+	}
+}
+
+#[cfg(test)] mod tests {
+	use super::*;
+
+	#[test]
+	fn includes() {
+		register_snippet("test_consts", "const float PI = 3.14159;");
+		register_snippet("test_angles", "#include \"test_consts\"\nfloat turns(float r) { return r / (2.0 * PI); }");
+		register_snippet("test_cycle_a", "#include \"test_cycle_b\"");
+		register_snippet("test_cycle_b", "#include \"test_cycle_a\"");
+
+		let src = resolve_includes("#include \"test_angles\"\n#include \"test_consts\";\nvoid main() {}").unwrap();
+		assert_eq!(src, "const float PI = 3.14159;\nfloat turns(float r) { return r / (2.0 * PI); }\nvoid main() {}\n");
+
+		match resolve_includes("#include \"test_missing\"") {
+			Err(Error::UnknownSnippet(ref name)) if name == "test_missing" => {}
+			_ => panic!("Expected unknown snippet error"),
+		}
+
+		match resolve_includes("#include \"test_cycle_a\"") {
+			Err(Error::SnippetCycle(ref chain)) => assert_eq!(chain, "test_cycle_a -> test_cycle_b -> test_cycle_a"),
+			_ => panic!("Expected cycle error"),
+		}
+	}
+}
@@ -3,29 +3,96 @@
 use common::math::*;
 
 use rendering::gl;
+use rendering::resources::Restore;
+use error::Error;
 
 use std::ops::Drop;
+use std::ptr::null;
 
 pub struct Texture {
 	pub gl_handle: u32,
 	pub size: Vec2i,
+
+	// Retained so the texture can be recreated after a context loss
+	format: u32,
+	component_type: u32,
+	retain_data: bool,
+	data: Option<Vec<u8>>,
+	min_filter: u32,
+	mag_filter: u32,
 }
 
 impl Texture {
 	pub fn new() -> Self {
-		let mut gl_handle = 0;
+		let mut tex = Texture {
+			gl_handle: 0,
+			size: Vec2i::zero(),
+
+			format: gl::RGBA,
+			component_type: gl::UNSIGNED_BYTE,
+			retain_data: false,
+			data: None,
+			min_filter: gl::NEAREST,
+			mag_filter: gl::NEAREST,
+		};
+
+		tex.create();
+		tex
+	}
+
+	/// A linearly filtered texture with uninitialised storage, for rendering to.
+	/// `format` is used as the internal format too
+	pub fn new_render_target(size: Vec2i, format: u32, component_type: u32) -> Self {
+		let mut tex = Texture::new();
+		tex.format = format;
+		tex.component_type = component_type;
 
+		tex.linear();
+		tex.allocate(size);
+		tex
+	}
+
+	fn create(&mut self) {
 		unsafe {
-			gl::GenTextures(1, &mut gl_handle);
-			let _bind_guard = TextureBindGuard::new_raw(gl_handle);
+			gl::GenTextures(1, &mut self.gl_handle);
+			let _bind_guard = TextureBindGuard::new(self);
 
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, self.min_filter as i32);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, self.mag_filter as i32);
 			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
 			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
 		}
+	}
+
+	/// Reallocates storage at `size`, leaving its contents undefined
+	pub fn allocate(&mut self, size: Vec2i) {
+		self.size = size;
+		self.data = None;
+
+		unsafe {
+			let _bind_guard = TextureBindGuard::new(self);
+			gl::TexImage2D(gl::TEXTURE_2D, 0, self.format as i32, size.x, size.y, 0,
+				self.format, self.component_type, null());
+		}
+	}
+
+	fn upload(&mut self, data: Vec<u8>, size: Vec2i) {
+		self.size = size;
+
+		unsafe {
+			let _bind_guard = TextureBindGuard::new(self);
+			gl::TexImage2D(gl::TEXTURE_2D, 0, self.format as i32, size.x, size.y, 0,
+				self.format, self.component_type, data.as_ptr() as *const _);
+		}
 
-		Texture { gl_handle, size: Vec2i::zero() }
+		self.data = if self.retain_data { Some(data) } else { None };
+	}
+
+	/// Keeps a copy of everything uploaded from here on, so the contents survive a context loss.
+	/// Without it, a restored texture keeps its size but its contents are undefined
+	pub fn retain_data(&mut self, retain: bool) {
+		self.retain_data = retain;
+		if !retain { self.data = None; }
 	}
 
 	pub fn bind_guard(&self) -> TextureBindGuard {
@@ -33,36 +100,51 @@ impl Texture {
 	}
 
 	pub fn from_1d(data: &[Color]) -> Self {
+		Texture::try_from_1d(data)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_from_1d(data: &[Color]) -> Result<Self, Error> {
 		let mut tex = Texture::new();
-		tex.upload_1d(data);
-		tex
+		tex.try_upload_1d(data)?;
+		Ok(tex)
 	}
 
-	// TODO: try and find a way to use browsers builtin png decode
 	pub fn from_png(data: &[u8]) -> Self {
-		use png::Decoder;
-
-		let decoder = Decoder::new(data);
-		let (info, mut reader) = decoder.read_info().unwrap();
+		Texture::try_from_png(data)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
 
-		assert!(info.width.is_power_of_two(), "Textures must be POW2");
-		assert!(info.height.is_power_of_two(), "Textures must be POW2");
+	pub fn try_from_png(data: &[u8]) -> Result<Self, Error> {
+		let mut tex = Texture::new();
+		tex.try_upload_png(data)?;
+		Ok(tex)
+	}
 
-		let mut buf = vec![0; info.buffer_size()];
-		reader.next_frame(&mut buf).unwrap();
+	pub fn upload_png(&mut self, data: &[u8]) {
+		self.try_upload_png(data)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
 
-		let mut tex = Texture::new();
-		let _bind_guard = TextureBindGuard::new(&tex);
+	// TODO: try and find a way to use browsers builtin png decode
+	pub fn try_upload_png(&mut self, data: &[u8]) -> Result<(), Error> {
+		use png::Decoder;
 
-		tex.size = Vec2i::new(info.width as i32, info.height as i32);
+		let decoder = Decoder::new(data);
+		let (info, mut reader) = decoder.read_info()
+			.map_err(|e| Error::ImageDecode(e.to_string()))?;
 
-		unsafe {
-			gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32,
-				tex.size.x, tex.size.y, 0, gl::RGBA,
-				gl::UNSIGNED_BYTE, buf.as_ptr() as *const _);
+		let size = Vec2i::new(info.width as i32, info.height as i32);
+		if !info.width.is_power_of_two() || !info.height.is_power_of_two() {
+			return Err(Error::InvalidTextureSize(size));
 		}
 
-		tex
+		let mut buf = vec![0; info.buffer_size()];
+		reader.next_frame(&mut buf)
+			.map_err(|e| Error::ImageDecode(e.to_string()))?;
+
+		self.upload(buf, size);
+		Ok(())
 	}
 
 	fn get_bound_id() -> u32 {
@@ -85,45 +167,55 @@ impl Texture {
 	}
 
 	pub fn upload_1d(&mut self, data: &[Color]) {
-		unsafe {
-			let len = data.len() as u32;
-			assert!(len.is_power_of_two(), "Textures must be POW2");
-
-			self.size = Vec2i::new(data.len() as i32, 1);
+		self.try_upload_1d(data)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
 
-			let mut v = Vec::with_capacity(data.len() * 4);
-			for c in data.iter() {
-				let (r,g,b,a) = c.to_byte_tuple();
-				v.extend_from_slice(&[r, g, b, a]);
-			}
+	pub fn try_upload_1d(&mut self, data: &[Color]) -> Result<(), Error> {
+		let size = Vec2i::new(data.len() as i32, 1);
+		if !data.len().is_power_of_two() {
+			return Err(Error::InvalidTextureSize(size));
+		}
 
-			let _bind_guard = TextureBindGuard::new(self);
-			gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, self.size.x, 1, 0, gl::RGBA, gl::UNSIGNED_BYTE, v.as_ptr() as *const _);
+		let mut v = Vec::with_capacity(data.len() * 4);
+		for c in data.iter() {
+			let (r,g,b,a) = c.to_byte_tuple();
+			v.extend_from_slice(&[r, g, b, a]);
 		}
+
+		self.upload(v, size);
+		Ok(())
 	}
 
 	pub fn upload_2d(&mut self, data: &[Color], size: Vec2i) {
-		unsafe {
-			let len = data.len() as i32;
-			assert!((size.x as u32).is_power_of_two(), "Textures must be POW2");
-			assert!((size.y as u32).is_power_of_two(), "Textures must be POW2");
-			assert!(len >= size.x*size.y, "Passed slice not large enough");
+		self.try_upload_2d(data, size)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
 
-			self.size = size;
+	pub fn try_upload_2d(&mut self, data: &[Color], size: Vec2i) -> Result<(), Error> {
+		if !(size.x as u32).is_power_of_two() || !(size.y as u32).is_power_of_two() {
+			return Err(Error::InvalidTextureSize(size));
+		}
 
-			let mut v = Vec::with_capacity(data.len() * 4);
-			for c in data.iter() {
-				let (r,g,b,a) = c.to_byte_tuple();
-				v.extend_from_slice(&[r, g, b, a]);
-			}
+		let expected = (size.x * size.y) as usize;
+		if data.len() < expected {
+			return Err(Error::NotEnoughTextureData { expected, given: data.len() });
+		}
 
-			let _bind_guard = TextureBindGuard::new(self);
-			gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, self.size.x, self.size.y, 0,
-				gl::RGBA, gl::UNSIGNED_BYTE, v.as_ptr() as *const _);
+		let mut v = Vec::with_capacity(data.len() * 4);
+		for c in data.iter() {
+			let (r,g,b,a) = c.to_byte_tuple();
+			v.extend_from_slice(&[r, g, b, a]);
 		}
+
+		self.upload(v, size);
+		Ok(())
 	}
 
 	pub fn linear(&mut self) {
+		self.min_filter = gl::LINEAR;
+		self.mag_filter = gl::LINEAR;
+
 		unsafe {
 			let _bind_guard = TextureBindGuard::new(self);
 
@@ -133,6 +225,9 @@ impl Texture {
 	}
 
 	pub fn nearest(&mut self) {
+		self.min_filter = gl::NEAREST;
+		self.mag_filter = gl::LINEAR;
+
 		unsafe {
 			let _bind_guard = TextureBindGuard::new(self);
 
@@ -142,6 +237,27 @@ impl Texture {
 	}
 }
 
+impl Drop for Texture {
+	fn drop(&mut self) {
+		unsafe { gl::DeleteTextures(1, &self.gl_handle); }
+	}
+}
+
+impl Restore for Texture {
+	fn restore(&mut self) -> Result<(), Error> {
+		self.create();
+
+		let size = self.size;
+		match self.data.take() {
+			Some(data) => self.upload(data, size),
+			None if size != Vec2i::zero() => self.allocate(size),
+			None => {}
+		}
+
+		Ok(())
+	}
+}
+
 pub struct TextureBindGuard {
 	prev_binding: Option<u32>,
 }
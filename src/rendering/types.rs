@@ -43,3 +43,32 @@ impl Viewport {
 	}
 }
 
+
+/// A colour stored as four bytes, for use as a normalized UNSIGNED_BYTE vertex attribute
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C)]
+pub struct PackedColor {
+	pub r: u8,
+	pub g: u8,
+	pub b: u8,
+	pub a: u8,
+}
+
+impl PackedColor {
+	pub fn new<C>(color: C) -> Self where C: Into<Color> {
+		let (r,g,b,a) = color.into().to_byte_tuple();
+		PackedColor {r, g, b, a}
+	}
+}
+
+impl From<Color> for PackedColor {
+	fn from(c: Color) -> Self {
+		PackedColor::new(c)
+	}
+}
+
+impl From<PackedColor> for Color {
+	fn from(c: PackedColor) -> Self {
+		Color::rgba(c.r as f32 / 255.0, c.g as f32 / 255.0, c.b as f32 / 255.0, c.a as f32 / 255.0)
+	}
+}
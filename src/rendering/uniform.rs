@@ -0,0 +1,103 @@
+use std::marker::PhantomData;
+
+use common::math::*;
+use rendering::gl;
+
+/// An active uniform as reported by the program after linking
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UniformInfo {
+	pub location: i32,
+	pub ty: u32,
+	/// The number of elements, for arrays
+	pub size: i32,
+}
+
+/// A value that can be uploaded to a uniform
+pub trait UniformValue {
+	/// The GL types of the uniforms this can be uploaded to
+	const GL_TYPES: &'static [u32];
+
+	unsafe fn upload(&self, location: i32);
+}
+
+impl UniformValue for f32 {
+	const GL_TYPES: &'static [u32] = &[gl::FLOAT];
+	unsafe fn upload(&self, location: i32) { gl::Uniform1f(location, *self); }
+}
+
+impl UniformValue for i32 {
+	const GL_TYPES: &'static [u32] = &[gl::INT, gl::BOOL, gl::SAMPLER_2D, gl::SAMPLER_CUBE];
+	unsafe fn upload(&self, location: i32) { gl::Uniform1i(location, *self); }
+}
+
+impl UniformValue for Vec2 {
+	const GL_TYPES: &'static [u32] = &[gl::FLOAT_VEC2];
+	unsafe fn upload(&self, location: i32) { gl::Uniform2f(location, self.x, self.y); }
+}
+
+impl UniformValue for Vec3 {
+	const GL_TYPES: &'static [u32] = &[gl::FLOAT_VEC3];
+	unsafe fn upload(&self, location: i32) { gl::Uniform3f(location, self.x, self.y, self.z); }
+}
+
+impl UniformValue for Vec4 {
+	const GL_TYPES: &'static [u32] = &[gl::FLOAT_VEC4];
+	unsafe fn upload(&self, location: i32) { gl::Uniform4f(location, self.x, self.y, self.z, self.w); }
+}
+
+impl UniformValue for Color {
+	const GL_TYPES: &'static [u32] = &[gl::FLOAT_VEC4];
+	unsafe fn upload(&self, location: i32) { gl::Uniform4f(location, self.r, self.g, self.b, self.a); }
+}
+
+impl UniformValue for Mat4 {
+	const GL_TYPES: &'static [u32] = &[gl::FLOAT_MAT4];
+	unsafe fn upload(&self, location: i32) {
+		gl::UniformMatrix4fv(location, 1, 0, self.transpose().rows.as_ptr() as *const f32);
+	}
+}
+
+pub fn glsl_type_name(ty: u32) -> &'static str {
+	match ty {
+		gl::FLOAT => "float",
+		gl::FLOAT_VEC2 => "vec2",
+		gl::FLOAT_VEC3 => "vec3",
+		gl::FLOAT_VEC4 => "vec4",
+		gl::INT => "int",
+		gl::BOOL => "bool",
+		gl::FLOAT_MAT2 => "mat2",
+		gl::FLOAT_MAT3 => "mat3",
+		gl::FLOAT_MAT4 => "mat4",
+		gl::SAMPLER_2D => "sampler2D",
+		gl::SAMPLER_CUBE => "samplerCube",
+		_ => "unknown",
+	}
+}
+
+/// A uniform location whose type was checked when it was fetched with Shader::get_uniform.
+/// Handles to uniforms the program doesn't use have location -1, and setting them does nothing
+pub struct UniformHandle<T: UniformValue> {
+	pub program: u32,
+	pub location: i32,
+	_marker: PhantomData<T>,
+}
+
+impl<T> UniformHandle<T> where T: UniformValue {
+	pub fn new(program: u32, location: i32) -> Self {
+		UniformHandle { program, location, _marker: PhantomData }
+	}
+
+	pub fn is_active(&self) -> bool { self.location >= 0 }
+
+	/// The program the handle was fetched from must be bound
+	pub fn set(&self, v: &T) {
+		unsafe {
+			v.upload(self.location);
+		}
+	}
+}
+
+impl<T> Copy for UniformHandle<T> where T: UniformValue {}
+impl<T> Clone for UniformHandle<T> where T: UniformValue {
+	fn clone(&self) -> Self { *self }
+}
@@ -1,55 +1,324 @@
 use bindings::emscripten::*;
 use bindings::gl;
+use error::Error;
+use events::Event;
+use render_state::RenderState;
+use rendering::mesh::invalidate_attribute_state;
+use rendering::shader::invalidate_uniform_cache;
+use rendering::material::invalidate_material_state;
+use rendering::resources::ResourceRegistry;
 
 use common::color::*;
 use common::vector::Vec2i;
 
+use extensions::*;
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::ffi::CString;
+
+thread_local! {
+	// Mirror the current context's, for code that doesn't have the context to hand
+	static CAPABILITIES: Cell<Capabilities> = Cell::new(Capabilities::default());
+	static EXTENSIONS: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+
+	// Filled by the context lost/restored callbacks, which are handed the context handle
+	static LOST_CONTEXTS: RefCell<HashSet<EMSCRIPTEN_WEBGL_CONTEXT_HANDLE>> = RefCell::new(HashSet::new());
+	static CONTEXT_EVENTS: RefCell<Vec<(EMSCRIPTEN_WEBGL_CONTEXT_HANDLE, Event)>> = RefCell::new(Vec::new());
+}
+
+/// Whether the current context supports 32-bit element indices
+pub fn element_index_uint_enabled() -> bool {
+	capabilities().element_index_uint
+}
+
+/// The capabilities of the current context
+pub fn capabilities() -> Capabilities {
+	CAPABILITIES.with(|c| c.get())
+}
+
+/// A handle for `E` if the current context has it enabled
+pub fn current_extension<E>() -> Option<E> where E: Extension {
+	let enabled = capabilities().is_webgl2() && E::CORE_IN_WEBGL2
+		|| EXTENSIONS.with(|e| e.borrow().contains(E::NAME));
+
+	if enabled { Some(E::assume_enabled()) } else { None }
+}
+
+/// What a context supports. Under WebGL1, features that need an extension are only available
+/// once it's been enabled with `WebGLContext::enable_extension`
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Capabilities {
+	/// 1 or 2, or 0 before any context has been created
+	pub webgl_version: i32,
+
+	pub element_index_uint: bool,
+	pub vertex_array_objects: bool,
+	pub instancing: bool,
+	pub draw_buffers: bool,
+	pub uniform_buffers: bool,
+	pub texture_3d: bool,
+	pub sampler_objects: bool,
+	pub float_textures: bool,
+	pub depth_textures: bool,
+	pub standard_derivatives: bool,
+	/// Float textures can be rendered to. Needs EXT_color_buffer_float, which is WebGL2 only
+	pub color_buffer_float: bool,
+
+	pub max_texture_size: i32,
+	pub max_texture_units: i32,
+	pub max_vertex_attribs: i32,
+	/// 1 without draw_buffers
+	pub max_draw_buffers: i32,
+	pub max_color_attachments: i32,
+	pub max_samples: i32,
+	pub max_3d_texture_size: i32,
+	pub max_array_texture_layers: i32,
+	pub max_uniform_buffer_bindings: i32,
+	pub max_uniform_block_size: i32,
+	pub uniform_buffer_offset_alignment: i32,
+}
+
+impl Capabilities {
+	pub fn is_webgl2(&self) -> bool { self.webgl_version >= 2 }
+
+	// Expects the context being queried to be current
+	fn query(webgl_version: i32) -> Self {
+		let webgl2 = webgl_version >= 2;
+		let webgl2_limit = |pname, fallback| if webgl2 { get_integer(pname) } else { fallback };
+
+		Capabilities {
+			webgl_version,
+
+			element_index_uint: webgl2,
+			vertex_array_objects: webgl2,
+			instancing: webgl2,
+			draw_buffers: webgl2,
+			uniform_buffers: webgl2,
+			texture_3d: webgl2,
+			sampler_objects: webgl2,
+			float_textures: webgl2,
+			depth_textures: webgl2,
+			standard_derivatives: webgl2,
+			color_buffer_float: false,
+
+			max_texture_size: get_integer(gl::MAX_TEXTURE_SIZE),
+			max_texture_units: get_integer(gl::MAX_TEXTURE_IMAGE_UNITS),
+			max_vertex_attribs: get_integer(gl::MAX_VERTEX_ATTRIBS),
+			max_draw_buffers: webgl2_limit(gl::MAX_DRAW_BUFFERS, 1),
+			max_color_attachments: webgl2_limit(gl::MAX_COLOR_ATTACHMENTS, 1),
+			max_samples: webgl2_limit(gl::MAX_SAMPLES, 0),
+			max_3d_texture_size: webgl2_limit(gl::MAX_3D_TEXTURE_SIZE, 0),
+			max_array_texture_layers: webgl2_limit(gl::MAX_ARRAY_TEXTURE_LAYERS, 0),
+			max_uniform_buffer_bindings: webgl2_limit(gl::MAX_UNIFORM_BUFFER_BINDINGS, 0),
+			max_uniform_block_size: webgl2_limit(gl::MAX_UNIFORM_BLOCK_SIZE, 0),
+			uniform_buffer_offset_alignment: webgl2_limit(gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT, 0),
+		}
+	}
+
+	// Marks what enabling extension `name` makes available. Expects its context to be current
+	fn apply_extension(&mut self, name: &str) {
+		match name {
+			"OES_element_index_uint" => self.element_index_uint = true,
+			"OES_texture_float" => self.float_textures = true,
+			"OES_vertex_array_object" => self.vertex_array_objects = true,
+			"ANGLE_instanced_arrays" => self.instancing = true,
+			"WEBGL_depth_texture" => self.depth_textures = true,
+			"OES_standard_derivatives" => self.standard_derivatives = true,
+			"EXT_color_buffer_float" => self.color_buffer_float = true,
+
+			"WEBGL_draw_buffers" => {
+				self.draw_buffers = true;
+				self.max_draw_buffers = get_integer(gl::MAX_DRAW_BUFFERS);
+				self.max_color_attachments = get_integer(gl::MAX_COLOR_ATTACHMENTS);
+			}
+
+			_ => {}
+		}
+	}
+}
+
+fn get_integer(pname: u32) -> i32 {
+	let mut value = 0;
+	unsafe { gl::GetIntegerv(pname, &mut value); }
+	value
+}
+
 pub struct WebGLContext {
 	ems_context: EMSCRIPTEN_WEBGL_CONTEXT_HANDLE,
+	target: String,
+
+	// Both updated as extensions are enabled
+	capabilities: Cell<Capabilities>,
+	extensions: RefCell<HashSet<String>>,
+
+	render_state: Cell<RenderState>,
+	stencil_clear_value: Cell<u8>,
 }
 
 impl WebGLContext {
 	pub fn new(alpha: bool) -> Self {
-		use std::mem::uninitialized;
-
-		let ems_context_handle = unsafe {
-			let mut attribs = uninitialized();
-			emscripten_webgl_init_context_attributes(&mut attribs);
-			attribs.alpha = if alpha {1} else {0};
-			attribs.stencil = 1;
-			attribs.antialias = 1;
-			attribs.preserveDrawingBuffer = 0;
-			attribs.enableExtensionsByDefault = 0;
-
-			emscripten_webgl_create_context(b"canvas\0".as_ptr() as _, &attribs)
-		};
+		WebGLContext::try_new(alpha)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
 
-		match ems_context_handle {
-			EMSCRIPTEN_RESULT_NOT_SUPPORTED => {
-				panic!("WebGL not supported");
-			}
+	pub fn try_new(alpha: bool) -> Result<Self, Error> {
+		WebGLContext::default_builder(alpha).try_finalize()
+	}
 
-			EMSCRIPTEN_RESULT_FAILED_NOT_DEFERRED => {
-				panic!("WebGL context creation failed (FAILED_NOT_DEFERRED)");
-			}
+	/// Creates a WebGL2 context if the browser supports it, and a WebGL1 context otherwise.
+	/// Check `capabilities()` to see which you got. WebGL2 needs linking with `-s USE_WEBGL2=1`
+	pub fn new_webgl2(alpha: bool) -> Self {
+		WebGLContext::try_new_webgl2(alpha)
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
 
-			EMSCRIPTEN_RESULT_FAILED => {
-				panic!("WebGL context creation failed (FAILED)");
-			}
+	pub fn try_new_webgl2(alpha: bool) -> Result<Self, Error> {
+		WebGLContext::default_builder(alpha).prefer_webgl2().try_finalize()
+	}
 
-			x if x < 0 => {
-				panic!("WebGL context creation failed ({})", x);
-			}
+	fn default_builder(alpha: bool) -> WebGLContextBuilder {
+		WebGLContextBuilder::new()
+			.alpha(alpha)
+			.stencil(true)
+			.antialias(true)
+			.preserve_drawing_buffer(false)
+			.enable_extensions_by_default(false)
+	}
 
-			_ => {}
+	/// Makes this the context GL calls go to, for when there's more than one
+	pub fn make_current(&self) -> Result<(), Error> {
+		let result = unsafe { emscripten_webgl_make_context_current(self.ems_context) };
+		if result != EMSCRIPTEN_RESULT_SUCCESS {
+			return Err(Error::ContextCreation(result));
+		}
+
+		CAPABILITIES.with(|c| c.set(self.capabilities.get()));
+		EXTENSIONS.with(|e| *e.borrow_mut() = self.extensions.borrow().clone());
+		invalidate_attribute_state();
+		Ok(())
+	}
+
+	/// Enables `E` if the browser supports it, returning a handle that proves it's available
+	pub fn enable_extension<E>(&self) -> Option<E> where E: Extension {
+		let enabled = self.is_webgl2() && E::CORE_IN_WEBGL2
+			|| self.enable_extension_by_name(E::NAME);
+
+		if enabled { Some(E::assume_enabled()) } else { None }
+	}
+
+	pub fn try_enable_extension<E>(&self) -> Result<E, Error> where E: Extension {
+		self.enable_extension::<E>()
+			.ok_or(Error::MissingExtension(E::NAME))
+	}
+
+	/// A handle for `E` if it's already been enabled
+	pub fn extension<E>(&self) -> Option<E> where E: Extension {
+		let enabled = self.is_webgl2() && E::CORE_IN_WEBGL2
+			|| self.has_extension(E::NAME);
+
+		if enabled { Some(E::assume_enabled()) } else { None }
+	}
+
+	/// Enables any extension, returning whether it's supported. Known extensions also update
+	/// `capabilities()`
+	pub fn enable_extension_by_name(&self, name: &str) -> bool {
+		if self.has_extension(name) { return true }
+
+		let c_name = CString::new(name).unwrap();
+		let supported = unsafe { emscripten_webgl_enable_extension(self.ems_context, c_name.as_ptr()) != 0 };
+		if !supported { return false }
+
+		self.extensions.borrow_mut().insert(name.to_string());
+
+		// Limits are queried from the current context, so switch if need be
+		let previous = unsafe { emscripten_webgl_get_current_context() };
+		if previous != self.ems_context {
+			unsafe { emscripten_webgl_make_context_current(self.ems_context); }
 		}
 
-		if unsafe {emscripten_webgl_make_context_current(ems_context_handle) != EMSCRIPTEN_RESULT_SUCCESS} {
-			panic!("Failed to make webgl context current");
+		let mut capabilities = self.capabilities.get();
+		capabilities.apply_extension(name);
+		self.capabilities.set(capabilities);
+
+		if previous != self.ems_context {
+			unsafe { emscripten_webgl_make_context_current(previous); }
+		} else {
+			CAPABILITIES.with(|c| c.set(capabilities));
+			EXTENSIONS.with(|e| e.borrow_mut().insert(name.to_string()));
 		}
 
-		WebGLContext { ems_context: ems_context_handle }
+		true
+	}
+
+	/// Whether extension `name` has been enabled on this context
+	pub fn has_extension(&self, name: &str) -> bool {
+		self.extensions.borrow().contains(name)
+	}
+
+	/// Whether the browser has taken this context away. Nothing is drawn until it's restored
+	pub fn is_lost(&self) -> bool {
+		LOST_CONTEXTS.with(|l| l.borrow().contains(&self.ems_context))
+	}
+
+	/// Moves any ContextLost and ContextRestored events for this context onto `queue`.
+	/// Should be called once a frame
+	pub fn poll_events(&self, queue: &mut Vec<Event>) {
+		CONTEXT_EVENTS.with(|e| {
+			let events = ::std::mem::replace(&mut *e.borrow_mut(), Vec::new());
+
+			for (ems_context, event) in events {
+				if ems_context == self.ems_context {
+					queue.push(event);
+				} else {
+					e.borrow_mut().push((ems_context, event));
+				}
+			}
+		});
 	}
+
+	/// Gets the context going again after a ContextRestored event. Extensions that were enabled
+	/// before the loss are enabled again, then everything in `registry` is recreated. Extensions
+	/// the restored context no longer supports are returned as Error::ExtensionsLost afterwards
+	pub fn restore(&self, registry: &ResourceRegistry) -> Result<(), Error> {
+		self.make_current()?;
+
+		// Losing the context reset all GL state
+		self.render_state.set(RenderState::new());
+		unsafe { gl::ClearStencil(self.stencil_clear_value.get() as i32) }
+
+		let extensions: Vec<String> = self.extensions.borrow_mut().drain().collect();
+		self.capabilities.set(Capabilities::query(self.capabilities.get().webgl_version));
+
+		CAPABILITIES.with(|c| c.set(self.capabilities.get()));
+		EXTENSIONS.with(|e| e.borrow_mut().clear());
+
+		let lost: Vec<String> = extensions.into_iter()
+			.filter(|name| !self.enable_extension_by_name(name))
+			.collect();
+
+		// Program handles are reused by the new context, so anything keyed by them is stale
+		invalidate_uniform_cache();
+		invalidate_material_state();
+
+		registry.restore_all()?;
+
+		if !lost.is_empty() {
+			return Err(Error::ExtensionsLost(lost));
+		}
+
+		Ok(())
+	}
+
+	pub fn is_current(&self) -> bool {
+		unsafe { emscripten_webgl_get_current_context() == self.ems_context }
+	}
+
+	/// The selector of the canvas this context renders to
+	pub fn target(&self) -> &str { &self.target }
+
+	pub fn capabilities(&self) -> Capabilities { self.capabilities.get() }
+	pub fn is_webgl2(&self) -> bool { self.capabilities.get().is_webgl2() }
 	
 	pub fn clear_color(&self) {
 		unsafe {
@@ -77,31 +346,211 @@ impl WebGLContext {
 		}
 	}
 
+	pub fn render_state(&self) -> RenderState { self.render_state.get() }
+
+	/// Only makes the GL calls needed to change from the current state. Expects this context
+	/// to be current
+	pub fn set_render_state(&self, state: RenderState) {
+		state.apply(Some(&self.render_state.get()));
+		self.render_state.set(state);
+	}
+
+	/// Makes every GL call for `state`, for when GL state has been changed without going through
+	/// the context
+	pub fn reset_render_state(&self, state: RenderState) {
+		state.apply(None);
+		self.render_state.set(state);
+	}
+
 	pub fn enable_color_write(&self) {
-		unsafe { gl::ColorMask(1, 1, 1, 1) }
+		self.set_render_state(self.render_state().color_mask(true, true, true, true));
 	}
 	pub fn disable_color_write(&self) {
-		unsafe { gl::ColorMask(0, 0, 0, 0) }
+		self.set_render_state(self.render_state().color_mask(false, false, false, false));
 	}
 
 	pub fn enable_depth_write(&self) {
-		unsafe { gl::DepthMask(1) }
+		self.set_render_state(self.render_state().depth_write(true));
 	}
 	pub fn disable_depth_write(&self) {
-		unsafe { gl::DepthMask(0) }
+		self.set_render_state(self.render_state().depth_write(false));
 	}
 
+	/// Only sets the stencil func, op and masks - RenderState::stencil_test turns the test on
 	pub fn set_stencil(&self, params: StencilParams) {
+		self.set_render_state(self.render_state().stencil_params(params));
+	}
+
+	pub fn set_stencil_separate(&self, front: StencilParams, back: StencilParams) {
+		self.set_render_state(self.render_state().stencil_params_separate(front, back));
+	}
+
+	/// The value clear_stencil and clear_all fill the stencil buffer with
+	pub fn set_stencil_clear_value(&self, value: u8) {
+		self.stencil_clear_value.set(value);
+		unsafe { gl::ClearStencil(value as i32) }
+	}
+
+	/// Only clears the bits in the current stencil write mask
+	pub fn clear_stencil(&self) {
 		unsafe {
-			gl::StencilFunc(params.condition, params.reference as i32, 0xff);
-			gl::StencilOp(params.stencil_fail, params.depth_fail, params.pass);
+			gl::Clear(gl::STENCIL_BUFFER_BIT);
+		}
+	}
+}
+
+/// Creates a WebGLContext with any of the attributes emscripten exposes. Attributes that aren't
+/// set keep emscripten's defaults, and the context is created on `canvas` unless `target` says
+/// otherwise
+pub struct WebGLContextBuilder {
+	target: String,
+	attributes: EmscriptenWebGLContextAttributes,
+	fallback_to_webgl1: bool,
+}
+
+impl WebGLContextBuilder {
+	pub fn new() -> Self {
+		use std::mem::MaybeUninit;
+
+		let attributes = unsafe {
+			let mut attributes = MaybeUninit::uninit();
+			emscripten_webgl_init_context_attributes(attributes.as_mut_ptr());
+			attributes.assume_init()
+		};
+
+		WebGLContextBuilder {
+			target: "canvas".to_string(),
+			attributes,
+			fallback_to_webgl1: false,
 		}
 	}
+
+	/// A CSS selector for the canvas, or one of emscripten's special targets like `#canvas`
+	pub fn target(mut self, target: &str) -> Self { self.target = target.to_string(); self }
+
+	pub fn alpha(mut self, v: bool) -> Self { self.attributes.alpha = v as i32; self }
+	pub fn depth(mut self, v: bool) -> Self { self.attributes.depth = v as i32; self }
+	pub fn stencil(mut self, v: bool) -> Self { self.attributes.stencil = v as i32; self }
+	pub fn antialias(mut self, v: bool) -> Self { self.attributes.antialias = v as i32; self }
+	pub fn premultiplied_alpha(mut self, v: bool) -> Self { self.attributes.premultipliedAlpha = v as i32; self }
+	pub fn preserve_drawing_buffer(mut self, v: bool) -> Self { self.attributes.preserveDrawingBuffer = v as i32; self }
+	pub fn prefer_low_power(mut self, v: bool) -> Self { self.attributes.preferLowPowerToHighPerformance = v as i32; self }
+	pub fn fail_if_major_performance_caveat(mut self, v: bool) -> Self { self.attributes.failIfMajorPerformanceCaveat = v as i32; self }
+	pub fn enable_extensions_by_default(mut self, v: bool) -> Self { self.attributes.enableExtensionsByDefault = v as i32; self }
+	pub fn explicit_swap_control(mut self, v: bool) -> Self { self.attributes.explicitSwapControl = v as i32; self }
+
+	/// Requests a specific WebGL version. Creation fails if it isn't available
+	pub fn version(mut self, major: i32, minor: i32) -> Self {
+		self.attributes.majorVersion = major;
+		self.attributes.minorVersion = minor;
+		self.fallback_to_webgl1 = false;
+		self
+	}
+
+	/// Requests WebGL2, falling back to WebGL1 if it isn't available
+	pub fn prefer_webgl2(mut self) -> Self {
+		self = self.version(2, 0);
+		self.fallback_to_webgl1 = true;
+		self
+	}
+
+	pub fn attributes(&self) -> &EmscriptenWebGLContextAttributes { &self.attributes }
+
+	pub fn finalize(self) -> WebGLContext {
+		self.try_finalize()
+			.unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// Creates the context and makes it current
+	pub fn try_finalize(self) -> Result<WebGLContext, Error> {
+		match self.create(&self.attributes) {
+			Err(error) if self.fallback_to_webgl1 && self.attributes.majorVersion > 1 => {
+				let attributes = EmscriptenWebGLContextAttributes { majorVersion: 1, minorVersion: 0, ..self.attributes };
+
+				// Why WebGL2 failed is more useful to know than why the fallback did
+				self.create(&attributes).map_err(|_| error)
+			}
+
+			result => result,
+		}
+	}
+
+	fn create(&self, attributes: &EmscriptenWebGLContextAttributes) -> Result<WebGLContext, Error> {
+		let target = CString::new(self.target.as_str()).unwrap();
+		let ems_context = unsafe { emscripten_webgl_create_context(target.as_ptr(), attributes) };
+
+		if ems_context <= 0 {
+			return Err(Error::ContextCreation(ems_context));
+		}
+
+		let result = unsafe { emscripten_webgl_make_context_current(ems_context) };
+		if result != EMSCRIPTEN_RESULT_SUCCESS {
+			// Otherwise the canvas stays taken, and a fallback context can't be created on it
+			unsafe { emscripten_webgl_destroy_context(ems_context); }
+			return Err(Error::ContextCreation(result));
+		}
+
+		unsafe {
+			emscripten_set_webglcontextlost_callback(target.as_ptr(), ems_context as _, 1, Some(on_context_lost));
+			emscripten_set_webglcontextrestored_callback(target.as_ptr(), ems_context as _, 1, Some(on_context_restored));
+		}
+
+		let capabilities = Capabilities::query(attributes.majorVersion);
+		CAPABILITIES.with(|c| c.set(capabilities));
+		EXTENSIONS.with(|e| e.borrow_mut().clear());
+		invalidate_attribute_state();
+
+		let context = WebGLContext {
+			ems_context,
+			target: self.target.clone(),
+
+			capabilities: Cell::new(capabilities),
+			extensions: RefCell::new(HashSet::new()),
+
+			render_state: Cell::new(RenderState::new()),
+			stencil_clear_value: Cell::new(0),
+		};
+
+		// Extensions emscripten enabled behind our back still need tracking
+		if attributes.enableExtensionsByDefault != 0 {
+			for name in KNOWN_EXTENSIONS.iter() {
+				context.enable_extension_by_name(name);
+			}
+		}
+
+		Ok(context)
+	}
+}
+
+unsafe extern "C"
+fn on_context_lost(_: i32, _: *const CVoid, ud: *mut CVoid) -> i32 {
+	let ems_context = ud as EMSCRIPTEN_WEBGL_CONTEXT_HANDLE;
+
+	LOST_CONTEXTS.with(|l| l.borrow_mut().insert(ems_context));
+	CONTEXT_EVENTS.with(|e| e.borrow_mut().push((ems_context, Event::ContextLost)));
+
+	// Has emscripten call preventDefault, without which the context is never restored
+	1
+}
+
+unsafe extern "C"
+fn on_context_restored(_: i32, _: *const CVoid, ud: *mut CVoid) -> i32 {
+	let ems_context = ud as EMSCRIPTEN_WEBGL_CONTEXT_HANDLE;
+
+	LOST_CONTEXTS.with(|l| l.borrow_mut().remove(&ems_context));
+	CONTEXT_EVENTS.with(|e| e.borrow_mut().push((ems_context, Event::ContextRestored)));
+
+	1
 }
 
+/// The stencil test and ops for one face. `condition` compares `reference` against the stencil
+/// value, both masked by `read_mask`. Only bits in `write_mask` are changed by the ops
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct StencilParams {
 	pub condition: u32,
 	pub reference: u8,
+	pub read_mask: u8,
+	pub write_mask: u8,
 
 	pub stencil_fail: u32,
 	pub depth_fail: u32,
@@ -113,6 +562,8 @@ impl StencilParams {
 		StencilParams {
 			reference,
 			condition: gl::NEVER,
+			read_mask: 0xff,
+			write_mask: 0xff,
 
 			stencil_fail: gl::KEEP,
 			depth_fail: gl::KEEP,
@@ -120,6 +571,13 @@ impl StencilParams {
 		}
 	}
 
+	pub fn read_mask(self, read_mask: u8) -> Self { StencilParams { read_mask, ..self } }
+	pub fn write_mask(self, write_mask: u8) -> Self { StencilParams { write_mask, ..self } }
+
+	pub fn on_stencil_fail(self, op: u32) -> Self { StencilParams { stencil_fail: op, ..self } }
+	pub fn on_depth_fail(self, op: u32) -> Self { StencilParams { depth_fail: op, ..self } }
+	pub fn on_pass(self, op: u32) -> Self { StencilParams { pass: op, ..self } }
+
 
 	pub fn pass_if(self, condition: u32) -> Self {
 		StencilParams { condition, ..self }
@@ -146,4 +604,140 @@ impl StencilParams {
 	pub fn invert(self) -> Self {
 		Self { pass: gl::INVERT, ..self }
 	}
-}
\ No newline at end of file
+
+	pub fn increment_wrap(self) -> Self {
+		Self { pass: gl::INCR_WRAP, ..self }
+	}
+
+	pub fn decrement_wrap(self) -> Self {
+		Self { pass: gl::DECR_WRAP, ..self }
+	}
+}
+
+#[cfg(all(test, feature = "headless"))] mod tests {
+	use bindings::headless;
+	use super::*;
+
+	#[test]
+	fn webgl2_fallback() {
+		headless::reset();
+
+		let ctx = WebGLContext::new_webgl2(false);
+		let caps = ctx.capabilities();
+		assert!(ctx.is_webgl2());
+		assert!(caps.vertex_array_objects && caps.instancing && caps.uniform_buffers && caps.sampler_objects);
+		assert!(caps.element_index_uint);
+		assert_eq!(caps.max_draw_buffers, 8);
+		assert_eq!(capabilities(), caps);
+
+		// Core in WebGL2, so never asked for
+		assert!(headless::calls_named("emscripten_webgl_enable_extension").is_empty());
+
+		headless::reset();
+		headless::with_state(|s| s.webgl2_supported = false);
+
+		let ctx = WebGLContext::new_webgl2(false);
+		let caps = ctx.capabilities();
+		assert_eq!(caps.webgl_version, 1);
+		assert!(!caps.vertex_array_objects && !caps.texture_3d);
+		assert!(!caps.element_index_uint);
+		assert_eq!((caps.max_draw_buffers, caps.max_3d_texture_size), (1, 0));
+		assert_eq!(caps.max_texture_size, 4096);
+
+		let versions: Vec<i32> = headless::with_state(|s| s.contexts.values().map(|c| c.attributes.majorVersion).collect());
+		assert_eq!(versions, vec![1]);
+	}
+
+	#[test]
+	fn failed_creation() {
+		headless::reset();
+		headless::with_state(|s| s.make_current_fails = true);
+
+		match WebGLContextBuilder::new().prefer_webgl2().try_finalize() {
+			Err(Error::ContextCreation(EMSCRIPTEN_RESULT_FAILED)) => {}
+			_ => panic!("Expected context creation to fail"),
+		}
+
+		// Neither attempt leaves a context behind
+		assert_eq!(headless::calls_named("emscripten_webgl_create_context").len(), 2);
+		assert!(headless::with_state(|s| s.contexts.is_empty()));
+
+		// The WebGL2 error is the one reported
+		headless::with_state(|s| s.webgl2_supported = false);
+		match WebGLContextBuilder::new().prefer_webgl2().try_finalize() {
+			Err(Error::ContextCreation(0)) => {}
+			_ => panic!("Expected the WebGL2 error"),
+		}
+
+		// Nothing to fall back to from WebGL1
+		headless::take_calls();
+		assert!(WebGLContextBuilder::new().prefer_webgl2().version(1, 0).try_finalize().is_err());
+		assert_eq!(headless::calls_named("emscripten_webgl_create_context").len(), 1);
+	}
+
+	#[test]
+	fn builder_and_multiple_contexts() {
+		headless::reset();
+
+		let first = WebGLContextBuilder::new()
+			.target("#first")
+			.depth(false)
+			.premultiplied_alpha(false)
+			.explicit_swap_control(true)
+			.finalize();
+
+		let second = WebGLContextBuilder::new()
+			.target("#second")
+			.version(2, 0)
+			.finalize();
+
+		let contexts = headless::with_state(|s| s.contexts.clone());
+		assert_eq!(contexts.len(), 2);
+
+		let first_ctx = contexts.values().find(|c| c.target == "#first").unwrap();
+		assert_eq!((first_ctx.attributes.depth, first_ctx.attributes.premultipliedAlpha), (0, 0));
+		assert_eq!(first_ctx.attributes.explicitSwapControl, 1);
+		assert_eq!(first_ctx.attributes.majorVersion, 1);
+		assert_eq!(first.target(), "#first");
+
+		// The most recently created context is current
+		assert!(second.is_current() && !first.is_current());
+		assert!(capabilities().is_webgl2());
+
+		first.make_current().unwrap();
+		assert!(first.is_current());
+		assert_eq!(capabilities().webgl_version, 1);
+
+		headless::with_state(|s| s.webgl2_supported = false);
+		match WebGLContextBuilder::new().version(2, 0).try_finalize() {
+			Err(Error::ContextCreation(_)) => {}
+			Err(e) => panic!("Unexpected error {}", e),
+			Ok(_) => panic!("Created WebGL2 context without support"),
+		}
+
+		// Failing to create a context leaves the current one alone
+		assert!(first.is_current());
+	}
+
+	#[test]
+	fn restore_without_extension() {
+		headless::reset();
+
+		let ctx = WebGLContext::new(false);
+		ctx.enable_extension::<OesElementIndexUint>().unwrap();
+		ctx.enable_extension::<OesVertexArrayObject>().unwrap();
+
+		headless::lose_context(1);
+		headless::with_state(|s| s.supported_extensions.remove("OES_vertex_array_object"));
+		headless::restore_context(1);
+
+		match ctx.restore(&ResourceRegistry::new()) {
+			Err(Error::ExtensionsLost(ref names)) if names == &["OES_vertex_array_object"] => {}
+			x => panic!("Unexpected result {:?}", x),
+		}
+
+		// Whatever's still supported is enabled again
+		assert!(ctx.has_extension("OES_element_index_uint"));
+		assert!(!ctx.capabilities().vertex_array_objects);
+	}
+}
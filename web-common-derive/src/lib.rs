@@ -0,0 +1,151 @@
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate syn;
+#[macro_use] extern crate quote;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use syn::{Attribute, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
+
+// Generates a Vertex impl with one attribute binding per field, in declaration order.
+// Widths and component types come from each field type's VertexAttribute impl, and offsets
+// are taken from the struct itself so reordering fields can't break the layout.
+//
+// Each binding is named after its field, except the first which is named `position` to match
+// ShaderBuilder. `#[vertex(name = "...")]` on a field overrides the name, and
+// `#[vertex(shader_attributes)]` on the struct additionally implements ShaderAttributes.
+// `#[vertex(per_instance)]` gives the layout a divisor of 1 for use with InstanceBuffer, and
+// leaves the first field with its own name.
+//
+// Vertices are uploaded as their raw bytes, so the struct has to be #[repr(C)] for the field
+// order to be fixed and the offsets to mean anything.
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+	let input: DeriveInput = syn::parse(input).unwrap();
+
+	let name = &input.ident;
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+	let fields = match input.data {
+		Data::Struct(ref s) => match s.fields {
+			Fields::Named(ref fields) => &fields.named,
+			_ => panic!("#[derive(Vertex)] requires a struct with named fields"),
+		},
+
+		_ => panic!("#[derive(Vertex)] can only be used on structs"),
+	};
+
+	if !is_repr_c(&input.attrs) {
+		panic!("#[derive(Vertex)] requires #[repr(C)] on {}", name);
+	}
+
+	let per_instance = has_flag(&input.attrs, "per_instance");
+
+	let bindings = fields.iter().enumerate().map(|(index, field)| {
+		let attr_name = attribute_name(index, field, per_instance);
+		let index = index as u32;
+		let ident = field.ident.as_ref().unwrap();
+		let ty = &field.ty;
+
+		quote! {
+			.add_typed_binding(#index,
+				<#ty as VertexAttribute>::WIDTH,
+				<#ty as VertexAttribute>::COMPONENT_TYPE,
+				<#ty as VertexAttribute>::NORMALIZED,
+				::std::mem::offset_of!(Self, #ident) as u32)
+			.named(#attr_name)
+		}
+	});
+
+	let divisor = if per_instance { 1u32 } else { 0u32 };
+
+	let mut output = quote! {
+		impl #impl_generics ::web_common::rendering::mesh::Vertex for #name #ty_generics #where_clause {
+			fn get_layout() -> ::web_common::rendering::mesh::VertexLayout {
+				use ::web_common::rendering::mesh::{VertexLayout, VertexAttribute};
+
+				VertexLayout::new::<Self>()
+					.divisor(#divisor)
+					#(#bindings)*
+			}
+		}
+	};
+
+	if has_flag(&input.attrs, "shader_attributes") {
+		let attributes = fields.iter().enumerate().map(|(index, field)| {
+			let attr_name = attribute_name(index, field, per_instance);
+			let ty = &field.ty;
+
+			quote! {
+				(#attr_name, <#ty as VertexAttribute>::GLSL_TYPE)
+			}
+		});
+
+		output.extend(quote! {
+			impl #impl_generics ::web_common::rendering::mesh::ShaderAttributes for #name #ty_generics #where_clause {
+				fn shader_attributes() -> Vec<(&'static str, &'static str)> {
+					use ::web_common::rendering::mesh::VertexAttribute;
+
+					vec![ #(#attributes),* ]
+				}
+			}
+		});
+	}
+
+	output.into()
+}
+
+fn attribute_name(index: usize, field: &syn::Field, per_instance: bool) -> syn::LitStr {
+	get_name(&field.attrs).unwrap_or_else(|| {
+		let name = match index {
+			0 if !per_instance => "position".to_string(),
+			_ => field.ident.as_ref().unwrap().to_string(),
+		};
+
+		syn::LitStr::new(&name, Span::call_site())
+	})
+}
+
+fn vertex_attr_items(attrs: &[Attribute]) -> Vec<NestedMeta> {
+	attrs.iter()
+		.filter(|a| a.path.segments.len() == 1 && a.path.segments[0].ident == "vertex")
+		.flat_map(|a| match a.parse_meta() {
+			Ok(Meta::List(list)) => list.nested.into_iter().collect::<Vec<_>>(),
+			_ => panic!("Malformed vertex attribute, expected #[vertex(...)]"),
+		})
+		.collect()
+}
+
+fn has_flag(attrs: &[Attribute], flag: &str) -> bool {
+	vertex_attr_items(attrs).iter()
+		.any(|item| match *item {
+			NestedMeta::Meta(Meta::Word(ref ident)) => ident == flag,
+			_ => false,
+		})
+}
+
+fn get_name(attrs: &[Attribute]) -> Option<syn::LitStr> {
+	vertex_attr_items(attrs).into_iter()
+		.filter_map(|item| match item {
+			NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.ident == "name" => match nv.lit {
+				Lit::Str(ref s) => Some(s.clone()),
+				_ => panic!("Vertex attribute name must be a string"),
+			},
+
+			_ => None,
+		})
+		.next()
+}
+
+fn is_repr_c(attrs: &[Attribute]) -> bool {
+	attrs.iter()
+		.filter(|a| a.path.segments.len() == 1 && a.path.segments[0].ident == "repr")
+		.filter_map(|a| match a.parse_meta() {
+			Ok(Meta::List(list)) => Some(list.nested),
+			_ => None,
+		})
+		.any(|nested| nested.iter().any(|item| match *item {
+			NestedMeta::Meta(Meta::Word(ref ident)) => ident == "C",
+			_ => false,
+		}))
+}